@@ -0,0 +1,124 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use pest::error::{Error, ErrorVariant, LineColLocation};
+use pest::{Position, RuleType};
+
+/// An error at a position of the input, or of a file it includes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// The included file of the error, if it is not in the input
+    pub file: Option<String>,
+    pub line: usize,
+    pub column: usize,
+    /// The text of the line, to point at the column
+    pub line_text: String,
+    pub message: String,
+}
+
+impl ParseError {
+    pub(crate) fn at(pos: Position, message: impl Into<String>) -> Self {
+        let (line, column) = pos.line_col();
+        Self {
+            file: None,
+            line,
+            column,
+            line_text: pos
+                .line_of()
+                .trim_end_matches(&['\r', '\n'][..])
+                .to_string(),
+            message: message.into(),
+        }
+    }
+
+    /// Builds an `expected ...` message from a pest error in `input`, with `describe` naming the
+    /// rules.
+    pub(crate) fn from_pest<R: RuleType>(
+        err: Error<R>,
+        input: &str,
+        describe: fn(&R) -> String,
+    ) -> Self {
+        fn list(mut descriptions: Vec<String>) -> String {
+            let mut seen = HashSet::new();
+            descriptions.retain(|description| seen.insert(description.clone()));
+            match descriptions.split_last() {
+                Some((last, [])) => last.clone(),
+                Some((last, init)) => format!("{} or {}", init.join(", "), last),
+                None => String::new(),
+            }
+        }
+        let message = match &err.variant {
+            ErrorVariant::ParsingError {
+                positives,
+                negatives,
+            } => {
+                let positives = list(positives.iter().map(describe).collect());
+                let negatives = list(negatives.iter().map(describe).collect());
+                match (negatives.is_empty(), positives.is_empty()) {
+                    (true, true) => "unexpected input".to_string(),
+                    (true, false) => format!("expected {}", positives),
+                    (false, true) => format!("unexpected {}", negatives),
+                    (false, false) => format!("unexpected {}; expected {}", negatives, positives),
+                }
+            }
+            ErrorVariant::CustomError { message } => message.clone(),
+        };
+        let (line, column) = match err.line_col {
+            LineColLocation::Pos(pos) | LineColLocation::Span(pos, _) => pos,
+        };
+        Self {
+            file: None,
+            line,
+            column,
+            line_text: input.lines().nth(line - 1).unwrap_or("").to_string(),
+            message,
+        }
+    }
+
+    /// Where the error is, as `file:line:column` or `line:column`.
+    pub fn location(&self) -> String {
+        match &self.file {
+            Some(file) => format!("{}:{}:{}", file, self.line, self.column),
+            None => format!("{}:{}", self.line, self.column),
+        }
+    }
+}
+
+/// Prints the error with its line and a caret under its column.
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let gutter = " ".repeat(self.line.to_string().len());
+        // Tabs keep their width in the line above the caret
+        let indent = self
+            .line_text
+            .chars()
+            .take(self.column.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+        writeln!(f, "error: {}", self.message)?;
+        writeln!(f, "{}--> {}", gutter, self.location())?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", self.line, self.line_text)?;
+        write!(f, "{} | {}^", gutter, indent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ParseError;
+
+    #[test]
+    fn caret_points_at_column() {
+        let err = ParseError {
+            file: None,
+            line: 12,
+            column: 5,
+            line_text: "\tp(x y)".to_string(),
+            message: "expected `,` or `)`".to_string(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "error: expected `,` or `)`\n  --> 12:5\n   |\n12 | \tp(x y)\n   | \t   ^"
+        );
+    }
+}
@@ -1,30 +1,22 @@
 // Author: Michał Niciejewski
 use pest::Parser;
-use pest::error::Error;
+
+use crate::error::ParseError;
+use crate::signature::{Signature, SymbolKind};
 
 #[derive(Parser)]
 #[grammar = "grammar.pest"]
 struct FormulaParser;
 
-#[derive(Hash, Eq, Debug, Clone)]
+#[derive(Hash, PartialEq, Eq, Debug, Clone)]
 pub enum GenTerm<VarType> {
     Var(VarType),
     Fun(String, Vec<GenTerm<VarType>>),
 }
 
-impl<VarType: std::cmp::PartialEq> PartialEq for GenTerm<VarType> {
-    fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (GenTerm::Var(name1), GenTerm::Var(name2)) => name1 == name2,
-            (GenTerm::Fun(name1, terms1), GenTerm::Fun(name2, terms2)) => name1 == name2 && terms1 == terms2,
-            _ => false
-        }
-    }
-}
-
 pub type Term = GenTerm<String>;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Formula {
     False,
     True,
@@ -40,57 +32,122 @@ pub enum Formula {
 
 pub type FormulaBox = Box<Formula>;
 
-pub fn parse_formula(raw_formula: &str) -> Result<FormulaBox, Error<Rule>> {
-    let parsed_formula = FormulaParser::parse(Rule::file, raw_formula)?.next().unwrap();
+/// Names are quoted, with `\` escaping `"` and `\`.
+pub(crate) fn unescape(name: &str) -> String {
+    let mut chars = name.chars();
+    let mut res = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => res.extend(chars.next()),
+            _ => res.push(c),
+        }
+    }
+    res
+}
+
+fn describe(rule: &Rule) -> String {
+    match rule {
+        Rule::f_rel => "`Rel`",
+        Rule::f_not => "`Not`",
+        Rule::f_and => "`And`",
+        Rule::f_or => "`Or`",
+        Rule::f_implies => "`Implies`",
+        Rule::f_iff => "`Iff`",
+        Rule::f_forall => "`Forall`",
+        Rule::f_exists => "`Exists`",
+        Rule::f_true => "`T`",
+        Rule::f_false => "`F`",
+        Rule::t_var => "`Var`",
+        Rule::t_fun => "`Fun`",
+        Rule::string => "a quoted name",
+        Rule::term_array => "a list of terms",
+        Rule::EOI => "the end of the input",
+        _ => return format!("{:?}", rule),
+    }
+    .to_string()
+}
+
+pub fn parse_formula(raw_formula: &str) -> Result<FormulaBox, ParseError> {
+    parse_formula_with(raw_formula, &mut Signature::new())
+}
+
+/// Parses a formula, recording its symbols and warnings in `signature`.
+pub fn parse_formula_with(raw_formula: &str, signature: &mut Signature) -> Result<FormulaBox, ParseError> {
+    let parsed_formula = FormulaParser::parse(Rule::file, raw_formula)
+        .map_err(|err| ParseError::from_pest(err, raw_formula, describe))?
+        .next()
+        .unwrap();
 
     use pest::iterators::Pair;
 
-    fn build_ast_term(pair: Pair<Rule>) -> Term {
+    fn build_ast_terms(pair: Pair<Rule>, signature: &mut Signature) -> Result<Vec<Term>, ParseError> {
+        pair.into_inner().map(|pair| build_ast_term(pair, signature)).collect()
+    }
+
+    fn build_ast_term(pair: Pair<Rule>, signature: &mut Signature) -> Result<Term, ParseError> {
         let rule = pair.as_rule();
+        let pos = pair.as_span().start_pos();
         let mut inner_rules = pair.into_inner();
         macro_rules! get_next_inner {
-            () => { inner_rules.next().unwrap().into_inner(); };
+            () => { inner_rules.next().unwrap().into_inner() };
         }
         macro_rules! parse_next_string {
-            () => { String::from(get_next_inner!().as_str()); };
+            () => { unescape(get_next_inner!().as_str()) };
         }
-        match rule {
+        Ok(match rule {
             Rule::t_var => Term::Var(parse_next_string!()),
-            Rule::t_fun => Term::Fun(parse_next_string!(),
-                get_next_inner!().map(|pair| { build_ast_term(pair) }).collect()),
+            Rule::t_fun => {
+                let name = parse_next_string!();
+                let args = build_ast_terms(inner_rules.next().unwrap(), signature)?;
+                Term::Fun(signature.use_symbol(&name, SymbolKind::Function, args.len(), pos)?, args)
+            }
             _ => unreachable!(),
-        }
+        })
     }
 
-    fn build_ast_formula(pair: Pair<Rule>) -> FormulaBox {
+    fn build_ast_formula(pair: Pair<Rule>, signature: &mut Signature) -> Result<FormulaBox, ParseError> {
         let rule = pair.as_rule();
+        let pos = pair.as_span().start_pos();
         let mut inner_rules = pair.into_inner();
         macro_rules! get_next_inner {
-            () => { inner_rules.next().unwrap().into_inner(); };
+            () => { inner_rules.next().unwrap().into_inner() };
         }
         macro_rules! parse_next_formula {
-            () => { build_ast_formula(inner_rules.next().unwrap()); };
+            () => { build_ast_formula(inner_rules.next().unwrap(), signature)? };
         }
         macro_rules! parse_next_string {
-            () => { String::from(get_next_inner!().as_str()); };
+            () => { unescape(get_next_inner!().as_str()) };
         }
 
         let formula = match rule {
-            Rule::f_rel => Formula::Rel(parse_next_string!(),
-                get_next_inner!().map(|pair| { build_ast_term(pair) }).collect()),
+            Rule::f_rel => {
+                let name = parse_next_string!();
+                let args = build_ast_terms(inner_rules.next().unwrap(), signature)?;
+                Formula::Rel(signature.use_symbol(&name, SymbolKind::Relation, args.len(), pos)?, args)
+            }
             Rule::f_not => Formula::Not(parse_next_formula!()),
             Rule::f_and => Formula::And(parse_next_formula!(), parse_next_formula!()),
             Rule::f_or => Formula::Or(parse_next_formula!(), parse_next_formula!()),
             Rule::f_implies => Formula::Implies(parse_next_formula!(), parse_next_formula!()),
             Rule::f_iff => Formula::Iff(parse_next_formula!(), parse_next_formula!()),
-            Rule::f_forall => Formula::Forall(parse_next_string!(), parse_next_formula!()),
-            Rule::f_exists => Formula::Exists(parse_next_string!(), parse_next_formula!()),
+            Rule::f_forall | Rule::f_exists => {
+                let var = inner_rules.next().unwrap();
+                let var_pos = var.as_span().start_pos();
+                let var = unescape(var.into_inner().as_str());
+                signature.bind(&var, var_pos);
+                let body = parse_next_formula!();
+                signature.unbind(1);
+                match rule {
+                    Rule::f_forall => Formula::Forall(var, body),
+                    _ => Formula::Exists(var, body),
+                }
+            }
             Rule::f_true => Formula::True,
             Rule::f_false => Formula::False,
             _ => unreachable!(),
         };
-        FormulaBox::new(formula)
+        Ok(FormulaBox::new(formula))
     }
 
-    Ok(build_ast_formula(parsed_formula))
+    build_ast_formula(parsed_formula, signature)
 }
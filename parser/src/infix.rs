@@ -0,0 +1,259 @@
+use pest::iterators::Pair;
+use pest::Parser;
+
+use crate::error::ParseError;
+use crate::formula::{unescape, Formula, FormulaBox, Term};
+use crate::signature::{Signature, SymbolKind};
+
+#[derive(Parser)]
+#[grammar = "infix.pest"]
+struct InfixParser;
+
+fn name(pair: Pair<Rule>) -> String {
+    let text = pair.as_str();
+    match pair.as_rule() {
+        Rule::quoted => unescape(&text[1..text.len() - 1]),
+        _ => text.to_string(),
+    }
+}
+
+/// Identifiers bound by an enclosing quantifier are variables, and every other identifier in a
+/// term is a function symbol.
+fn build_term(
+    pair: Pair<Rule>,
+    bound: &[String],
+    signature: &mut Signature,
+) -> Result<Term, ParseError> {
+    let pos = pair.as_span().start_pos();
+    let mut inner = pair.into_inner();
+    let name = name(inner.next().unwrap());
+    let args = inner
+        .map(|arg| build_term(arg, bound, signature))
+        .collect::<Result<Vec<_>, _>>()?;
+    if args.is_empty() && bound.contains(&name) {
+        Ok(Term::Var(name))
+    } else {
+        let name = signature.use_symbol(&name, SymbolKind::Function, args.len(), pos)?;
+        Ok(Term::Fun(name, args))
+    }
+}
+
+fn build_formula(
+    pair: Pair<Rule>,
+    bound: &mut Vec<String>,
+    signature: &mut Signature,
+) -> Result<Formula, ParseError> {
+    let rule = pair.as_rule();
+    let pos = pair.as_span().start_pos();
+    let mut inner = pair.into_inner();
+    Ok(match rule {
+        Rule::iff | Rule::disjunction | Rule::conjunction => {
+            let mut res = build_formula(inner.next().unwrap(), bound, signature)?;
+            for operand in inner {
+                let (a, b) = (
+                    Box::new(res),
+                    Box::new(build_formula(operand, bound, signature)?),
+                );
+                res = match rule {
+                    Rule::iff => Formula::Iff(a, b),
+                    Rule::disjunction => Formula::Or(a, b),
+                    _ => Formula::And(a, b),
+                };
+            }
+            res
+        }
+        Rule::implication => {
+            let premise = build_formula(inner.next().unwrap(), bound, signature)?;
+            match inner.next() {
+                Some(conclusion) => Formula::Implies(
+                    Box::new(premise),
+                    Box::new(build_formula(conclusion, bound, signature)?),
+                ),
+                None => premise,
+            }
+        }
+        Rule::negation => Formula::Not(Box::new(build_formula(
+            inner.next().unwrap(),
+            bound,
+            signature,
+        )?)),
+        Rule::quantified => {
+            let is_forall = inner.next().unwrap().as_rule() == Rule::forall;
+            let vars = inner
+                .next()
+                .unwrap()
+                .into_inner()
+                .map(|var| {
+                    let pos = var.as_span().start_pos();
+                    let var = name(var);
+                    signature.bind(&var, pos);
+                    var
+                })
+                .collect::<Vec<_>>();
+            bound.extend(vars.iter().cloned());
+            let body = build_formula(inner.next().unwrap(), bound, signature);
+            bound.truncate(bound.len() - vars.len());
+            signature.unbind(vars.len());
+            vars.into_iter().rev().fold(body?, |acc, var| {
+                if is_forall {
+                    Formula::Forall(var, Box::new(acc))
+                } else {
+                    Formula::Exists(var, Box::new(acc))
+                }
+            })
+        }
+        Rule::f_true => Formula::True,
+        Rule::f_false => Formula::False,
+        Rule::equality | Rule::disequality => {
+            let args = inner
+                .map(|term| build_term(term, bound, signature))
+                .collect::<Result<Vec<_>, _>>()?;
+            let equality = Formula::Rel(
+                signature.use_symbol("=", SymbolKind::Relation, args.len(), pos)?,
+                args,
+            );
+            match rule {
+                Rule::equality => equality,
+                _ => Formula::Not(Box::new(equality)),
+            }
+        }
+        Rule::relation => {
+            let name = name(inner.next().unwrap());
+            let args = inner
+                .map(|term| build_term(term, bound, signature))
+                .collect::<Result<Vec<_>, _>>()?;
+            let name = signature.use_symbol(&name, SymbolKind::Relation, args.len(), pos)?;
+            Formula::Rel(name, args)
+        }
+        _ => unreachable!(),
+    })
+}
+
+fn describe(rule: &Rule) -> String {
+    match rule {
+        Rule::ident => "an identifier",
+        Rule::quoted => "a quoted name",
+        Rule::term => "a term",
+        Rule::variables => "variables",
+        Rule::quantified => "a quantifier",
+        Rule::negation => "`~`",
+        Rule::f_true => "`true`",
+        Rule::f_false => "`false`",
+        Rule::relation | Rule::equality | Rule::disequality => "an atom",
+        Rule::EOI => "the end of the input",
+        _ => return format!("{:?}", rule),
+    }
+    .to_string()
+}
+
+/// Parses a formula in the infix syntax, such as `forall x. p(x) -> exists y. q(x, y)`. It has
+/// the connectives `~`, `&`, `|`, `->` and `<->` (or `¬`, `∧`, `∨`, `→`, `↔`), the constants
+/// `true` and `false`, and equality `=` and `!=` of terms, which is the relation `=`.
+pub fn parse_infix(input: &str) -> Result<FormulaBox, ParseError> {
+    parse_infix_with(input, &mut Signature::new())
+}
+
+/// Parses a formula in the infix syntax, recording its symbols and warnings in `signature`.
+pub fn parse_infix_with(input: &str, signature: &mut Signature) -> Result<FormulaBox, ParseError> {
+    let pair = InfixParser::parse(Rule::file, input)
+        .map_err(|err| ParseError::from_pest(err, input, describe))?
+        .next()
+        .unwrap();
+    Ok(Box::new(build_formula(pair, &mut Vec::new(), signature)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_infix;
+    use crate::formula::{Formula, Term};
+
+    fn rel(name: &str) -> Box<Formula> {
+        Box::new(Formula::Rel(name.to_string(), vec![]))
+    }
+
+    #[test]
+    fn precedence_and_associativity() {
+        let formula = parse_infix("~p & q | r -> s -> t <-> u").unwrap();
+        let expected = Formula::Iff(
+            Box::new(Formula::Implies(
+                Box::new(Formula::Or(
+                    Box::new(Formula::And(Box::new(Formula::Not(rel("p"))), rel("q"))),
+                    rel("r"),
+                )),
+                Box::new(Formula::Implies(rel("s"), rel("t"))),
+            )),
+            rel("u"),
+        );
+        assert_eq!(format!("{:?}", formula), format!("{:?}", expected));
+        let formula = parse_infix("p & (q | r) & s").unwrap();
+        let expected = Formula::And(
+            Box::new(Formula::And(
+                rel("p"),
+                Box::new(Formula::Or(rel("q"), rel("r"))),
+            )),
+            rel("s"),
+        );
+        assert_eq!(format!("{:?}", formula), format!("{:?}", expected));
+    }
+
+    #[test]
+    fn quantifiers_bind_variables() {
+        let formula = parse_infix("forall x. p(x) -> exists y z. q(x, f(y), a) & y != z").unwrap();
+        let x = || Term::Var("x".to_string());
+        let y = || Term::Var("y".to_string());
+        let expected = Formula::Forall(
+            "x".to_string(),
+            Box::new(Formula::Implies(
+                Box::new(Formula::Rel("p".to_string(), vec![x()])),
+                Box::new(Formula::Exists(
+                    "y".to_string(),
+                    Box::new(Formula::Exists(
+                        "z".to_string(),
+                        Box::new(Formula::And(
+                            Box::new(Formula::Rel(
+                                "q".to_string(),
+                                vec![
+                                    x(),
+                                    Term::Fun("f".to_string(), vec![y()]),
+                                    Term::Fun("a".to_string(), vec![]),
+                                ],
+                            )),
+                            Box::new(Formula::Not(Box::new(Formula::Rel(
+                                "=".to_string(),
+                                vec![y(), Term::Var("z".to_string())],
+                            )))),
+                        )),
+                    )),
+                )),
+            )),
+        );
+        assert_eq!(format!("{:?}", formula), format!("{:?}", expected));
+        // Outside the scope of the quantifier, x is a constant
+        match *parse_infix("(∀x. p(x)) ∧ p(x)").unwrap() {
+            Formula::And(_, b) => assert_eq!(
+                format!("{:?}", b),
+                format!(
+                    "{:?}",
+                    Formula::Rel("p".to_string(), vec![Term::Fun("x".to_string(), vec![])])
+                )
+            ),
+            formula => panic!("unexpected {:?}", formula),
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert!(parse_infix("p & ").is_err());
+        assert!(parse_infix("forall. p").is_err());
+        assert!(parse_infix("p(x").is_err());
+        assert!(parse_infix("forall x p(x)").is_err());
+        let err = parse_infix("p(a) &\n  p(a, b)").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 3));
+        assert_eq!(err.message, "`p` has 2 arguments here but 1 at 1:1");
+        let err = parse_infix("p(f) | f").unwrap_err();
+        assert_eq!(
+            err.message,
+            "`f` is used as a relation here but as a function at 1:3"
+        );
+    }
+}
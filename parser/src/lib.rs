@@ -3,4 +3,10 @@ extern crate pest;
 #[macro_use]
 extern crate pest_derive;
 
+pub mod error;
 pub mod formula;
+pub mod infix;
+pub mod printer;
+pub mod signature;
+pub mod smtlib;
+pub mod tptp;
@@ -0,0 +1,347 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+
+use crate::formula::{Formula, Term};
+
+fn quote(name: &str, quote: char) -> String {
+    let mut res = String::new();
+    res.push(quote);
+    for c in name.chars() {
+        if c == quote || c == '\\' {
+            res.push('\\');
+        }
+        res.push(c);
+    }
+    res.push(quote);
+    res
+}
+
+fn native_term(term: &Term) -> String {
+    match term {
+        Term::Var(name) => format!("Var {}", quote(name, '"')),
+        Term::Fun(name, args) => format!(
+            "Fun {} [{}]",
+            quote(name, '"'),
+            args.iter().map(native_term).collect::<Vec<_>>().join(", ")
+        ),
+    }
+}
+
+/// Prints `formula` in the syntax read by `parse_formula`.
+pub fn to_native(formula: &Formula) -> String {
+    match formula {
+        Formula::True => "T".to_string(),
+        Formula::False => "F".to_string(),
+        Formula::Rel(name, args) => format!(
+            "Rel {} [{}]",
+            quote(name, '"'),
+            args.iter().map(native_term).collect::<Vec<_>>().join(", ")
+        ),
+        Formula::Not(phi) => format!("Not ({})", to_native(phi)),
+        Formula::And(a, b) => format!("And ({}) ({})", to_native(a), to_native(b)),
+        Formula::Or(a, b) => format!("Or ({}) ({})", to_native(a), to_native(b)),
+        Formula::Implies(a, b) => format!("Implies ({}) ({})", to_native(a), to_native(b)),
+        Formula::Iff(a, b) => format!("Iff ({}) ({})", to_native(a), to_native(b)),
+        Formula::Exists(var, phi) => format!("Exists {} ({})", quote(var, '"'), to_native(phi)),
+        Formula::Forall(var, phi) => format!("Forall {} ({})", quote(var, '"'), to_native(phi)),
+    }
+}
+
+fn infix_name(name: &str) -> String {
+    let mut chars = name.chars();
+    let is_ident = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '\'')
+        && !["forall", "exists", "true", "false"].contains(&name);
+    if is_ident {
+        name.to_string()
+    } else {
+        quote(name, '\'')
+    }
+}
+
+fn infix_application(name: &str, args: &[Term]) -> String {
+    if args.is_empty() {
+        infix_name(name)
+    } else {
+        format!(
+            "{}({})",
+            infix_name(name),
+            args.iter().map(infix_term).collect::<Vec<_>>().join(", ")
+        )
+    }
+}
+
+fn infix_term(term: &Term) -> String {
+    match term {
+        Term::Var(name) => infix_name(name),
+        Term::Fun(name, args) => infix_application(name, args),
+    }
+}
+
+/// Prints `formula` in parentheses unless its connective binds at least as tightly as
+/// `precedence`: quantifiers bind the most loosely, then `<->`, `->`, `|`, `&` and the others.
+fn infix(formula: &Formula, precedence: u8) -> String {
+    let (own_precedence, res) = match formula {
+        Formula::True => (5, "true".to_string()),
+        Formula::False => (5, "false".to_string()),
+        Formula::Rel(name, args) if name == "=" && args.len() == 2 => (
+            5,
+            format!("{} = {}", infix_term(&args[0]), infix_term(&args[1])),
+        ),
+        Formula::Rel(name, args) => (5, infix_application(name, args)),
+        Formula::Not(phi) => match phi.as_ref() {
+            Formula::Rel(name, args) if name == "=" && args.len() == 2 => (
+                5,
+                format!("{} != {}", infix_term(&args[0]), infix_term(&args[1])),
+            ),
+            _ => (5, format!("~{}", infix(phi, 5))),
+        },
+        Formula::And(a, b) => (4, format!("{} & {}", infix(a, 4), infix(b, 5))),
+        Formula::Or(a, b) => (3, format!("{} | {}", infix(a, 3), infix(b, 4))),
+        Formula::Implies(a, b) => (2, format!("{} -> {}", infix(a, 3), infix(b, 2))),
+        Formula::Iff(a, b) => (1, format!("{} <-> {}", infix(a, 1), infix(b, 2))),
+        Formula::Exists(var, phi) => (0, format!("exists {}. {}", infix_name(var), infix(phi, 0))),
+        Formula::Forall(var, phi) => (0, format!("forall {}. {}", infix_name(var), infix(phi, 0))),
+    };
+    if own_precedence < precedence {
+        format!("({})", res)
+    } else {
+        res
+    }
+}
+
+/// Prints `formula` in the syntax read by `parse_infix`. Free variables, and constants named
+/// like an enclosing bound variable, cannot be told apart from other symbols in this syntax.
+pub fn to_infix(formula: &Formula) -> String {
+    infix(formula, 0)
+}
+
+fn is_tptp_word(name: &str, is_upper: bool) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| {
+        if is_upper {
+            c.is_ascii_uppercase()
+        } else {
+            c.is_ascii_lowercase()
+        }
+    }) && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn tptp_name(name: &str) -> String {
+    if is_tptp_word(name, false) {
+        name.to_string()
+    } else {
+        quote(name, '\'')
+    }
+}
+
+/// Prints TPTP formulas, with `vars` renaming their variables.
+struct TptpPrinter {
+    vars: HashMap<String, String>,
+}
+
+impl TptpPrinter {
+    /// Keeps the names of variables that are TPTP variables, and renames the others to unused
+    /// `X<n>`.
+    fn new(formula: &Formula) -> Self {
+        fn term_vars(term: &Term, names: &mut Vec<String>) {
+            match term {
+                Term::Var(name) => names.push(name.clone()),
+                Term::Fun(_, args) => args.iter().for_each(|arg| term_vars(arg, names)),
+            }
+        }
+        fn formula_vars(formula: &Formula, names: &mut Vec<String>) {
+            match formula {
+                Formula::True | Formula::False => {}
+                Formula::Rel(_, args) => args.iter().for_each(|arg| term_vars(arg, names)),
+                Formula::Not(phi) => formula_vars(phi, names),
+                Formula::And(a, b)
+                | Formula::Or(a, b)
+                | Formula::Implies(a, b)
+                | Formula::Iff(a, b) => {
+                    formula_vars(a, names);
+                    formula_vars(b, names);
+                }
+                Formula::Exists(var, phi) | Formula::Forall(var, phi) => {
+                    names.push(var.clone());
+                    formula_vars(phi, names);
+                }
+            }
+        }
+        let mut names = Vec::new();
+        formula_vars(formula, &mut names);
+        let used = names
+            .iter()
+            .filter(|name| is_tptp_word(name, true))
+            .cloned()
+            .collect::<HashSet<_>>();
+        let mut fresh = (0..)
+            .map(|idx| format!("X{}", idx))
+            .filter(|name| !used.contains(name));
+        let mut vars = HashMap::new();
+        for name in names {
+            if let Entry::Vacant(entry) = vars.entry(name) {
+                let renamed = if used.contains(entry.key()) {
+                    entry.key().clone()
+                } else {
+                    fresh.next().unwrap()
+                };
+                entry.insert(renamed);
+            }
+        }
+        Self { vars }
+    }
+
+    fn term(&self, term: &Term) -> String {
+        match term {
+            Term::Var(name) => self.vars[name].clone(),
+            Term::Fun(name, args) => self.application(name, args),
+        }
+    }
+
+    fn application(&self, name: &str, args: &[Term]) -> String {
+        if args.is_empty() {
+            tptp_name(name)
+        } else {
+            format!(
+                "{}({})",
+                tptp_name(name),
+                args.iter()
+                    .map(|arg| self.term(arg))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            )
+        }
+    }
+
+    /// A unitary formula, which can be an operand of any connective.
+    fn unitary(&self, formula: &Formula) -> String {
+        match formula {
+            Formula::And(_, _)
+            | Formula::Or(_, _)
+            | Formula::Implies(_, _)
+            | Formula::Iff(_, _) => {
+                format!("({})", self.formula(formula))
+            }
+            _ => self.formula(formula),
+        }
+    }
+
+    fn formula(&self, formula: &Formula) -> String {
+        match formula {
+            Formula::True => "$true".to_string(),
+            Formula::False => "$false".to_string(),
+            Formula::Rel(name, args) if name == "=" && args.len() == 2 => {
+                format!("{} = {}", self.term(&args[0]), self.term(&args[1]))
+            }
+            Formula::Rel(name, args) => self.application(name, args),
+            Formula::Not(phi) => match phi.as_ref() {
+                Formula::Rel(name, args) if name == "=" && args.len() == 2 => {
+                    format!("{} != {}", self.term(&args[0]), self.term(&args[1]))
+                }
+                _ => format!("~ {}", self.unitary(phi)),
+            },
+            // Chains of `&` and of `|` associate to the left
+            Formula::And(a, b) => match a.as_ref() {
+                Formula::And(_, _) => format!("{} & {}", self.formula(a), self.unitary(b)),
+                _ => format!("{} & {}", self.unitary(a), self.unitary(b)),
+            },
+            Formula::Or(a, b) => match a.as_ref() {
+                Formula::Or(_, _) => format!("{} | {}", self.formula(a), self.unitary(b)),
+                _ => format!("{} | {}", self.unitary(a), self.unitary(b)),
+            },
+            Formula::Implies(a, b) => format!("{} => {}", self.unitary(a), self.unitary(b)),
+            Formula::Iff(a, b) => format!("{} <=> {}", self.unitary(a), self.unitary(b)),
+            Formula::Exists(var, phi) => format!("?[{}]: {}", self.vars[var], self.unitary(phi)),
+            Formula::Forall(var, phi) => format!("![{}]: {}", self.vars[var], self.unitary(phi)),
+        }
+    }
+}
+
+/// Prints `formula` as the annotated TPTP formula `fof(name, role, formula).`. Variables are
+/// renamed to upper-case words, and free variables are universally quantified by TPTP readers.
+pub fn to_tptp(formula: &Formula, name: &str, role: &str) -> String {
+    format!(
+        "fof({}, {}, {}).",
+        tptp_name(name),
+        role,
+        TptpPrinter::new(formula).formula(formula)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{to_infix, to_native, to_tptp};
+    use crate::formula::{parse_formula, Formula, Term};
+    use crate::infix::parse_infix;
+    use crate::tptp::parse_tptp;
+
+    fn example() -> Formula {
+        let var = |name: &str| Term::Var(name.to_string());
+        let fun = |name: &str, args| Term::Fun(name.to_string(), args);
+        let rel = |name: &str, args| Box::new(Formula::Rel(name.to_string(), args));
+        // forall x. (exists y. 'a "b\c'(x, y)) -> (~p <-> (q | r) & (s -> t) -> x != f(forall))
+        Formula::Forall(
+            "x".to_string(),
+            Box::new(Formula::Implies(
+                Box::new(Formula::Exists(
+                    "y".to_string(),
+                    rel("a \"b\\c", vec![var("x"), var("y")]),
+                )),
+                Box::new(Formula::Iff(
+                    Box::new(Formula::Not(rel("p", vec![]))),
+                    Box::new(Formula::Implies(
+                        Box::new(Formula::And(
+                            Box::new(Formula::Or(rel("q", vec![]), rel("r", vec![]))),
+                            Box::new(Formula::Implies(rel("s", vec![]), rel("t", vec![]))),
+                        )),
+                        Box::new(Formula::Not(rel(
+                            "=",
+                            vec![var("x"), fun("f", vec![fun("forall", vec![])])],
+                        ))),
+                    )),
+                )),
+            )),
+        )
+    }
+
+    #[test]
+    fn native_round_trip() {
+        let formula = example();
+        let printed = to_native(&formula);
+        assert_eq!(
+            format!("{:?}", parse_formula(&printed).unwrap()),
+            format!("{:?}", formula)
+        );
+    }
+
+    #[test]
+    fn infix_round_trip() {
+        let formula = example();
+        let printed = to_infix(&formula);
+        assert_eq!(
+            printed,
+            "forall x. (exists y. 'a \"b\\\\c'(x, y)) -> (~p <-> (q | r) & (s -> t) -> x != f('forall'))"
+        );
+        assert_eq!(
+            format!("{:?}", parse_infix(&printed).unwrap()),
+            format!("{:?}", formula)
+        );
+    }
+
+    #[test]
+    fn tptp_round_trip() {
+        let formula = example();
+        let printed = to_tptp(&formula, "goal", "conjecture");
+        let problem = parse_tptp(&printed, Path::new(".")).unwrap();
+        // The variables are renamed
+        assert_eq!(
+            to_infix(&problem.conjectures[0]),
+            "forall X0. (exists X1. 'a \"b\\\\c'(X0, X1)) -> (~p <-> (q | r) & (s -> t) -> X0 != f('forall'))"
+        );
+    }
+}
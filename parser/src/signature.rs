@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use pest::Position;
+
+use crate::error::ParseError;
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub enum SymbolKind {
+    Function,
+    Relation,
+}
+
+impl SymbolKind {
+    fn name(self) -> &'static str {
+        match self {
+            SymbolKind::Function => "function",
+            SymbolKind::Relation => "relation",
+        }
+    }
+}
+
+/// A symbol of the parsed input, under the name it has in the parsed formula.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub arity: usize,
+}
+
+/// The sort of the terms of unsorted inputs, and of the undeclared symbols and variables.
+pub const INDIVIDUAL: &str = "$i";
+
+/// The sort of formulas, which declared relations give.
+pub const BOOLEAN: &str = "$o";
+
+/// The sorts declared by an input and the sorts of its symbols and variables, which have the sort
+/// `INDIVIDUAL` unless declared otherwise.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Sorts {
+    /// The declared sorts other than `INDIVIDUAL`, in the order of their declarations
+    pub sorts: Vec<String>,
+    /// The functions, with the sorts of their arguments and of their values
+    pub funs: HashMap<String, (Vec<String>, String)>,
+    /// The relations, with the sorts of their arguments
+    pub rels: HashMap<String, Vec<String>>,
+    /// The variables bound with a sort other than `INDIVIDUAL`. They are given names that no
+    /// variable of the input has, so that free variables and the variables of other formulas
+    /// with the same name keep the sort `INDIVIDUAL`.
+    pub vars: HashMap<String, String>,
+}
+
+impl Sorts {
+    /// Whether `sort` is `INDIVIDUAL` or declared.
+    pub fn is_sort(&self, sort: &str) -> bool {
+        sort == INDIVIDUAL || self.sorts.iter().any(|declared| declared == sort)
+    }
+
+    /// A name for a variable `name` bound with `sort`, which is `name` if `sort` is `INDIVIDUAL`
+    /// and `name!<n>` otherwise.
+    pub fn bind(&mut self, name: &str, sort: &str) -> String {
+        if sort == INDIVIDUAL {
+            return name.to_string();
+        }
+        let mut idx = 1;
+        let mut candidate = format!("{}!{}", name, idx);
+        while self.vars.get(&candidate).is_some_and(|bound| bound != sort) {
+            idx += 1;
+            candidate = format!("{}!{}", name, idx);
+        }
+        self.vars.insert(candidate.clone(), sort.to_string());
+        candidate
+    }
+}
+
+/// A suspicious part of the input, which is still parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning(pub ParseError);
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let diagnostic = self.0.to_string();
+        write!(f, "warning{}", &diagnostic["error".len()..])
+    }
+}
+
+/// The symbol table of an input: the kind and arity of its symbols, as they are first used in it.
+/// A name used with another kind or arity is an error, unless the signature is permissive, in
+/// which case each other use is a distinct symbol `name/arity`.
+#[derive(Default)]
+pub struct Signature {
+    permissive: bool,
+    /// The included file being read, if any
+    pub(crate) file: Option<String>,
+    /// The symbols of each name of the input, with where they are first used
+    symbols: HashMap<String, Vec<(Symbol, String)>>,
+    /// The variables in scope, innermost last, with their names in the parsed formula and where
+    /// they are bound
+    bound: Vec<(String, String, String)>,
+    sorts: Sorts,
+    warnings: Vec<Warning>,
+}
+
+impl Signature {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn permissive() -> Self {
+        Self {
+            permissive: true,
+            ..Self::default()
+        }
+    }
+
+    /// The symbols of the input, sorted by name.
+    pub fn symbols(&self) -> Vec<Symbol> {
+        let mut symbols = self
+            .symbols
+            .values()
+            .flatten()
+            .map(|(symbol, _)| symbol.clone())
+            .collect::<Vec<_>>();
+        symbols.sort();
+        symbols
+    }
+
+    /// The sorts declared by the input and the sorts of its symbols and variables.
+    pub fn sorts(&self) -> &Sorts {
+        &self.sorts
+    }
+
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    fn location(&self, pos: &Position) -> String {
+        let (line, column) = pos.line_col();
+        match &self.file {
+            Some(file) => format!("{}:{}:{}", file, line, column),
+            None => format!("{}:{}", line, column),
+        }
+    }
+
+    pub(crate) fn error(&self, pos: Position, message: impl Into<String>) -> ParseError {
+        let mut error = ParseError::at(pos, message);
+        error.file = self.file.clone();
+        error
+    }
+
+    /// Records the use of `name` at `pos` and returns the name of its symbol. The use must agree
+    /// with the previous ones unless the signature is permissive.
+    pub(crate) fn use_symbol(
+        &mut self,
+        name: &str,
+        kind: SymbolKind,
+        arity: usize,
+        pos: Position,
+    ) -> Result<String, ParseError> {
+        let location = self.location(&pos);
+        let uses = self.symbols.entry(name.to_string()).or_default();
+        if let Some((symbol, _)) = uses
+            .iter()
+            .find(|(symbol, _)| symbol.kind == kind && symbol.arity == arity)
+        {
+            return Ok(symbol.name.clone());
+        }
+        let symbol_name = match uses.first() {
+            None => name.to_string(),
+            Some(_) if self.permissive => {
+                let mut split = format!("{}/{}", name, arity);
+                while uses.iter().any(|(symbol, _)| symbol.name == split) {
+                    split.push('\'');
+                }
+                split
+            }
+            Some((first, first_location)) => {
+                let message = if first.kind != kind {
+                    format!(
+                        "`{}` is used as a {} here but as a {} at {}",
+                        name,
+                        kind.name(),
+                        first.kind.name(),
+                        first_location
+                    )
+                } else {
+                    let arguments = if arity == 1 { "argument" } else { "arguments" };
+                    format!(
+                        "`{}` has {} {} here but {} at {}",
+                        name, arity, arguments, first.arity, first_location
+                    )
+                };
+                return Err(self.error(pos, message));
+            }
+        };
+        let symbol = Symbol {
+            name: symbol_name.clone(),
+            kind,
+            arity,
+        };
+        uses.push((symbol, location));
+        Ok(symbol_name)
+    }
+
+    /// Brings the variable `name` bound at `pos` in scope, warning if it shadows another one.
+    pub(crate) fn bind(&mut self, name: &str, pos: Position) {
+        self.bind_with_sort(name, INDIVIDUAL, pos);
+    }
+
+    /// Brings the variable `name` of `sort` bound at `pos` in scope, warning if it shadows
+    /// another one, and returns its name in the parsed formula.
+    pub(crate) fn bind_with_sort(&mut self, name: &str, sort: &str, pos: Position) -> String {
+        let location = self.location(&pos);
+        if let Some((_, _, outer)) = self.bound.iter().rev().find(|(var, _, _)| var == name) {
+            let message = format!("`{}` shadows the variable bound at {}", name, outer);
+            self.warnings.push(Warning(self.error(pos, message)));
+        }
+        let renamed = self.sorts.bind(name, sort);
+        self.bound
+            .push((name.to_string(), renamed.clone(), location));
+        renamed
+    }
+
+    /// The name in the parsed formula of the variable `name`.
+    pub(crate) fn var(&self, name: &str) -> String {
+        match self.bound.iter().rev().find(|(var, _, _)| var == name) {
+            Some((_, renamed, _)) => renamed.clone(),
+            None => name.to_string(),
+        }
+    }
+
+    /// Declares the sort `name` at `pos`.
+    pub(crate) fn declare_sort(&mut self, name: &str, pos: Position) -> Result<(), ParseError> {
+        if self.sorts.is_sort(name) || name == BOOLEAN {
+            return Err(self.error(pos, format!("sort `{}` is already declared", name)));
+        }
+        self.sorts.sorts.push(name.to_string());
+        Ok(())
+    }
+
+    /// Declares at `pos` the function `name` from `args` to `sort`, or the relation if `sort` is
+    /// `BOOLEAN`.
+    pub(crate) fn declare_symbol(
+        &mut self,
+        name: &str,
+        args: Vec<String>,
+        sort: String,
+        pos: Position,
+    ) -> Result<(), ParseError> {
+        if let Some(unknown) = args
+            .iter()
+            .chain(Some(&sort).filter(|sort| *sort != BOOLEAN))
+            .find(|sort| !self.sorts.is_sort(sort))
+        {
+            return Err(self.error(pos, format!("unknown sort `{}`", unknown)));
+        }
+        if self.sorts.funs.contains_key(name) || self.sorts.rels.contains_key(name) {
+            return Err(self.error(pos, format!("`{}` is already declared", name)));
+        }
+        if sort == BOOLEAN {
+            self.sorts.rels.insert(name.to_string(), args);
+        } else {
+            self.sorts.funs.insert(name.to_string(), (args, sort));
+        }
+        Ok(())
+    }
+
+    /// Ends the scope of the last `count` variables brought in scope.
+    pub(crate) fn unbind(&mut self, count: usize) {
+        self.bound.truncate(self.bound.len() - count);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Signature, Symbol, SymbolKind};
+    use crate::formula::parse_formula_with;
+    use crate::infix::parse_infix_with;
+
+    fn symbol(name: &str, kind: SymbolKind, arity: usize) -> Symbol {
+        Symbol {
+            name: name.to_string(),
+            kind,
+            arity,
+        }
+    }
+
+    #[test]
+    fn permissive_signature_splits_symbols() {
+        let input = "p(a) & p(a, f(a)) | p(f) & a(p(a, a))";
+        assert!(parse_infix_with(input, &mut Signature::new()).is_err());
+        let mut signature = Signature::permissive();
+        let formula = parse_infix_with(input, &mut signature).unwrap();
+        assert_eq!(
+            crate::printer::to_infix(&formula),
+            "p(a) & 'p/2'(a, f(a)) | p('f/0') & 'a/1'('p/2\\''(a, a))"
+        );
+        assert_eq!(
+            signature.symbols(),
+            vec![
+                symbol("a", SymbolKind::Function, 0),
+                symbol("a/1", SymbolKind::Relation, 1),
+                symbol("f", SymbolKind::Function, 1),
+                symbol("f/0", SymbolKind::Function, 0),
+                symbol("p", SymbolKind::Relation, 1),
+                symbol("p/2", SymbolKind::Relation, 2),
+                symbol("p/2'", SymbolKind::Function, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn warns_about_shadowed_variables() {
+        let mut signature = Signature::new();
+        parse_infix_with(
+            "forall x. (exists y x. p(x, y)) & exists y. q(y)",
+            &mut signature,
+        )
+        .unwrap();
+        let messages = signature
+            .warnings()
+            .iter()
+            .map(|warning| warning.0.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(messages, vec!["`x` shadows the variable bound at 1:8"]);
+        let mut signature = Signature::new();
+        parse_formula_with(
+            r#"Forall "x" (Exists "x" (Rel "p" [Var "x"]))"#,
+            &mut signature,
+        )
+        .unwrap();
+        assert_eq!(
+            signature.warnings()[0].to_string(),
+            "warning: `x` shadows the variable bound at 1:8\n --> 1:20\n  |\n1 | \
+             Forall \"x\" (Exists \"x\" (Rel \"p\" [Var \"x\"]))\n  |                    ^"
+        );
+    }
+}
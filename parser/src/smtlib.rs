@@ -0,0 +1,643 @@
+use std::collections::HashMap;
+use std::mem;
+
+use pest::iterators::Pair;
+use pest::Parser;
+
+use crate::error::ParseError;
+use crate::formula::{Formula, Term};
+
+#[derive(Parser)]
+#[grammar = "smtlib.pest"]
+struct SmtParser;
+
+type Result<T> = std::result::Result<T, ParseError>;
+
+/// The sort of formulas. Every other sort is uninterpreted.
+pub const BOOL: &str = "Bool";
+
+/// The commands of a script, after checking sorts. Options and informations are dropped.
+#[derive(Debug)]
+pub enum Command {
+    DeclareSort(String),
+    /// `declare-fun` and `declare-const`: the name, the argument sorts and the result sort
+    DeclareFun(String, Vec<String>, String),
+    /// The asserted formula, with the sorts of the variables bound in it
+    Assert(Formula, Vec<(String, String)>),
+    CheckSat,
+    GetModel,
+    Push(usize),
+    Pop(usize),
+    Echo(String),
+    Exit,
+}
+
+#[derive(Clone)]
+enum Expr {
+    Term(Term, String),
+    Formula(Formula),
+}
+
+fn error<T>(pair: &Pair<Rule>, message: impl Into<String>) -> Result<T> {
+    Err(ParseError::at(pair.as_span().start_pos(), message))
+}
+
+fn symbol(pair: &Pair<Rule>) -> Option<String> {
+    let text = pair.as_str();
+    match pair.as_rule() {
+        Rule::simple_symbol => Some(text.to_string()),
+        Rule::quoted_symbol => Some(text[1..text.len() - 1].to_string()),
+        _ => None,
+    }
+}
+
+fn expect_symbol(pair: &Pair<Rule>) -> Result<String> {
+    symbol(pair).map_or_else(|| error(pair, "expected a symbol"), Ok)
+}
+
+fn expect_list<'a>(pair: &Pair<'a, Rule>) -> Result<Vec<Pair<'a, Rule>>> {
+    match pair.as_rule() {
+        Rule::list => Ok(pair.clone().into_inner().collect()),
+        _ => error(pair, "expected a list"),
+    }
+}
+
+fn expect_numeral(pair: &Pair<Rule>) -> Result<usize> {
+    match pair.as_rule() {
+        Rule::numeral => pair
+            .as_str()
+            .parse()
+            .map_or_else(|_| error(pair, "numeral too large"), Ok),
+        _ => error(pair, "expected a numeral"),
+    }
+}
+
+fn formula(pair: &Pair<Rule>, expr: Expr) -> Result<Formula> {
+    match expr {
+        Expr::Formula(formula) => Ok(formula),
+        Expr::Term(_, sort) => error(
+            pair,
+            format!("expected a formula, found a term of sort {}", sort),
+        ),
+    }
+}
+
+fn conjunction(formulas: Vec<Formula>) -> Formula {
+    let mut formulas = formulas.into_iter();
+    match formulas.next() {
+        Some(first) => formulas.fold(first, |acc, formula| {
+            Formula::And(Box::new(acc), Box::new(formula))
+        }),
+        None => Formula::True,
+    }
+}
+
+fn disjunction(formulas: Vec<Formula>) -> Formula {
+    let mut formulas = formulas.into_iter();
+    match formulas.next() {
+        Some(first) => formulas.fold(first, |acc, formula| {
+            Formula::Or(Box::new(acc), Box::new(formula))
+        }),
+        None => Formula::False,
+    }
+}
+
+/// The declarations in scope while reading a script.
+#[derive(Default)]
+struct Reader {
+    sorts: Vec<String>,
+    funs: HashMap<String, (Vec<String>, String)>,
+    /// For each `push`, the number of sorts and the functions declared since
+    scopes: Vec<(usize, Vec<String>)>,
+    /// Quantified variables and `let` bindings, innermost last
+    bindings: Vec<(String, Expr)>,
+    /// The sorts of the quantified variables, whose names are not reused with another sort
+    var_sorts: HashMap<String, String>,
+    /// The quantified variables of the assertion being read, with their sorts
+    asserted_vars: Vec<(String, String)>,
+}
+
+impl Reader {
+    fn sort(&self, pair: &Pair<Rule>) -> Result<String> {
+        let sort = expect_symbol(pair)?;
+        if sort != BOOL && !self.sorts.contains(&sort) {
+            return error(pair, format!("unknown sort {}", sort));
+        }
+        Ok(sort)
+    }
+
+    fn declare_fun(
+        &mut self,
+        pair: &Pair<Rule>,
+        name: String,
+        args: Vec<String>,
+        sort: String,
+    ) -> Result<()> {
+        if self.funs.contains_key(&name) {
+            return error(pair, format!("{} is already declared", name));
+        }
+        if args.iter().any(|arg| arg == BOOL) {
+            return error(pair, "Boolean arguments are not supported");
+        }
+        if let Some((_, names)) = self.scopes.last_mut() {
+            names.push(name.clone());
+        }
+        self.funs.insert(name, (args, sort));
+        Ok(())
+    }
+
+    /// A name for the variable `name` of `sort` that does not capture the variables of `let`
+    /// bindings and that no variable of another sort has.
+    fn fresh_var(&mut self, name: &str, sort: &str) -> String {
+        let is_taken = |candidate: &str| {
+            self.var_sorts
+                .get(candidate)
+                .is_some_and(|bound| bound != sort)
+                || self.bindings.iter().any(|(_, expr)| match expr {
+                    Expr::Term(Term::Var(var), _) => var == candidate,
+                    _ => false,
+                })
+        };
+        let mut candidate = name.to_string();
+        let mut idx = 0;
+        while is_taken(&candidate) {
+            idx += 1;
+            candidate = format!("{}!{}", name, idx);
+        }
+        self.var_sorts.insert(candidate.clone(), sort.to_string());
+        self.asserted_vars
+            .push((candidate.clone(), sort.to_string()));
+        candidate
+    }
+
+    fn quantifier(&mut self, items: &[Pair<Rule>], is_forall: bool) -> Result<Expr> {
+        if items.len() != 3 {
+            return error(&items[0], "expected variables and a body");
+        }
+        let mut vars = Vec::new();
+        for var in expect_list(&items[1])? {
+            let declaration = expect_list(&var)?;
+            if declaration.len() != 2 {
+                return error(&var, "expected a variable and its sort");
+            }
+            let sort = self.sort(&declaration[1])?;
+            if sort == BOOL {
+                return error(&declaration[1], "quantifying over Bool is not supported");
+            }
+            let name = self.fresh_var(&expect_symbol(&declaration[0])?, &sort);
+            vars.push(name.clone());
+            self.bindings.push((
+                expect_symbol(&declaration[0])?,
+                Expr::Term(Term::Var(name), sort),
+            ));
+        }
+        let body = self.expr(items[2].clone());
+        self.bindings.truncate(self.bindings.len() - vars.len());
+        let body = formula(&items[2], body?)?;
+        Ok(Expr::Formula(vars.into_iter().rev().fold(
+            body,
+            |acc, var| {
+                if is_forall {
+                    Formula::Forall(var, Box::new(acc))
+                } else {
+                    Formula::Exists(var, Box::new(acc))
+                }
+            },
+        )))
+    }
+
+    fn let_binding(&mut self, items: &[Pair<Rule>]) -> Result<Expr> {
+        if items.len() != 3 {
+            return error(&items[0], "expected bindings and a body");
+        }
+        // The bindings are parallel: they are all evaluated before any is in scope
+        let mut bindings = Vec::new();
+        for binding in expect_list(&items[1])? {
+            let binding = expect_list(&binding)?;
+            if binding.len() != 2 {
+                return error(&items[1], "expected a name and its value");
+            }
+            bindings.push((expect_symbol(&binding[0])?, self.expr(binding[1].clone())?));
+        }
+        let count = bindings.len();
+        self.bindings.extend(bindings);
+        let body = self.expr(items[2].clone());
+        self.bindings.truncate(self.bindings.len() - count);
+        body
+    }
+
+    fn expr(&mut self, pair: Pair<Rule>) -> Result<Expr> {
+        if let Some(name) = symbol(&pair) {
+            return self.apply(&pair, &name, vec![]);
+        }
+        let items = expect_list(&pair)?;
+        let head = match items.first() {
+            Some(head) => head,
+            None => return error(&pair, "expected an application"),
+        };
+        match expect_symbol(head)?.as_str() {
+            "forall" => self.quantifier(&items, true),
+            "exists" => self.quantifier(&items, false),
+            "let" => self.let_binding(&items),
+            // Annotations do not change the meaning
+            "!" if items.len() > 1 => self.expr(items[1].clone()),
+            name => {
+                let args = items[1..]
+                    .iter()
+                    .map(|arg| Ok((arg.clone(), self.expr(arg.clone())?)))
+                    .collect::<Result<Vec<_>>>()?;
+                self.apply(&pair, name, args)
+            }
+        }
+    }
+
+    fn apply(
+        &mut self,
+        pair: &Pair<Rule>,
+        name: &str,
+        args: Vec<(Pair<Rule>, Expr)>,
+    ) -> Result<Expr> {
+        if args.is_empty() {
+            if let Some((_, expr)) = self.bindings.iter().rev().find(|(bound, _)| bound == name) {
+                return Ok(expr.clone());
+            }
+        }
+        let formulas = |args: Vec<(Pair<Rule>, Expr)>| {
+            args.into_iter()
+                .map(|(pair, expr)| formula(&pair, expr))
+                .collect::<Result<Vec<_>>>()
+        };
+        let arity = args.len();
+        let result = match (name, arity) {
+            ("true", 0) => Formula::True,
+            ("false", 0) => Formula::False,
+            ("not", 1) => Formula::Not(Box::new(formulas(args)?.remove(0))),
+            ("and", _) => conjunction(formulas(args)?),
+            ("or", _) => disjunction(formulas(args)?),
+            ("=>", _) if arity >= 2 => {
+                let mut formulas = formulas(args)?;
+                let last = formulas.pop().unwrap();
+                formulas.into_iter().rev().fold(last, |acc, formula| {
+                    Formula::Implies(Box::new(formula), Box::new(acc))
+                })
+            }
+            ("xor", _) if arity >= 2 => {
+                let mut formulas = formulas(args)?.into_iter();
+                let first = formulas.next().unwrap();
+                formulas.fold(first, |acc, formula| {
+                    Formula::Not(Box::new(Formula::Iff(Box::new(acc), Box::new(formula))))
+                })
+            }
+            ("ite", 3) => {
+                let mut formulas = formulas(args)?.into_iter();
+                let (cond, a, b) = (
+                    formulas.next().unwrap(),
+                    formulas.next().unwrap(),
+                    formulas.next().unwrap(),
+                );
+                Formula::Or(
+                    Box::new(Formula::And(Box::new(cond.clone()), Box::new(a))),
+                    Box::new(Formula::And(
+                        Box::new(Formula::Not(Box::new(cond))),
+                        Box::new(b),
+                    )),
+                )
+            }
+            ("=", _) | ("distinct", _) if arity >= 2 => {
+                let operands = self.operands(args)?;
+                let equal = |a: &Expr, b: &Expr| match (a, b) {
+                    (Expr::Term(a, _), Expr::Term(b, _)) => {
+                        Formula::Rel("=".to_string(), vec![a.clone(), b.clone()])
+                    }
+                    (Expr::Formula(a), Expr::Formula(b)) => {
+                        Formula::Iff(Box::new(a.clone()), Box::new(b.clone()))
+                    }
+                    _ => unreachable!(),
+                };
+                if name == "=" {
+                    conjunction(
+                        operands
+                            .windows(2)
+                            .map(|pair| equal(&pair[0], &pair[1]))
+                            .collect(),
+                    )
+                } else {
+                    let mut disequalities = Vec::new();
+                    for (idx, a) in operands.iter().enumerate() {
+                        for b in &operands[idx + 1..] {
+                            disequalities.push(Formula::Not(Box::new(equal(a, b))));
+                        }
+                    }
+                    conjunction(disequalities)
+                }
+            }
+            _ => {
+                let (arg_sorts, sort) = match self.funs.get(name) {
+                    Some(signature) => signature.clone(),
+                    None => return error(pair, format!("unknown symbol {}", name)),
+                };
+                if arg_sorts.len() != arity {
+                    return error(
+                        pair,
+                        format!(
+                            "{} expects {} arguments, found {}",
+                            name,
+                            arg_sorts.len(),
+                            arity
+                        ),
+                    );
+                }
+                let mut terms = Vec::new();
+                for ((arg, expr), expected) in args.into_iter().zip(&arg_sorts) {
+                    match expr {
+                        Expr::Term(term, sort) if sort == *expected => terms.push(term),
+                        Expr::Term(_, sort) => {
+                            return error(
+                                &arg,
+                                format!("expected a term of sort {}, found {}", expected, sort),
+                            )
+                        }
+                        Expr::Formula(_) => {
+                            return error(&arg, format!("expected a term of sort {}", expected))
+                        }
+                    }
+                }
+                if sort == BOOL {
+                    Formula::Rel(name.to_string(), terms)
+                } else {
+                    return Ok(Expr::Term(Term::Fun(name.to_string(), terms), sort));
+                }
+            }
+        };
+        Ok(Expr::Formula(result))
+    }
+
+    /// The operands of `=` or `distinct`, which are all formulas or all terms of the same sort.
+    fn operands(&self, args: Vec<(Pair<Rule>, Expr)>) -> Result<Vec<Expr>> {
+        let sort_of = |expr: &Expr| match expr {
+            Expr::Term(_, sort) => sort.clone(),
+            Expr::Formula(_) => BOOL.to_string(),
+        };
+        let sort = sort_of(&args[0].1);
+        for (pair, expr) in &args[1..] {
+            if sort_of(expr) != sort {
+                return error(
+                    pair,
+                    format!(
+                        "expected an operand of sort {}, found {}",
+                        sort,
+                        sort_of(expr)
+                    ),
+                );
+            }
+        }
+        Ok(args.into_iter().map(|(_, expr)| expr).collect())
+    }
+
+    fn command(&mut self, pair: Pair<Rule>, commands: &mut Vec<Command>) -> Result<()> {
+        let items = expect_list(&pair)?;
+        let head = match items.first() {
+            Some(head) => expect_symbol(head)?,
+            None => return error(&pair, "expected a command"),
+        };
+        let expect_args = |count: usize| {
+            if items.len() == count + 1 {
+                Ok(())
+            } else {
+                error(&pair, format!("{} expects {} arguments", head, count))
+            }
+        };
+        match head.as_str() {
+            "set-logic" | "set-option" | "set-info" => {}
+            "declare-sort" => {
+                if items.len() != 2 && items.len() != 3 {
+                    return error(&pair, "declare-sort expects a name and an arity");
+                }
+                let name = expect_symbol(&items[1])?;
+                if items.len() == 3 && expect_numeral(&items[2])? != 0 {
+                    return error(&items[2], "sort parameters are not supported");
+                }
+                if name == BOOL || self.sorts.contains(&name) {
+                    return error(&items[1], format!("sort {} is already declared", name));
+                }
+                self.sorts.push(name.clone());
+                commands.push(Command::DeclareSort(name));
+            }
+            "declare-fun" => {
+                expect_args(3)?;
+                let name = expect_symbol(&items[1])?;
+                let args = expect_list(&items[2])?
+                    .iter()
+                    .map(|arg| self.sort(arg))
+                    .collect::<Result<Vec<_>>>()?;
+                let sort = self.sort(&items[3])?;
+                self.declare_fun(&pair, name.clone(), args.clone(), sort.clone())?;
+                commands.push(Command::DeclareFun(name, args, sort));
+            }
+            "declare-const" => {
+                expect_args(2)?;
+                let name = expect_symbol(&items[1])?;
+                let sort = self.sort(&items[2])?;
+                self.declare_fun(&pair, name.clone(), vec![], sort.clone())?;
+                commands.push(Command::DeclareFun(name, vec![], sort));
+            }
+            "assert" => {
+                expect_args(1)?;
+                self.asserted_vars.clear();
+                let expr = self.expr(items[1].clone())?;
+                let vars = mem::take(&mut self.asserted_vars);
+                commands.push(Command::Assert(formula(&items[1], expr)?, vars));
+            }
+            "check-sat" => {
+                expect_args(0)?;
+                commands.push(Command::CheckSat);
+            }
+            "get-model" => {
+                expect_args(0)?;
+                commands.push(Command::GetModel);
+            }
+            "push" | "pop" => {
+                let levels = match items.len() {
+                    1 => 1,
+                    _ => {
+                        expect_args(1)?;
+                        expect_numeral(&items[1])?
+                    }
+                };
+                if head == "push" {
+                    for _ in 0..levels {
+                        self.scopes.push((self.sorts.len(), Vec::new()));
+                    }
+                    commands.push(Command::Push(levels));
+                } else {
+                    if levels > self.scopes.len() {
+                        return error(&pair, "pop without a matching push");
+                    }
+                    for _ in 0..levels {
+                        let (sorts, funs) = self.scopes.pop().unwrap();
+                        self.sorts.truncate(sorts);
+                        for fun in funs {
+                            self.funs.remove(&fun);
+                        }
+                    }
+                    commands.push(Command::Pop(levels));
+                }
+            }
+            "echo" => {
+                expect_args(1)?;
+                let text = items[1].as_str();
+                if items[1].as_rule() != Rule::string {
+                    return error(&items[1], "expected a string");
+                }
+                commands.push(Command::Echo(text[1..text.len() - 1].replace("\"\"", "\"")));
+            }
+            "exit" => {
+                expect_args(0)?;
+                commands.push(Command::Exit);
+            }
+            _ => return error(&pair, format!("unsupported command {}", head)),
+        }
+        Ok(())
+    }
+}
+
+/// Parses an SMT-LIB 2 script in the logic of uninterpreted functions with quantifiers. Sorts
+/// are checked and kept apart from the formulas: they are given by the declarations and by the
+/// variables of each assertion, which are named apart from the variables of other sorts.
+/// Equality of terms is the relation `=`.
+pub fn parse_smtlib(input: &str) -> Result<Vec<Command>> {
+    let pairs = SmtParser::parse(Rule::script, input).map_err(|err| {
+        ParseError::from_pest(err, input, |rule| match rule {
+            Rule::list => "a list".to_string(),
+            Rule::simple_symbol | Rule::quoted_symbol => "a symbol".to_string(),
+            Rule::keyword => "a keyword".to_string(),
+            Rule::numeral | Rule::decimal | Rule::hexadecimal | Rule::binary | Rule::string => {
+                "a literal".to_string()
+            }
+            Rule::EOI => "the end of the input".to_string(),
+            _ => format!("{:?}", rule),
+        })
+    })?;
+    let mut reader = Reader::default();
+    let mut commands = Vec::new();
+    for pair in pairs {
+        if pair.as_rule() != Rule::EOI {
+            reader.command(pair, &mut commands)?;
+        }
+    }
+    Ok(commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_smtlib, Command};
+    use crate::formula::{Formula, Term};
+
+    #[test]
+    fn quantifiers_and_scopes() {
+        let commands = parse_smtlib(
+            "; Socrates\n\
+             (set-logic UF)\n\
+             (declare-sort S 0)\n\
+             (declare-fun human (S) Bool)\n\
+             (declare-const socrates S)\n\
+             (assert (forall ((x S)) (! (=> (human x) (exists ((y S)) (= x y))) :named ax)))\n\
+             (push)\n\
+             (declare-const plato S)\n\
+             (assert (let ((p plato) (q socrates)) (distinct p q)))\n\
+             (check-sat)\n\
+             (pop 1)\n\
+             (declare-const plato S)\n\
+             (get-model)\n",
+        )
+        .unwrap();
+        assert_eq!(commands.len(), 11);
+        match &commands[3] {
+            Command::Assert(Formula::Forall(x, body), _) => match body.as_ref() {
+                Formula::Implies(_, exists) => match exists.as_ref() {
+                    Formula::Exists(y, eq) => match eq.as_ref() {
+                        Formula::Rel(rel, args) => {
+                            assert_eq!(rel, "=");
+                            assert_eq!(args, &vec![Term::Var(x.clone()), Term::Var(y.clone())]);
+                        }
+                        formula => panic!("unexpected {:?}", formula),
+                    },
+                    formula => panic!("unexpected {:?}", formula),
+                },
+                formula => panic!("unexpected {:?}", formula),
+            },
+            command => panic!("unexpected {:?}", command),
+        }
+        match &commands[6] {
+            Command::Assert(Formula::Not(eq), _) => match eq.as_ref() {
+                Formula::Rel(_, args) => assert_eq!(
+                    args,
+                    &vec![
+                        Term::Fun("plato".to_string(), vec![]),
+                        Term::Fun("socrates".to_string(), vec![])
+                    ]
+                ),
+                formula => panic!("unexpected {:?}", formula),
+            },
+            command => panic!("unexpected {:?}", command),
+        }
+    }
+
+    #[test]
+    fn let_bindings_are_not_captured() {
+        let commands = parse_smtlib(
+            "(declare-sort S 0)\n\
+             (declare-fun p (S S) Bool)\n\
+             (assert (forall ((x S)) (let ((y x)) (forall ((x S)) (p y x)))))\n",
+        )
+        .unwrap();
+        match &commands[2] {
+            Command::Assert(Formula::Forall(x, body), _) => match body.as_ref() {
+                Formula::Forall(inner, _) => assert_ne!(x, inner),
+                formula => panic!("unexpected {:?}", formula),
+            },
+            command => panic!("unexpected {:?}", command),
+        }
+    }
+
+    #[test]
+    fn names_variables_apart_by_sort() {
+        let commands = parse_smtlib(
+            "(declare-sort S 0)\n\
+             (declare-sort T 0)\n\
+             (declare-fun p (S T) Bool)\n\
+             (assert (forall ((x S)) (exists ((y T)) (p x y))))\n\
+             (assert (forall ((y S) (x T)) (p y x)))\n",
+        )
+        .unwrap();
+        let vars = |command: &Command| match command {
+            Command::Assert(_, vars) => vars.clone(),
+            command => panic!("unexpected {:?}", command),
+        };
+        let pair = |var: &str, sort: &str| (var.to_string(), sort.to_string());
+        assert_eq!(vars(&commands[3]), vec![pair("x", "S"), pair("y", "T")]);
+        assert_eq!(vars(&commands[4]), vec![pair("y!1", "S"), pair("x!1", "T")]);
+    }
+
+    #[test]
+    fn rejects_ill_sorted_scripts() {
+        let script_error = |input| match parse_smtlib(input) {
+            Err(err) => err.line,
+            result => panic!("unexpected {:?}", result),
+        };
+        assert_eq!(script_error("(declare-fun f (S) S)"), 1);
+        assert_eq!(
+            script_error("(declare-sort S 0)\n(declare-const a S)\n(assert (and a true))"),
+            3
+        );
+        assert_eq!(
+            script_error("(push)\n(declare-sort S 0)\n(pop)\n(declare-const a S)"),
+            4
+        );
+        assert_eq!(script_error("(check-sat-assuming ())"), 1);
+        assert_eq!(
+            parse_smtlib("(assert").unwrap_err().message,
+            "expected a literal, a symbol, a keyword or a list"
+        );
+    }
+}
@@ -0,0 +1,455 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use pest::iterators::{Pair, Pairs};
+use pest::Parser;
+
+use crate::error::ParseError;
+use crate::formula::{Formula, FormulaBox, Term};
+use crate::signature::{Signature, SymbolKind, INDIVIDUAL};
+
+/// The type of sorts in TFF type declarations.
+const TYPE: &str = "$tType";
+
+#[derive(Parser)]
+#[grammar = "tptp.pest"]
+struct TptpParser;
+
+/// The formulas of a TPTP problem, split by role. Clauses are universally closed, and negated
+/// conjectures are hypotheses.
+#[derive(Debug, Default)]
+pub struct Problem {
+    pub axioms: Vec<Formula>,
+    pub conjectures: Vec<Formula>,
+}
+
+impl Problem {
+    /// The formula stating that the axioms imply the conjunction of the conjectures, or that the
+    /// axioms are unsatisfiable if there is no conjecture.
+    pub fn into_formula(self) -> FormulaBox {
+        fn conjunction(formulas: Vec<Formula>, empty: Formula) -> FormulaBox {
+            let mut formulas = formulas.into_iter().rev().map(Box::new);
+            match formulas.next() {
+                Some(last) => {
+                    formulas.fold(last, |acc, formula| Box::new(Formula::And(formula, acc)))
+                }
+                None => Box::new(empty),
+            }
+        }
+        let conclusion = if self.conjectures.is_empty() {
+            Box::new(Formula::False)
+        } else {
+            conjunction(self.conjectures, Formula::True)
+        };
+        Box::new(Formula::Implies(
+            conjunction(self.axioms, Formula::True),
+            conclusion,
+        ))
+    }
+}
+
+fn unquote(pair: Pair<Rule>) -> String {
+    let text = pair.as_str();
+    match pair.as_rule() {
+        Rule::single_quoted => text[1..text.len() - 1]
+            .replace("\\'", "'")
+            .replace("\\\\", "\\"),
+        _ => text.to_string(),
+    }
+}
+
+fn build_terms(pairs: Pairs<Rule>, signature: &mut Signature) -> Result<Vec<Term>, ParseError> {
+    pairs.map(|pair| build_term(pair, signature)).collect()
+}
+
+fn build_term(pair: Pair<Rule>, signature: &mut Signature) -> Result<Term, ParseError> {
+    match pair.as_rule() {
+        Rule::variable => Ok(Term::Var(signature.var(pair.as_str()))),
+        Rule::function_term => {
+            let pos = pair.as_span().start_pos();
+            let mut inner = pair.into_inner();
+            let functor = unquote(inner.next().unwrap().into_inner().next().unwrap());
+            let args = build_terms(inner, signature)?;
+            let functor = signature.use_symbol(&functor, SymbolKind::Function, args.len(), pos)?;
+            Ok(Term::Fun(functor, args))
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn build_atom(pair: Pair<Rule>, signature: &mut Signature) -> Result<Formula, ParseError> {
+    let pos = pair.as_span().start_pos();
+    let (rel, args) = match pair.as_rule() {
+        Rule::defined_true => return Ok(Formula::True),
+        Rule::defined_false => return Ok(Formula::False),
+        Rule::equality | Rule::disequality => (
+            "=".to_string(),
+            build_terms(pair.clone().into_inner(), signature)?,
+        ),
+        Rule::plain_atom => {
+            let mut inner = pair.clone().into_inner();
+            let rel = unquote(inner.next().unwrap().into_inner().next().unwrap());
+            (rel, build_terms(inner, signature)?)
+        }
+        _ => unreachable!(),
+    };
+    let rel = signature.use_symbol(&rel, SymbolKind::Relation, args.len(), pos)?;
+    let atom = Formula::Rel(rel, args);
+    match pair.as_rule() {
+        Rule::disequality => Ok(Formula::Not(Box::new(atom))),
+        _ => Ok(atom),
+    }
+}
+
+fn build_fof(pair: Pair<Rule>, signature: &mut Signature) -> Result<Formula, ParseError> {
+    Ok(match pair.as_rule() {
+        Rule::fof_formula => {
+            let mut inner = pair.into_inner();
+            let first = build_fof(inner.next().unwrap(), signature)?;
+            let rest = match inner.next() {
+                Some(rest) => rest,
+                None => return Ok(first),
+            };
+            let rule = rest.as_rule();
+            let mut operands = rest.into_inner();
+            match rule {
+                Rule::fof_nonassoc_rest => {
+                    let connective = operands.next().unwrap().as_rule();
+                    let (a, b) = (
+                        Box::new(first),
+                        Box::new(build_fof(operands.next().unwrap(), signature)?),
+                    );
+                    match connective {
+                        Rule::iff => Formula::Iff(a, b),
+                        Rule::implies => Formula::Implies(a, b),
+                        Rule::implied_by => Formula::Implies(b, a),
+                        Rule::xor => Formula::Not(Box::new(Formula::Iff(a, b))),
+                        Rule::nor => Formula::Not(Box::new(Formula::Or(a, b))),
+                        Rule::nand => Formula::Not(Box::new(Formula::And(a, b))),
+                        _ => unreachable!(),
+                    }
+                }
+                Rule::fof_or_rest | Rule::fof_and_rest => {
+                    let mut res = first;
+                    for operand in operands {
+                        let (a, b) = (Box::new(res), Box::new(build_fof(operand, signature)?));
+                        res = match rule {
+                            Rule::fof_or_rest => Formula::Or(a, b),
+                            _ => Formula::And(a, b),
+                        };
+                    }
+                    res
+                }
+                _ => unreachable!(),
+            }
+        }
+        Rule::fof_quantified => {
+            let mut inner = pair.into_inner();
+            let is_forall = inner.next().unwrap().as_rule() == Rule::forall;
+            let mut vars = Vec::new();
+            for var in inner.next().unwrap().into_inner() {
+                let pos = var.as_span().start_pos();
+                let (name, sort) = match var.as_rule() {
+                    Rule::typed_variable => {
+                        let mut inner = var.into_inner();
+                        let name = inner.next().unwrap().as_str();
+                        let sort = inner.next().unwrap();
+                        let sort_pos = sort.as_span().start_pos();
+                        let sort = unquote(sort.into_inner().next().unwrap());
+                        if !signature.sorts().is_sort(&sort) {
+                            let message = format!("unknown sort `{}`", sort);
+                            return Err(signature.error(sort_pos, message));
+                        }
+                        (name, sort)
+                    }
+                    _ => (var.as_str(), INDIVIDUAL.to_string()),
+                };
+                vars.push(signature.bind_with_sort(name, &sort, pos));
+            }
+            let body = build_fof(inner.next().unwrap(), signature)?;
+            signature.unbind(vars.len());
+            vars.into_iter().rev().fold(body, |acc, var| {
+                if is_forall {
+                    Formula::Forall(var, Box::new(acc))
+                } else {
+                    Formula::Exists(var, Box::new(acc))
+                }
+            })
+        }
+        Rule::fof_negation => Formula::Not(Box::new(build_fof(
+            pair.into_inner().next().unwrap(),
+            signature,
+        )?)),
+        _ => build_atom(pair, signature)?,
+    })
+}
+
+fn build_cnf(pair: Pair<Rule>, signature: &mut Signature) -> Result<Formula, ParseError> {
+    let mut literals = pair.into_inner().map(|literal| match literal.as_rule() {
+        Rule::cnf_negation => Ok(Formula::Not(Box::new(build_atom(
+            literal.into_inner().next().unwrap(),
+            signature,
+        )?))),
+        _ => build_atom(literal, signature),
+    });
+    let first = literals.next().unwrap()?;
+    literals.try_fold(first, |acc, literal| {
+        Ok(Formula::Or(Box::new(acc), Box::new(literal?)))
+    })
+}
+
+/// Declares the sort or the symbol of the typed atom `pair`.
+fn declare(pair: Pair<Rule>, signature: &mut Signature) -> Result<(), ParseError> {
+    let pos = pair.as_span().start_pos();
+    let mut inner = pair.into_inner();
+    let first = inner.next().unwrap();
+    if first.as_rule() == Rule::typed_atom {
+        return declare(first, signature);
+    }
+    let name = unquote(first);
+    let atomic_type = |pair: Pair<Rule>| unquote(pair.into_inner().next().unwrap());
+    let sort = inner.next().unwrap();
+    match sort.as_rule() {
+        Rule::atomic_type if sort.as_str() == TYPE => signature.declare_sort(&name, pos),
+        Rule::atomic_type => signature.declare_symbol(&name, vec![], atomic_type(sort), pos),
+        Rule::mapping_type => {
+            let mut inner = sort.into_inner();
+            let args = inner
+                .next()
+                .unwrap()
+                .into_inner()
+                .map(atomic_type)
+                .collect();
+            let sort = atomic_type(inner.next().unwrap());
+            signature.declare_symbol(&name, args, sort, pos)
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn free_variables(formula: &Formula, bound: &mut Vec<String>, free: &mut Vec<String>) {
+    fn term_variables(term: &Term, bound: &[String], free: &mut Vec<String>) {
+        match term {
+            Term::Var(var) => {
+                if !bound.contains(var) && !free.contains(var) {
+                    free.push(var.clone());
+                }
+            }
+            Term::Fun(_, args) => {
+                for arg in args {
+                    term_variables(arg, bound, free);
+                }
+            }
+        }
+    }
+    match formula {
+        Formula::True | Formula::False => {}
+        Formula::Rel(_, args) => {
+            for arg in args {
+                term_variables(arg, bound, free);
+            }
+        }
+        Formula::Not(phi) => free_variables(phi, bound, free),
+        Formula::Or(a, b) | Formula::And(a, b) | Formula::Implies(a, b) | Formula::Iff(a, b) => {
+            free_variables(a, bound, free);
+            free_variables(b, bound, free);
+        }
+        Formula::Exists(var, phi) | Formula::Forall(var, phi) => {
+            bound.push(var.clone());
+            free_variables(phi, bound, free);
+            bound.pop();
+        }
+    }
+}
+
+/// Universally quantifies the free variables of `formula`.
+fn universal_closure(formula: Formula) -> Formula {
+    let mut free = Vec::new();
+    free_variables(&formula, &mut Vec::new(), &mut free);
+    free.into_iter()
+        .rev()
+        .fold(formula, |acc, var| Formula::Forall(var, Box::new(acc)))
+}
+
+fn describe(rule: &Rule) -> String {
+    match rule {
+        Rule::fof_annotated => "`fof(...)`",
+        Rule::tff_annotated => "`tff(...)`",
+        Rule::cnf_annotated => "`cnf(...)`",
+        Rule::include => "`include(...)`",
+        Rule::variable => "a variable",
+        Rule::functor => "a functor",
+        Rule::fof_variables => "variables",
+        Rule::role => "a role",
+        Rule::EOI => "the end of the input",
+        _ => return format!("{:?}", rule).replace('_', " "),
+    }
+    .to_string()
+}
+
+/// Adds the formulas of `input` to `problem`, keeping only those named in `selection` if given.
+fn collect(
+    input: &str,
+    include_dir: &Path,
+    selection: Option<&HashSet<String>>,
+    problem: &mut Problem,
+    signature: &mut Signature,
+) -> Result<(), ParseError> {
+    let pairs = TptpParser::parse(Rule::file, input).map_err(|err| {
+        let mut err = ParseError::from_pest(err, input, describe);
+        err.file = signature.file.clone();
+        err
+    })?;
+    for pair in pairs {
+        let rule = pair.as_rule();
+        let mut inner = pair.into_inner();
+        match rule {
+            Rule::fof_annotated | Rule::tff_annotated | Rule::cnf_annotated => {
+                let name = unquote(inner.next().unwrap());
+                let role = inner.next().unwrap().as_str().to_string();
+                let body = inner.next().unwrap();
+                if selection.is_some_and(|selection| !selection.contains(&name)) {
+                    continue;
+                }
+                if body.as_rule() == Rule::typed_atom {
+                    declare(body, signature)?;
+                    continue;
+                }
+                let formula = universal_closure(match rule {
+                    Rule::cnf_annotated => build_cnf(body, signature)?,
+                    _ => build_fof(body, signature)?,
+                });
+                match role.as_str() {
+                    "conjecture" => problem.conjectures.push(formula),
+                    _ => problem.axioms.push(formula),
+                }
+            }
+            Rule::include => {
+                let pos = inner.peek().unwrap().as_span().start_pos();
+                let path = unquote(inner.next().unwrap());
+                let names = inner
+                    .next()
+                    .map(|names| names.into_inner().map(unquote).collect::<HashSet<_>>());
+                let contents = fs::read_to_string(include_dir.join(&path)).map_err(|err| {
+                    let mut err = ParseError::at(pos, format!("cannot include {}: {}", path, err));
+                    err.file = signature.file.clone();
+                    err
+                })?;
+                let file = signature.file.replace(path);
+                let res = collect(&contents, include_dir, names.as_ref(), problem, signature);
+                signature.file = file;
+                res?;
+            }
+            Rule::EOI => {}
+            _ => unreachable!(),
+        }
+    }
+    Ok(())
+}
+
+/// Parses a TPTP problem in the FOF, CNF or TFF language. Included files are resolved relative to
+/// `include_dir`. Equality is the relation `=`. The sorts declared by TFF type declarations are
+/// recorded in the signature.
+pub fn parse_tptp(input: &str, include_dir: &Path) -> Result<Problem, ParseError> {
+    parse_tptp_with(input, include_dir, &mut Signature::new())
+}
+
+/// Parses a TPTP problem, recording its symbols and warnings in `signature`.
+pub fn parse_tptp_with(
+    input: &str,
+    include_dir: &Path,
+    signature: &mut Signature,
+) -> Result<Problem, ParseError> {
+    let mut problem = Problem::default();
+    collect(input, include_dir, None, &mut problem, signature)?;
+    Ok(problem)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{parse_tptp, parse_tptp_with};
+    use crate::formula::Formula;
+    use crate::signature::{Signature, INDIVIDUAL};
+
+    #[test]
+    fn roles_and_connectives() {
+        let problem = parse_tptp(
+            "% Socrates\n\
+             fof(human, axiom, ![X]: (human(X) => mortal(X))).\n\
+             cnf(socrates, hypothesis, human(socrates) | ~ 'dead'(X)).\n\
+             fof(goal, conjecture, ?[X, Y]: (mortal(X) & X = Y & ~ f(Y) != g)).\n",
+            Path::new("."),
+        )
+        .unwrap();
+        assert_eq!(problem.axioms.len(), 2);
+        assert_eq!(problem.conjectures.len(), 1);
+        match &problem.axioms[1] {
+            Formula::Forall(var, clause) => {
+                assert_eq!(var, "X");
+                assert!(matches!(clause.as_ref(), Formula::Or(_, _)));
+            }
+            formula => panic!("clause not closed: {:?}", formula),
+        }
+        assert!(matches!(
+            &problem.conjectures[0],
+            Formula::Exists(_, phi) if matches!(phi.as_ref(), Formula::Exists(_, _))
+        ));
+    }
+
+    #[test]
+    fn typed_declarations() {
+        let mut signature = Signature::new();
+        let problem = parse_tptp_with(
+            "tff(person_type, type, person: $tType).\n\
+             tff(socrates_type, type, (socrates: person)).\n\
+             tff(father_type, type, father: person > person).\n\
+             tff(older_type, type, older: (person * $i) > $o).\n\
+             tff(older, axiom, ![X: person, T]: older(father(X), T)).\n\
+             tff(goal, conjecture, ?[X: $i, Y]: (older(socrates, X) & X = Y)).\n",
+            Path::new("."),
+            &mut signature,
+        )
+        .unwrap();
+        let sorts = signature.sorts();
+        assert_eq!(sorts.sorts, vec!["person".to_string()]);
+        assert_eq!(
+            sorts.funs["father"],
+            (vec!["person".to_string()], "person".to_string())
+        );
+        assert_eq!(sorts.funs["socrates"], (vec![], "person".to_string()));
+        assert_eq!(
+            sorts.rels["older"],
+            vec!["person".to_string(), INDIVIDUAL.to_string()]
+        );
+        // X is renamed where it is bound with a sort other than `INDIVIDUAL`
+        match &problem.axioms[0] {
+            Formula::Forall(x, _) => {
+                assert_ne!(x, "X");
+                assert_eq!(sorts.vars[x], "person");
+            }
+            formula => panic!("unexpected {:?}", formula),
+        }
+        assert!(matches!(&problem.conjectures[0], Formula::Exists(x, _) if x == "X"));
+        assert_eq!(sorts.vars.get("X"), None);
+        assert_eq!(sorts.vars.get("Y"), None);
+        let err = parse_tptp("tff(a, type, f: s > $i).", Path::new(".")).unwrap_err();
+        assert_eq!(err.message, "unknown sort `s`");
+        let err = parse_tptp("tff(a, axiom, ![X: s]: p(X)).", Path::new(".")).unwrap_err();
+        assert_eq!((err.column, err.message.as_str()), (20, "unknown sort `s`"));
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert!(parse_tptp("fof(a, axiom, p => q => r).", Path::new(".")).is_err());
+        let err = parse_tptp("\n include('missing.ax').", Path::new(".")).unwrap_err();
+        assert_eq!((err.line, err.column), (2, 10));
+        let err = parse_tptp("cnf(a, axiom, p(f(X)) | f(a)).", Path::new(".")).unwrap_err();
+        assert_eq!(
+            err.message,
+            "`f` is used as a relation here but as a function at 1:17"
+        );
+    }
+}
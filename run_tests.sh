@@ -1,132 +1,33 @@
 #!/bin/bash
 
-TIMEOUT_AMOUNT=10s
-TIMEOUT=$(if which timeout >& /dev/null; then echo "timeout"; else echo "gtimeout"; fi)
+TIMEOUT_SECONDS=10
 PROVER=FO-prover
 
-correct=0
-timeout=0
-total_A=0
-total_B=0
-total_C=0
-score=0
-
-for test in ./tests/A/t*.txt; do
-	[ -e "$test" ] || continue
-	
-	if [[ "$test" =~ ^./tests/A/t(.*).txt ]]
-	then
-		n="${BASH_REMATCH[1]}"
-	else
-		printf "no match\n"
-	fi
-	
-	echo -n "Running test A $n..."
-
-	# run the solver with a timeout
-	result=$(cat "$test" | $TIMEOUT -sHUP $TIMEOUT_AMOUNT ./"$PROVER")
-
-	if (( $? == 0 )) ; then
-
-		if (( $result == "1" )) ; then
-			# passing a positive test gains +1
-			score=$((score + 1))
-			echo "OK"
-		elif (( $result == "0" )) ; then
-			# failing a positive test gains -2
-			score=$((score - 2))
-			echo "FAIL"
-		else 
-			# abort on unexpected output
-			echo "unexpected output"
-			return -1
-		fi
-
-	else
-		# timeout a positive test gains 0
-		echo "TIMEOUT"
-	fi
-
-	total_A=$((total_A+1))
-done
-
-for test in ./tests/B/t*.txt; do
-	[ -e "$test" ] || continue
-	
-	if [[ "$test" =~ ^./tests/B/t(.*).txt ]]
-	then
-		n="${BASH_REMATCH[1]}"
-	else
-		printf "no match\n"
-	fi
-	
-	echo -n "Running test B $n..."
-
-	# run the solver with a timeout
-	result=$(cat "$test" | $TIMEOUT -sHUP $TIMEOUT_AMOUNT ./"$PROVER")
-
-	if (( $? == 0 )) ; then
-
-		if (( $result == "1" )) ; then
-			score=$((score - 2))
-			echo "FAIL"
-		elif (( $result == "0" )) ; then
-			score=$((score + 2))
-			echo "WOW"
-		else 
-			# abort on unexpected output
-			echo "unexpected output"
-			return -1
-		fi
-
-	else
-		# timeout a test B gains 1
-		score=$((score + 1))
-		echo "TIMEOUT (OK)"
-	fi
-
-	total_B=$((total_B+1))
-done
-
-for test in ./tests/C/t*.txt; do
-	[ -e "$test" ] || continue
-	
-	if [[ "$test" =~ ^./tests/C/t(.*).txt ]]
-	then
-		n="${BASH_REMATCH[1]}"
-	else
-		printf "no match\n"
-	fi
-	
-	echo -n "Running test C $n..."
-
-	# run the solver with a timeout
-	result=$(cat "$test" | $TIMEOUT -sHUP $TIMEOUT_AMOUNT ./"$PROVER")
-
-	if (( $? == 0 )) ; then
-
-		if (( $result == "1" )) ; then
-			score=$((score - 2))
-			echo "FAIL"
-		elif (( $result == "0" )) ; then
-			score=$((score + 0))
-			echo "OK"
-		else 
-			# abort on unexpected output
-			echo "unexpected output"
-			return -1
-		fi
-
-	else
-		score=$((score - 1))
-		echo "TIMEOUT"
-	fi
-
-	total_C=$((total_C+1))
-done
-
-total=$((total_A + total_B + total_C))
+results=$(mktemp)
+trap 'rm -f "$results"' EXIT
+
+# solve every test in one batch, each in a process killed after the timeout, printing a row of
+# results for each
+./"$PROVER" --timeout "$TIMEOUT_SECONDS" --csv "$results" tests/A tests/B tests/C || exit 1
+
+# the points of each verdict on a test of each category, in which an error counts as unknown:
+# the tests of A are valid, and a valid verdict on the tests of B and C is wrong
+score=$(awk -F, '
+BEGIN {
+	points["A", "valid"] = 1;  points["A", "invalid"] = -2; points["A", "unknown"] = 0
+	points["B", "valid"] = -2; points["B", "invalid"] = 2;  points["B", "unknown"] = 1
+	points["C", "valid"] = -2; points["C", "invalid"] = 0;  points["C", "unknown"] = -1
+}
+NR > 1 {
+	n = split($1, path, "/")
+	verdict = $2 == "error" ? "unknown" : $2
+	score += points[path[n - 1], verdict]
+	total++
+}
+END { printf "%d %d\n", score, total }
+' "$results")
+read -r score total <<< "$score"
 
 echo "Score: $score/$total"
-echo -e "import math\nscore = min(max($score, 0), 81)*35/81\nprint(math.ceil(score), '=', score)" | python
+echo -e "import math\nscore = min(max($score, 0), 81)*35/81\nprint(math.ceil(score), '=', score)" | python3
 echo "$score" > "score.txt"
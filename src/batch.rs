@@ -0,0 +1,430 @@
+//! Solving many problems in one run, each with its own timeout, as a table of results.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use prover::{is_tautology_with_statistics, Statistics, UnknownReason, Verdict};
+
+use crate::{detect_format, parser_formula_to_fo_formula, read_problem, Format, Options, Problem};
+
+/// How a problem of a batch ended.
+enum Outcome {
+    Valid,
+    Invalid,
+    /// Why there is no verdict, as given by `UnknownReason`
+    Unknown(String),
+    /// The problem could not be read, or the prover failed on it
+    Error(String),
+}
+
+impl Outcome {
+    fn verdict(&self) -> &'static str {
+        match self {
+            Outcome::Valid => "valid",
+            Outcome::Invalid => "invalid",
+            Outcome::Unknown(_) => "unknown",
+            Outcome::Error(_) => "error",
+        }
+    }
+
+    /// Why there is no verdict, if there is none.
+    fn detail(&self) -> String {
+        match self {
+            Outcome::Unknown(detail) | Outcome::Error(detail) => detail.clone(),
+            _ => String::new(),
+        }
+    }
+}
+
+struct Row {
+    problem: PathBuf,
+    outcome: Outcome,
+    time: Duration,
+    stats: Statistics,
+}
+
+/// The files of `paths`, with the files of each directory in the order of their names. Hidden
+/// files such as `.DS_Store` are skipped.
+fn problem_files(paths: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let mut entries = fs::read_dir(path)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<io::Result<Vec<_>>>()?;
+            entries.retain(|entry| {
+                let hidden = entry
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+                entry.is_file() && !hidden
+            });
+            entries.sort();
+            files.extend(entries);
+        } else {
+            files.push(path.clone());
+        }
+    }
+    Ok(files)
+}
+
+/// The format of the problem `path`, from its extension if it is not given.
+fn format_of(path: &Path, input: &str, options: &Options) -> Format {
+    options
+        .format
+        .unwrap_or_else(|| match path.extension().and_then(|ext| ext.to_str()) {
+            Some("p") | Some("ax") => Format::Tptp,
+            Some("smt2") => Format::Smtlib,
+            _ => detect_format(input),
+        })
+}
+
+fn solve(path: &Path, options: &Options) -> (Outcome, Statistics) {
+    let input = match fs::read_to_string(path) {
+        Ok(input) => input,
+        Err(err) => return (Outcome::Error(err.to_string()), Statistics::default()),
+    };
+    let (formula, sorts) = match read_problem(&input, format_of(path, &input, options), options) {
+        Ok(Problem::Formula { formula, sorts, .. }) => (formula, sorts),
+        Ok(Problem::Script(_)) => {
+            let message = "SMT-LIB scripts are not supported in batch mode".to_string();
+            return (Outcome::Error(message), Statistics::default());
+        }
+        Err(err) => {
+            let message = format!("{}: {}", err.location(), err.message);
+            return (Outcome::Error(message), Statistics::default());
+        }
+    };
+    let (formula, mut translator) = match parser_formula_to_fo_formula(*formula, &sorts) {
+        Ok(translated) => translated,
+        Err(message) => return (Outcome::Error(message), Statistics::default()),
+    };
+    let mut prover_options = options.prover;
+    prover_options.config.deadline = options.timeout.map(|timeout| Instant::now() + timeout);
+    let (verdict, stats) = is_tautology_with_statistics(formula, &mut translator, &prover_options);
+    let outcome = match verdict {
+        Verdict::Valid(_) => Outcome::Valid,
+        Verdict::Invalid(_) => Outcome::Invalid,
+        Verdict::Unknown(reason) => Outcome::Unknown(reason.to_string()),
+    };
+    (outcome, stats)
+}
+
+/// The line a process solving a single problem of a batch reports its results with.
+fn report_line(outcome: &Outcome, stats: &Statistics) -> String {
+    format!(
+        "{}\t{}\t{}\t{}\t{}\t{}",
+        outcome.verdict(),
+        stats.instances,
+        stats.sat_calls,
+        stats.conflicts,
+        stats.decisions,
+        outcome.detail()
+    )
+}
+
+/// The results reported by `report_line`, if `report` is one.
+fn parse_report(report: &str) -> Option<(Outcome, Statistics)> {
+    let mut fields = report.trim_end_matches('\n').splitn(6, '\t');
+    let verdict = fields.next()?;
+    let stats = Statistics {
+        instances: fields.next()?.parse().ok()?,
+        sat_calls: fields.next()?.parse().ok()?,
+        conflicts: fields.next()?.parse().ok()?,
+        decisions: fields.next()?.parse().ok()?,
+        ..Statistics::default()
+    };
+    let detail = fields.next()?.to_string();
+    let outcome = match verdict {
+        "valid" => Outcome::Valid,
+        "invalid" => Outcome::Invalid,
+        "unknown" => Outcome::Unknown(detail),
+        "error" => Outcome::Error(detail),
+        _ => return None,
+    };
+    Some((outcome, stats))
+}
+
+/// Solves the problem `path` of a batch in this process, and reports its results on stdout.
+pub(crate) fn report(path: &Path, options: &Options) {
+    let (outcome, stats) = solve(path, options);
+    println!("{}", report_line(&outcome, &stats));
+}
+
+/// How long the process solving a problem may run past the timeout before it is killed.
+const GRACE_PERIOD: Duration = Duration::from_millis(100);
+
+/// Solves the problem `path` in a process of its own, so that a problem that does not stop at the
+/// timeout is killed, and a problem that crashes the prover only loses its own results.
+fn solve_in_child(path: &Path, options: &Options) -> (Outcome, Statistics) {
+    let failure = |message: String| (Outcome::Error(message), Statistics::default());
+    let child = env::current_exe().and_then(|exe| {
+        Command::new(exe)
+            .args(&options.solve_args)
+            .arg("--batch-problem")
+            .arg(path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()
+    });
+    let mut child = match child {
+        Ok(child) => child,
+        Err(err) => return failure(format!("cannot run the prover: {}", err)),
+    };
+    let kill_at = options
+        .timeout
+        .map(|timeout| Instant::now() + timeout + GRACE_PERIOD);
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => break,
+            Ok(None) if kill_at.is_some_and(|kill_at| Instant::now() >= kill_at) => {
+                // The process may have exited since, which leaves nothing to kill
+                let _ = child.kill();
+                let _ = child.wait();
+                let reason = UnknownReason::Timeout.to_string();
+                return (Outcome::Unknown(reason), Statistics::default());
+            }
+            Ok(None) => thread::sleep(Duration::from_millis(5)),
+            Err(err) => return failure(format!("cannot wait for the prover: {}", err)),
+        }
+    }
+    let output = match child.wait_with_output() {
+        Ok(output) => output,
+        Err(err) => return failure(format!("cannot read the results of the prover: {}", err)),
+    };
+    let report = String::from_utf8_lossy(&output.stdout);
+    match parse_report(&report) {
+        Some(results) if output.status.success() => results,
+        _ => failure(format!("the prover failed with {}", output.status)),
+    }
+}
+
+/// Quotes `field` if it has a separator, a quote or a line break.
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn json_string(string: &str) -> String {
+    let mut res = String::from("\"");
+    for c in string.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            c if c.is_control() => res.push_str(&format!("\\u{:04x}", c as u32)),
+            c => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
+
+fn to_csv(rows: &[Row]) -> String {
+    let mut res =
+        String::from("problem,verdict,seconds,instances,sat_calls,conflicts,decisions,detail\n");
+    for row in rows {
+        res.push_str(&format!(
+            "{},{},{:.3},{},{},{},{},{}\n",
+            csv_field(&row.problem.display().to_string()),
+            row.outcome.verdict(),
+            row.time.as_secs_f64(),
+            row.stats.instances,
+            row.stats.sat_calls,
+            row.stats.conflicts,
+            row.stats.decisions,
+            csv_field(&row.outcome.detail())
+        ));
+    }
+    res
+}
+
+fn to_json(rows: &[Row]) -> String {
+    let objects = rows
+        .iter()
+        .map(|row| {
+            let detail = row.outcome.detail();
+            format!(
+                "  {{\"problem\": {}, \"verdict\": \"{}\", \"seconds\": {:.3}, \"instances\": {}, \
+                 \"sat_calls\": {}, \"conflicts\": {}, \"decisions\": {}, \"detail\": {}}}",
+                json_string(&row.problem.display().to_string()),
+                row.outcome.verdict(),
+                row.time.as_secs_f64(),
+                row.stats.instances,
+                row.stats.sat_calls,
+                row.stats.conflicts,
+                row.stats.decisions,
+                if detail.is_empty() {
+                    "null".to_string()
+                } else {
+                    json_string(&detail)
+                }
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("[\n{}\n]\n", objects.join(",\n"))
+}
+
+/// Solves the problems of `options.batch`, printing a row of results as each one is solved, and
+/// writes the results as JSON and CSV if asked to.
+pub(crate) fn run(options: &Options) {
+    let files = problem_files(&options.batch).unwrap_or_else(|err| {
+        eprintln!("cannot read the problems: {}", err);
+        process::exit(4);
+    });
+    let width = files
+        .iter()
+        .map(|file| file.display().to_string().len())
+        .chain(Some("problem".len()))
+        .max()
+        .unwrap();
+    println!(
+        "{:width$}  {:7}  {:>8}  {:>9}  {:>9}  {:>9}  {:>9}",
+        "problem",
+        "verdict",
+        "time",
+        "instances",
+        "sat calls",
+        "conflicts",
+        "decisions",
+        width = width
+    );
+    let start = Instant::now();
+    let mut rows = Vec::new();
+    for file in files {
+        let problem_start = Instant::now();
+        let (outcome, stats) = solve_in_child(&file, options);
+        let row = Row {
+            problem: file,
+            outcome,
+            time: problem_start.elapsed(),
+            stats,
+        };
+        let line = format!(
+            "{:width$}  {:7}  {:>7.3}s  {:>9}  {:>9}  {:>9}  {:>9}  {}",
+            row.problem.display().to_string(),
+            row.outcome.verdict(),
+            row.time.as_secs_f64(),
+            row.stats.instances,
+            row.stats.sat_calls,
+            row.stats.conflicts,
+            row.stats.decisions,
+            row.outcome.detail(),
+            width = width
+        );
+        println!("{}", line.trim_end());
+        rows.push(row);
+    }
+    let count = |verdict| {
+        rows.iter()
+            .filter(|row| row.outcome.verdict() == verdict)
+            .count()
+    };
+    println!(
+        "{} problems in {:.1}s: {} valid, {} invalid, {} unknown, {} errors",
+        rows.len(),
+        start.elapsed().as_secs_f64(),
+        count("valid"),
+        count("invalid"),
+        count("unknown"),
+        count("error")
+    );
+    let outputs = [
+        (&options.json, to_json as fn(&[Row]) -> String),
+        (&options.csv, to_csv),
+    ];
+    for (path, write) in outputs {
+        if let Some(path) = path {
+            fs::write(path, write(&rows)).unwrap_or_else(|err| {
+                eprintln!("cannot write {}: {}", path.display(), err);
+                process::exit(1);
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use prover::Statistics;
+
+    use super::{parse_report, report_line, to_csv, to_json, Outcome, Row};
+
+    #[test]
+    fn writes_results_as_csv_and_json() {
+        let rows = vec![
+            Row {
+                problem: PathBuf::from("tests/A/t01.txt"),
+                outcome: Outcome::Valid,
+                time: Duration::from_millis(1500),
+                stats: Statistics {
+                    instances: 3,
+                    sat_calls: 2,
+                    conflicts: 1,
+                    decisions: 4,
+                    model_size: 0,
+                    complete_level: None,
+                },
+            },
+            Row {
+                problem: PathBuf::from("a, \"b\".p"),
+                outcome: Outcome::Error("1:2: expected \"(\"".to_string()),
+                time: Duration::from_millis(2),
+                stats: Statistics::default(),
+            },
+            Row {
+                problem: PathBuf::from("c.p"),
+                outcome: Outcome::Unknown("timeout".to_string()),
+                time: Duration::from_secs(10),
+                stats: Statistics::default(),
+            },
+        ];
+        assert_eq!(
+            to_csv(&rows),
+            "problem,verdict,seconds,instances,sat_calls,conflicts,decisions,detail\n\
+             tests/A/t01.txt,valid,1.500,3,2,1,4,\n\
+             \"a, \"\"b\"\".p\",error,0.002,0,0,0,0,\"1:2: expected \"\"(\"\"\"\n\
+             c.p,unknown,10.000,0,0,0,0,timeout\n"
+        );
+        assert_eq!(
+            to_json(&rows[1..]),
+            "[\n  \
+             {\"problem\": \"a, \\\"b\\\".p\", \"verdict\": \"error\", \"seconds\": 0.002, \
+             \"instances\": 0, \"sat_calls\": 0, \"conflicts\": 0, \"decisions\": 0, \
+             \"detail\": \"1:2: expected \\\"(\\\"\"},\n  \
+             {\"problem\": \"c.p\", \"verdict\": \"unknown\", \"seconds\": 10.000, \
+             \"instances\": 0, \"sat_calls\": 0, \"conflicts\": 0, \"decisions\": 0, \
+             \"detail\": \"timeout\"}\n]\n"
+        );
+    }
+
+    #[test]
+    fn reads_reported_results() {
+        let stats = Statistics {
+            instances: 3,
+            sat_calls: 2,
+            conflicts: 1,
+            decisions: 4,
+            ..Statistics::default()
+        };
+        let outcome = Outcome::Error("1:2: expected \"(\"\tor \")\"".to_string());
+        let (read, read_stats) =
+            parse_report(&format!("{}\n", report_line(&outcome, &stats))).expect("a report line");
+        assert_eq!(read.verdict(), "error");
+        assert_eq!(read.detail(), outcome.detail());
+        assert_eq!(read_stats, stats);
+        assert!(parse_report("").is_none());
+        assert!(parse_report("valid\t1\t2").is_none());
+    }
+}
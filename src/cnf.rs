@@ -0,0 +1,180 @@
+use std::{collections::BTreeSet, mem};
+
+use crate::fol::{self, NameAllocator, Rel, Term, Var};
+
+#[derive(PartialEq, Eq, Clone, Hash, Debug)]
+pub struct Literal {
+    pub positive: bool,
+    pub rel: Rel,
+    pub args: Vec<Term>,
+}
+
+impl Literal {
+    fn negated(&self) -> Literal {
+        Literal {
+            positive: !self.positive,
+            ..self.clone()
+        }
+    }
+}
+
+/// Disjunction of literals whose variables `vars` are implicitly universally quantified, each
+/// clause having its own.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Clause {
+    pub vars: Vec<Var>,
+    pub literals: Vec<Literal>,
+}
+
+impl Clause {
+    pub(crate) fn new(literals: Vec<Literal>) -> Self {
+        Clause {
+            vars: vars(&literals),
+            literals,
+        }
+    }
+}
+
+fn term_vars(term: &Term, vars: &mut BTreeSet<Var>) {
+    match term {
+        Term::Var(var) => {
+            vars.insert(*var);
+        }
+        Term::Fun(_, args) => {
+            for arg in args {
+                term_vars(arg, vars);
+            }
+        }
+    }
+}
+
+fn vars(literals: &[Literal]) -> Vec<Var> {
+    let mut vars = BTreeSet::new();
+    for literal in literals {
+        for arg in &literal.args {
+            term_vars(arg, &mut vars);
+        }
+    }
+    vars.into_iter().collect()
+}
+
+/// Clausal form of a quantifier-free formula in NNF, whose variables are universally quantified.
+/// Disjunctions are distributed over conjunctions unless that gives more clauses than defining
+/// the side with more clauses: the side is then replaced by an atom of a fresh relation from
+/// `rel_alloc` over its variables, which implies it. Syntactic tautologies are left out.
+pub(crate) fn clausify(formula: &fol::Formula, rel_alloc: &mut NameAllocator<Rel>) -> Vec<Clause> {
+    fn clauses(
+        formula: &fol::Formula,
+        rel_alloc: &mut NameAllocator<Rel>,
+        definitions: &mut Vec<Vec<Literal>>,
+    ) -> Vec<Vec<Literal>> {
+        match formula {
+            fol::Formula::True => vec![],
+            fol::Formula::False => vec![vec![]],
+            fol::Formula::Rel(rel, args) => vec![vec![Literal {
+                positive: true,
+                rel: *rel,
+                args: args.clone(),
+            }]],
+            fol::Formula::Not(phi) => match phi.as_ref() {
+                fol::Formula::Rel(rel, args) => vec![vec![Literal {
+                    positive: false,
+                    rel: *rel,
+                    args: args.clone(),
+                }]],
+                _ => panic!("expected NNF formula"),
+            },
+            fol::Formula::And(a, b) => {
+                let mut res = clauses(a, rel_alloc, definitions);
+                res.extend(clauses(b, rel_alloc, definitions));
+                res
+            }
+            fol::Formula::Or(a, b) => {
+                let mut a = clauses(a, rel_alloc, definitions);
+                let mut b = clauses(b, rel_alloc, definitions);
+                if a.len() * b.len() > a.len() + b.len() {
+                    if a.len() < b.len() {
+                        mem::swap(&mut a, &mut b);
+                    }
+                    let atom = Literal {
+                        positive: true,
+                        rel: rel_alloc.alloc(),
+                        args: vars(&a.concat()).into_iter().map(Term::Var).collect(),
+                    };
+                    for clause in a {
+                        definitions.push(Some(atom.negated()).into_iter().chain(clause).collect());
+                    }
+                    a = vec![vec![atom]];
+                }
+                let mut res = Vec::new();
+                for a in &a {
+                    for b in &b {
+                        res.push(a.iter().chain(b).cloned().collect());
+                    }
+                }
+                res
+            }
+            _ => panic!("expected quantifier-free NNF formula"),
+        }
+    }
+    let mut definitions = Vec::new();
+    let mut res = clauses(formula, rel_alloc, &mut definitions);
+    res.extend(definitions);
+    res.into_iter()
+        .filter_map(|literals| {
+            let mut unique = Vec::<Literal>::new();
+            for literal in literals {
+                if unique.contains(&literal.negated()) {
+                    return None;
+                }
+                if !unique.contains(&literal) {
+                    unique.push(literal);
+                }
+            }
+            Some(Clause::new(unique))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fol::{Formula, NameAllocator, Term};
+
+    use super::clausify;
+
+    #[test]
+    fn defines_disjunctions_of_several_clauses() {
+        let mut var_alloc = NameAllocator::default();
+        let (x, y, z) = (var_alloc.alloc(), var_alloc.alloc(), var_alloc.alloc());
+        let mut rel_alloc = NameAllocator::default();
+        let (p, q, r) = (rel_alloc.alloc(), rel_alloc.alloc(), rel_alloc.alloc());
+        let atom = |rel, var| Box::new(Formula::Rel(rel, vec![Term::Var(var)]));
+        // p(x) & q(y) & r(z), which is not distributed over
+        let conjunction = |x, y, z| {
+            Box::new(Formula::And(
+                atom(p, x),
+                Box::new(Formula::And(atom(q, y), atom(r, z))),
+            ))
+        };
+        let clauses = clausify(
+            &Formula::And(atom(p, x), conjunction(x, y, z)),
+            &mut rel_alloc,
+        );
+        assert_eq!(
+            clauses
+                .iter()
+                .map(|clause| clause.vars.clone())
+                .collect::<Vec<_>>(),
+            vec![vec![x], vec![x], vec![y], vec![z]]
+        );
+        // (p(x) & q(y) & r(z)) | (p(y) & q(z) & r(x)), which is defined by a fresh relation
+        let formula = Formula::Or(conjunction(x, y, z), conjunction(y, z, x));
+        let clauses = clausify(&formula, &mut rel_alloc);
+        assert_eq!(clauses.len(), 6);
+        assert!(clauses.iter().all(|clause| clause.literals.len() == 2));
+        assert!(clauses.iter().all(|clause| clause.vars == vec![x, y, z]));
+        // p(x) | not p(x) is a tautology
+        let tautology = Formula::Or(atom(p, x), Box::new(Formula::Not(atom(p, x))));
+        assert!(clausify(&tautology, &mut rel_alloc).is_empty());
+    }
+}
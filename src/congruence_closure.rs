@@ -0,0 +1,302 @@
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+
+use crate::fol::{Fun, Rel, Term};
+
+/// The ground literal `[not] rel(args)`.
+#[derive(PartialEq, Eq, Clone, Hash, Debug)]
+pub struct GroundLiteral {
+    pub positive: bool,
+    pub rel: Rel,
+    pub args: Vec<Term>,
+}
+
+impl GroundLiteral {
+    pub(crate) fn negated(&self) -> GroundLiteral {
+        GroundLiteral {
+            positive: !self.positive,
+            rel: self.rel,
+            args: self.args.clone(),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Hash)]
+enum Symbol {
+    Fun(Fun),
+    Rel(Rel),
+    True,
+}
+
+/// Why two nodes were merged.
+#[derive(Clone, Copy)]
+enum Reason {
+    /// The asserted literal with this index equates them.
+    Asserted(usize),
+    /// They are applications of the same symbol to equal arguments.
+    Congruence(usize, usize),
+}
+
+/// Decides conjunctions of ground literals in the theory of equality (with `equality` as the
+/// equality relation). An atom `rel(args)` is the application of the symbol `rel` that is merged
+/// with the constant `True` when the atom holds.
+///
+/// The classes are closed under congruence as the literals are asserted. Each merge is recorded
+/// as an edge of a proof forest, from which the literals implying an equality are read off.
+pub(crate) struct CongruenceClosure {
+    equality: Rel,
+    nodes: HashMap<(Symbol, Vec<usize>), usize>,
+    apps: Vec<(Symbol, Vec<usize>)>,
+    reprs: Vec<usize>,
+    classes: Vec<Vec<usize>>, // the nodes of each class, at its representative
+    uses: Vec<Vec<usize>>,    // the applications to the nodes of each class, at its representative
+    signatures: HashMap<(Symbol, Vec<usize>), usize>,
+    proofs: Vec<Option<(usize, Reason)>>, // proof forest over the nodes of each class
+    disequalities: Vec<(usize, usize, usize)>, // with the index of the asserted literal
+    asserted: usize,
+}
+
+impl CongruenceClosure {
+    pub(crate) fn new(equality: Rel) -> Self {
+        Self {
+            equality,
+            nodes: HashMap::new(),
+            apps: Vec::new(),
+            reprs: Vec::new(),
+            classes: Vec::new(),
+            uses: Vec::new(),
+            signatures: HashMap::new(),
+            proofs: Vec::new(),
+            disequalities: Vec::new(),
+            asserted: 0,
+        }
+    }
+
+    fn node(&mut self, symbol: Symbol, args: Vec<usize>) -> usize {
+        if let Some(node) = self.nodes.get(&(symbol, args.clone())) {
+            return *node;
+        }
+        let node = self.apps.len();
+        self.nodes.insert((symbol, args.clone()), node);
+        for arg in &args {
+            self.uses[self.reprs[*arg]].push(node);
+        }
+        self.apps.push((symbol, args));
+        self.reprs.push(node);
+        self.classes.push(vec![node]);
+        self.uses.push(Vec::new());
+        self.proofs.push(None);
+        match self.signatures.entry(self.signature(node)) {
+            Entry::Occupied(entry) => {
+                let other = *entry.get();
+                self.merge(node, other, Reason::Congruence(node, other));
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(node);
+            }
+        }
+        node
+    }
+
+    /// The node of the ground term `term`.
+    pub(crate) fn term(&mut self, term: &Term) -> usize {
+        match term {
+            Term::Var(var) => panic!("{:?} is not ground", var),
+            Term::Fun(fun, args) => {
+                let args = args.iter().map(|arg| self.term(arg)).collect();
+                self.node(Symbol::Fun(*fun), args)
+            }
+        }
+    }
+
+    /// The representative of the class of `node`.
+    pub(crate) fn find(&self, node: usize) -> usize {
+        self.reprs[node]
+    }
+
+    fn signature(&self, node: usize) -> (Symbol, Vec<usize>) {
+        let (symbol, args) = &self.apps[node];
+        (*symbol, args.iter().map(|arg| self.reprs[*arg]).collect())
+    }
+
+    /// Merges the classes of `a` and `b`, and then those of the applications that become
+    /// congruent.
+    fn merge(&mut self, a: usize, b: usize, reason: Reason) {
+        let mut pending = vec![(a, b, reason)];
+        while let Some((mut a, mut b, reason)) = pending.pop() {
+            if self.reprs[a] == self.reprs[b] {
+                continue;
+            }
+            // The smaller class joins the larger one, and its proof tree hangs below `b`
+            if self.classes[self.reprs[a]].len() > self.classes[self.reprs[b]].len() {
+                std::mem::swap(&mut a, &mut b);
+            }
+            self.reroot(a);
+            self.proofs[a] = Some((b, reason));
+            let (from, to) = (self.reprs[a], self.reprs[b]);
+            for node in std::mem::take(&mut self.classes[from]) {
+                self.reprs[node] = to;
+                self.classes[to].push(node);
+            }
+            for app in std::mem::take(&mut self.uses[from]) {
+                match self.signatures.entry(self.signature(app)) {
+                    Entry::Occupied(entry) => {
+                        let other = *entry.get();
+                        pending.push((app, other, Reason::Congruence(app, other)));
+                    }
+                    Entry::Vacant(entry) => {
+                        entry.insert(app);
+                    }
+                }
+                self.uses[to].push(app);
+            }
+        }
+    }
+
+    /// Makes `node` the root of its proof tree by reversing the edges to the root.
+    fn reroot(&mut self, mut node: usize) {
+        let mut child = None;
+        while let Some((parent, reason)) = std::mem::replace(&mut self.proofs[node], child) {
+            child = Some((node, reason));
+            node = parent;
+        }
+    }
+
+    pub(crate) fn assert_literal(&mut self, literal: &GroundLiteral) {
+        let (a, b) = if literal.rel == self.equality && literal.args.len() == 2 {
+            (self.term(&literal.args[0]), self.term(&literal.args[1]))
+        } else {
+            let args = literal.args.iter().map(|arg| self.term(arg)).collect();
+            (
+                self.node(Symbol::Rel(literal.rel), args),
+                self.node(Symbol::True, vec![]),
+            )
+        };
+        if literal.positive {
+            self.merge(a, b, Reason::Asserted(self.asserted));
+        } else {
+            self.disequalities.push((a, b, self.asserted));
+        }
+        self.asserted += 1;
+    }
+
+    /// Checks whether the asserted literals are satisfiable.
+    pub(crate) fn is_consistent(&self) -> bool {
+        self.disequalities
+            .iter()
+            .all(|(a, b, _)| self.reprs[*a] != self.reprs[*b])
+    }
+
+    /// The indices of asserted literals that are inconsistent, if the asserted literals are:
+    /// a violated disequality and the equalities on the paths between its sides in the proof
+    /// forest, explaining the congruences on them recursively.
+    fn conflict(&self) -> Option<Vec<usize>> {
+        let (a, b, disequality) = *self
+            .disequalities
+            .iter()
+            .find(|(a, b, _)| self.reprs[*a] == self.reprs[*b])?;
+        let mut core = vec![disequality];
+        let mut explained = HashSet::new();
+        let mut pending = vec![(a, b)];
+        while let Some((a, b)) = pending.pop() {
+            let mut ancestors = HashSet::new();
+            let mut node = Some(a);
+            while let Some(ancestor) = node {
+                ancestors.insert(ancestor);
+                node = self.proofs[ancestor].map(|(parent, _)| parent);
+            }
+            let mut ancestor = b;
+            while !ancestors.contains(&ancestor) {
+                ancestor = self.proofs[ancestor].unwrap().0;
+            }
+            for mut node in [a, b] {
+                while node != ancestor {
+                    let (parent, reason) = self.proofs[node].unwrap();
+                    if explained.insert(node) {
+                        match reason {
+                            Reason::Asserted(idx) => core.push(idx),
+                            Reason::Congruence(x, y) => {
+                                pending.extend(
+                                    self.apps[x]
+                                        .1
+                                        .iter()
+                                        .copied()
+                                        .zip(self.apps[y].1.iter().copied()),
+                                );
+                            }
+                        }
+                    }
+                    node = parent;
+                }
+            }
+        }
+        core.sort_unstable();
+        core.dedup();
+        Some(core)
+    }
+}
+
+pub(crate) fn is_consistent(literals: &[GroundLiteral], equality: Rel) -> bool {
+    let mut closure = CongruenceClosure::new(equality);
+    for literal in literals {
+        closure.assert_literal(literal);
+    }
+    closure.is_consistent()
+}
+
+/// Returns a subset of `literals` that is inconsistent in the theory of equality, if `literals`
+/// are inconsistent.
+pub(crate) fn conflict(literals: &[GroundLiteral], equality: Rel) -> Option<Vec<GroundLiteral>> {
+    let mut closure = CongruenceClosure::new(equality);
+    for literal in literals {
+        closure.assert_literal(literal);
+    }
+    closure
+        .conflict()
+        .map(|core| core.into_iter().map(|idx| literals[idx].clone()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fol::{NameAllocator, Rel, Term};
+
+    use super::{conflict, is_consistent, GroundLiteral};
+
+    fn literal(positive: bool, rel: Rel, args: Vec<Term>) -> GroundLiteral {
+        GroundLiteral {
+            positive,
+            rel,
+            args,
+        }
+    }
+
+    #[test]
+    fn congruence() {
+        let mut rel_alloc = NameAllocator::default();
+        let (eq, p) = (rel_alloc.alloc(), rel_alloc.alloc());
+        let mut fun_alloc = NameAllocator::default();
+        let (f, a, b) = (fun_alloc.alloc(), fun_alloc.alloc(), fun_alloc.alloc());
+        let a = Term::Fun(a, vec![]);
+        let b = Term::Fun(b, vec![]);
+        let f = |term| Term::Fun(f, vec![term]);
+        // f(f(f(a))) = a and f(f(f(f(f(a))))) = a imply f(a) = a
+        let fff_a = f(f(f(a.clone())));
+        let literals = vec![
+            literal(true, eq, vec![fff_a.clone(), a.clone()]),
+            literal(true, eq, vec![f(f(fff_a)), a.clone()]),
+            literal(false, eq, vec![f(a.clone()), a.clone()]),
+        ];
+        assert!(!is_consistent(&literals, eq));
+        assert_eq!(conflict(&literals, eq).unwrap(), literals);
+        // a = b and P(f(a)) imply P(f(b))
+        let literals = vec![
+            literal(true, p, vec![f(a.clone())]),
+            literal(true, eq, vec![a.clone(), b.clone()]),
+            literal(false, p, vec![f(b.clone())]),
+            literal(false, eq, vec![f(a), b]),
+        ];
+        assert!(!is_consistent(&literals, eq));
+        let core = conflict(&literals, eq).unwrap();
+        assert_eq!(core.len(), 3);
+        assert!(is_consistent(&literals[1..], eq));
+    }
+}
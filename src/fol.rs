@@ -1,24 +1,61 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeSet, HashMap, HashSet},
     fmt, mem,
 };
 
 use itertools::Itertools;
 
+/// A sort of the many-sorted logic. Unsorted formulas are of the default sort.
 #[derive(PartialEq, Eq, Clone, Copy, Hash, Default, PartialOrd, Ord)]
-pub(crate) struct Var {
+pub struct Sort {
     name: usize,
 }
 
+impl fmt::Debug for Sort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "s_{}", self.name)
+    }
+}
+
+impl Sort {
+    pub fn index(&self) -> usize {
+        self.name
+    }
+}
+
+/// A variable, annotated with the sort it ranges over.
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Default, PartialOrd, Ord)]
+pub struct Var {
+    name: usize,
+    sort: Sort,
+}
+
 impl fmt::Debug for Var {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "v_{}", self.name)
     }
 }
 
+impl Var {
+    pub fn index(&self) -> usize {
+        self.name
+    }
+
+    pub fn sort(&self) -> Sort {
+        self.sort
+    }
+
+    /// The variable annotated with `sort` instead.
+    pub fn with_sort(self, sort: Sort) -> Self {
+        Self { sort, ..self }
+    }
+}
+
+/// A function, annotated with the sort of its values.
 #[derive(PartialEq, Eq, Clone, Copy, Hash, Default)]
-pub(crate) struct Fun {
+pub struct Fun {
     name: usize,
+    sort: Sort,
 }
 
 impl fmt::Debug for Fun {
@@ -27,8 +64,23 @@ impl fmt::Debug for Fun {
     }
 }
 
+impl Fun {
+    pub fn index(&self) -> usize {
+        self.name
+    }
+
+    pub fn sort(&self) -> Sort {
+        self.sort
+    }
+
+    /// The function annotated with `sort` instead.
+    pub fn with_sort(self, sort: Sort) -> Self {
+        Self { sort, ..self }
+    }
+}
+
 #[derive(PartialEq, Eq, Clone, Copy, Hash, Default)]
-pub(crate) struct Rel {
+pub struct Rel {
     name: usize,
 }
 
@@ -38,14 +90,29 @@ impl fmt::Debug for Rel {
     }
 }
 
-pub(crate) trait NextName {
+impl Rel {
+    pub fn index(&self) -> usize {
+        self.name
+    }
+}
+
+pub trait NextName {
     fn next_name(&self) -> Self;
 }
 
+impl NextName for Sort {
+    fn next_name(&self) -> Self {
+        Self {
+            name: self.name + 1,
+        }
+    }
+}
+
 impl NextName for Var {
     fn next_name(&self) -> Self {
         Self {
             name: self.name + 1,
+            sort: Sort::default(),
         }
     }
 }
@@ -54,6 +121,7 @@ impl NextName for Fun {
     fn next_name(&self) -> Self {
         Self {
             name: self.name + 1,
+            sort: Sort::default(),
         }
     }
 }
@@ -66,24 +134,50 @@ impl NextName for Rel {
     }
 }
 
-#[derive(Default)]
-pub(crate) struct NameAllocator<T> {
+#[derive(PartialEq, Eq, Clone, Default, Debug)]
+pub struct NameAllocator<T> {
     next: T,
 }
 
 impl<T: NextName + Default + Clone> NameAllocator<T> {
-    pub(crate) fn alloc(&mut self) -> T {
+    pub fn alloc(&mut self) -> T {
         let next = self.next.next_name();
         mem::replace(&mut self.next, next)
     }
 }
 
 #[derive(PartialEq, Eq, Clone, Hash)]
-pub(crate) enum Term {
+pub enum Term {
     Var(Var),
     Fun(Fun, Vec<Term>),
 }
 
+impl Term {
+    /// The nesting depth of the functions of the term, 0 for variables and constants.
+    pub fn depth(&self) -> usize {
+        match self {
+            Term::Var(_) => 0,
+            Term::Fun(_, args) => args.iter().map(|arg| arg.depth() + 1).max().unwrap_or(0),
+        }
+    }
+
+    /// The sort of the values of the term.
+    pub fn sort(&self) -> Sort {
+        match self {
+            Term::Var(var) => var.sort(),
+            Term::Fun(fun, _) => fun.sort(),
+        }
+    }
+
+    /// The number of occurrences of functions and variables in the term.
+    pub fn size(&self) -> usize {
+        match self {
+            Term::Var(_) => 1,
+            Term::Fun(_, args) => 1 + args.iter().map(Term::size).sum::<usize>(),
+        }
+    }
+}
+
 impl fmt::Debug for Term {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -94,7 +188,7 @@ impl fmt::Debug for Term {
 }
 
 #[derive(PartialEq, Eq, Clone, Hash)]
-pub(crate) enum Formula {
+pub enum Formula {
     True,
     False,
     Rel(Rel, Vec<Term>),
@@ -124,7 +218,7 @@ impl fmt::Debug for Formula {
     }
 }
 
-pub(crate) fn into_nnf(formula: Formula) -> (Formula, NameAllocator<Var>) {
+pub fn into_nnf(formula: Formula) -> (Formula, NameAllocator<Var>) {
     #[derive(Default)]
     struct Transformer {
         var_alloc: NameAllocator<Var>,
@@ -134,7 +228,10 @@ pub(crate) fn into_nnf(formula: Formula) -> (Formula, NameAllocator<Var>) {
     impl Transformer {
         fn remap_var(&mut self, var: Var) -> Var {
             let alloc = &mut self.var_alloc;
-            *self.vars.entry(var).or_insert_with(|| alloc.alloc())
+            *self
+                .vars
+                .entry(var)
+                .or_insert_with(|| alloc.alloc().with_sort(var.sort()))
         }
 
         fn with_shadowed_var<T, F: FnOnce(&mut Self) -> T>(&mut self, var: Var, fun: F) -> T {
@@ -161,7 +258,7 @@ pub(crate) fn into_nnf(formula: Formula) -> (Formula, NameAllocator<Var>) {
             }
         }
 
-        fn into_nnf(&mut self, formula: Formula) -> Formula {
+        fn nnf(&mut self, formula: Formula) -> Formula {
             match formula {
                 Formula::True => Formula::True,
                 Formula::False => Formula::False,
@@ -172,17 +269,12 @@ pub(crate) fn into_nnf(formula: Formula) -> (Formula, NameAllocator<Var>) {
                         .map(|term| self.remap_term(term))
                         .collect(),
                 ),
-                Formula::Or(a, b) => {
-                    Formula::Or(Box::new(self.into_nnf(*a)), Box::new(self.into_nnf(*b)))
-                }
-                Formula::And(a, b) => {
-                    Formula::And(Box::new(self.into_nnf(*a)), Box::new(self.into_nnf(*b)))
+                Formula::Or(a, b) => Formula::Or(Box::new(self.nnf(*a)), Box::new(self.nnf(*b))),
+                Formula::And(a, b) => Formula::And(Box::new(self.nnf(*a)), Box::new(self.nnf(*b))),
+                Formula::Implies(a, b) => {
+                    Formula::Or(Box::new(self.nnf(Formula::Not(a))), Box::new(self.nnf(*b)))
                 }
-                Formula::Implies(a, b) => Formula::Or(
-                    Box::new(self.into_nnf(Formula::Not(a))),
-                    Box::new(self.into_nnf(*b)),
-                ),
-                Formula::Iff(a, b) => self.into_nnf(Formula::Or(
+                Formula::Iff(a, b) => self.nnf(Formula::Or(
                     Box::new(Formula::And(a.clone(), b.clone())),
                     Box::new(Formula::And(
                         Box::new(Formula::Not(a)),
@@ -190,29 +282,27 @@ pub(crate) fn into_nnf(formula: Formula) -> (Formula, NameAllocator<Var>) {
                     )),
                 )),
                 Formula::Exists(var, phi) => self.with_shadowed_var(var, |me| {
-                    Formula::Exists(me.remap_var(var), Box::new(me.into_nnf(*phi)))
+                    Formula::Exists(me.remap_var(var), Box::new(me.nnf(*phi)))
                 }),
                 Formula::Forall(var, phi) => self.with_shadowed_var(var, |me| {
-                    Formula::Forall(me.remap_var(var), Box::new(me.into_nnf(*phi)))
+                    Formula::Forall(me.remap_var(var), Box::new(me.nnf(*phi)))
                 }),
                 Formula::Not(phi) => match *phi {
                     Formula::True => Formula::False,
                     Formula::False => Formula::True,
                     Formula::Rel(rel, terms) => {
-                        Formula::Not(Box::new(self.into_nnf(Formula::Rel(rel, terms))))
+                        Formula::Not(Box::new(self.nnf(Formula::Rel(rel, terms))))
                     }
-                    Formula::Or(a, b) => self.into_nnf(Formula::And(
+                    Formula::Or(a, b) => self.nnf(Formula::And(
                         Box::new(Formula::Not(a)),
                         Box::new(Formula::Not(b)),
                     )),
-                    Formula::And(a, b) => self.into_nnf(Formula::Or(
+                    Formula::And(a, b) => self.nnf(Formula::Or(
                         Box::new(Formula::Not(a)),
                         Box::new(Formula::Not(b)),
                     )),
-                    Formula::Implies(a, b) => {
-                        self.into_nnf(Formula::And(a, Box::new(Formula::Not(b))))
-                    }
-                    Formula::Iff(a, b) => self.into_nnf(Formula::And(
+                    Formula::Implies(a, b) => self.nnf(Formula::And(a, Box::new(Formula::Not(b)))),
+                    Formula::Iff(a, b) => self.nnf(Formula::And(
                         Box::new(Formula::Or(a.clone(), b.clone())),
                         Box::new(Formula::Or(
                             Box::new(Formula::Not(a)),
@@ -220,23 +310,23 @@ pub(crate) fn into_nnf(formula: Formula) -> (Formula, NameAllocator<Var>) {
                         )),
                     )),
                     Formula::Exists(var, phi) => self.with_shadowed_var(var, |me| {
-                        Formula::Forall(me.remap_var(var), Box::new(me.into_nnf(Formula::Not(phi))))
+                        Formula::Forall(me.remap_var(var), Box::new(me.nnf(Formula::Not(phi))))
                     }),
                     Formula::Forall(var, phi) => self.with_shadowed_var(var, |me| {
-                        Formula::Exists(me.remap_var(var), Box::new(me.into_nnf(Formula::Not(phi))))
+                        Formula::Exists(me.remap_var(var), Box::new(me.nnf(Formula::Not(phi))))
                     }),
-                    Formula::Not(phi) => self.into_nnf(*phi),
+                    Formula::Not(phi) => self.nnf(*phi),
                 },
             }
         }
     }
 
     let mut transformer = Transformer::default();
-    let formula = transformer.into_nnf(formula);
+    let formula = transformer.nnf(formula);
     (formula, transformer.var_alloc)
 }
 
-pub(crate) fn into_pnf(formula: Formula) -> Formula {
+pub fn into_pnf(formula: Formula) -> Formula {
     enum Quantifier {
         Exists(Var),
         Forall(Var),
@@ -282,7 +372,7 @@ pub(crate) fn into_pnf(formula: Formula) -> Formula {
         })
 }
 
-pub(crate) fn free_variables(formula: &Formula) -> HashSet<Var> {
+pub fn free_variables(formula: &Formula) -> HashSet<Var> {
     fn dfs_term(term: &Term, free_variables: &mut HashSet<Var>) {
         match term {
             Term::Var(var) => {
@@ -335,14 +425,53 @@ pub(crate) fn free_variables(formula: &Formula) -> HashSet<Var> {
     fv
 }
 
-pub(crate) fn into_sentence(formula: Formula) -> Formula {
+pub fn into_sentence(formula: Formula) -> Formula {
+    // In a fixed order, so that the clausal form can be derived again
     let fv = free_variables(&formula);
-    fv.into_iter().fold(formula, |formula, var| {
+    fv.into_iter().sorted().fold(formula, |formula, var| {
         Formula::Forall(var, Box::new(formula))
     })
 }
 
-pub(crate) fn skolemize(formula: Formula, fun_alloc: &mut NameAllocator<Fun>) -> Formula {
+/// Pushes the quantifiers of a formula in negation normal form inward over the conjunctions and
+/// disjunctions with a side in which their variable does not occur, and drops the quantifiers
+/// whose variable does not occur at all. Skolem functions then depend only on the universal
+/// variables their existential variable is really in the scope of.
+pub fn miniscope(formula: Formula) -> Formula {
+    /// Quantifies `formula`, in which the quantifiers are already pushed inward, over `var`.
+    fn quantify(forall: bool, var: Var, formula: Formula) -> Formula {
+        let occurs = |formula: &Formula| free_variables(formula).contains(&var);
+        match formula {
+            formula if !occurs(&formula) => formula,
+            Formula::And(a, b) if !occurs(&a) => {
+                Formula::And(a, Box::new(quantify(forall, var, *b)))
+            }
+            Formula::And(a, b) if !occurs(&b) => {
+                Formula::And(Box::new(quantify(forall, var, *a)), b)
+            }
+            Formula::Or(a, b) if !occurs(&a) => Formula::Or(a, Box::new(quantify(forall, var, *b))),
+            Formula::Or(a, b) if !occurs(&b) => Formula::Or(Box::new(quantify(forall, var, *a)), b),
+            // Quantifiers of the same kind commute
+            Formula::Forall(inner, phi) if forall => {
+                Formula::Forall(inner, Box::new(quantify(forall, var, *phi)))
+            }
+            Formula::Exists(inner, phi) if !forall => {
+                Formula::Exists(inner, Box::new(quantify(forall, var, *phi)))
+            }
+            formula if forall => Formula::Forall(var, Box::new(formula)),
+            formula => Formula::Exists(var, Box::new(formula)),
+        }
+    }
+    match formula {
+        Formula::And(a, b) => Formula::And(Box::new(miniscope(*a)), Box::new(miniscope(*b))),
+        Formula::Or(a, b) => Formula::Or(Box::new(miniscope(*a)), Box::new(miniscope(*b))),
+        Formula::Forall(var, phi) => quantify(true, var, miniscope(*phi)),
+        Formula::Exists(var, phi) => quantify(false, var, miniscope(*phi)),
+        formula => formula,
+    }
+}
+
+pub fn skolemize(formula: Formula, fun_alloc: &mut NameAllocator<Fun>) -> Formula {
     struct Skolemizer<'a> {
         env: Vec<Var>,
         varmap: HashMap<Var, Term>,
@@ -389,7 +518,7 @@ pub(crate) fn skolemize(formula: Formula, fun_alloc: &mut NameAllocator<Fun>) ->
                     let check = self.varmap.insert(
                         var,
                         Term::Fun(
-                            self.fun_alloc.alloc(),
+                            self.fun_alloc.alloc().with_sort(var.sort()),
                             self.env.iter().map(|x| Term::Var(*x)).collect(),
                         ),
                     );
@@ -416,11 +545,11 @@ pub(crate) fn skolemize(formula: Formula, fun_alloc: &mut NameAllocator<Fun>) ->
             varmap: HashMap::new(),
             fun_alloc,
         }
-        .skolemize(into_nnf(into_sentence(formula)).0),
+        .skolemize(miniscope(into_nnf(into_sentence(formula)).0)),
     )
 }
 
-pub(crate) fn func_sig(formula: &Formula) -> HashSet<(Fun, usize)> {
+pub fn func_sig(formula: &Formula) -> HashSet<(Fun, usize)> {
     fn dfs_term(term: &Term, func_sig: &mut HashSet<(Fun, usize)>) {
         match term {
             Term::Var(_) => {}
@@ -466,3 +595,120 @@ pub(crate) fn func_sig(formula: &Formula) -> HashSet<(Fun, usize)> {
     dfs(formula, &mut fs);
     fs
 }
+
+/// Calls `bound` with the variables quantified in `formula` and `term` with the terms and subterms
+/// of its atoms.
+fn visit(formula: &Formula, bound: &mut impl FnMut(Var), term: &mut impl FnMut(&Term)) {
+    fn visit_term(t: &Term, term: &mut impl FnMut(&Term)) {
+        term(t);
+        if let Term::Fun(_, args) = t {
+            for arg in args {
+                visit_term(arg, term);
+            }
+        }
+    }
+    match formula {
+        Formula::True | Formula::False => {}
+        Formula::Rel(_, args) => {
+            for arg in args {
+                visit_term(arg, term);
+            }
+        }
+        Formula::Not(phi) => visit(phi, bound, term),
+        Formula::Or(a, b) | Formula::And(a, b) | Formula::Implies(a, b) | Formula::Iff(a, b) => {
+            visit(a, bound, term);
+            visit(b, bound, term);
+        }
+        Formula::Exists(var, phi) | Formula::Forall(var, phi) => {
+            bound(*var);
+            visit(phi, bound, term);
+        }
+    }
+}
+
+/// The functions of `formula` with the sorts of their arguments.
+pub fn sorted_func_sig(formula: &Formula) -> HashSet<(Fun, Vec<Sort>)> {
+    let mut res = HashSet::new();
+    visit(formula, &mut |_| {}, &mut |term| {
+        if let Term::Fun(fun, args) = term {
+            res.insert((*fun, args.iter().map(Term::sort).collect()));
+        }
+    });
+    res
+}
+
+/// The sorts of the variables and terms of `formula`.
+pub fn sorts(formula: &Formula) -> BTreeSet<Sort> {
+    let mut res = BTreeSet::new();
+    let mut bound = BTreeSet::new();
+    visit(
+        formula,
+        &mut |var| {
+            bound.insert(var.sort());
+        },
+        &mut |term| {
+            res.insert(term.sort());
+        },
+    );
+    res.extend(bound);
+    res
+}
+
+/// Shorthands for the formulas built in tests.
+#[cfg(test)]
+pub(crate) mod builders {
+    use super::{Formula, Var};
+
+    pub(crate) fn forall(var: Var, phi: Formula) -> Formula {
+        Formula::Forall(var, Box::new(phi))
+    }
+
+    pub(crate) fn exists(var: Var, phi: Formula) -> Formula {
+        Formula::Exists(var, Box::new(phi))
+    }
+
+    pub(crate) fn implies(a: Formula, b: Formula) -> Formula {
+        Formula::Implies(Box::new(a), Box::new(b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{func_sig, miniscope, skolemize, Formula, Fun, NameAllocator, Rel, Term, Var};
+
+    #[test]
+    fn miniscoping_lowers_the_arity_of_skolem_functions() {
+        let mut var_alloc = NameAllocator::<Var>::default();
+        let (x, y) = (var_alloc.alloc(), var_alloc.alloc());
+        let mut rel_alloc = NameAllocator::<Rel>::default();
+        let (p, q) = (rel_alloc.alloc(), rel_alloc.alloc());
+        let atom = |rel, var| Box::new(Formula::Rel(rel, vec![Term::Var(var)]));
+        // forall x. forall y. p(x) | q(y)
+        assert_eq!(
+            miniscope(Formula::Forall(
+                x,
+                Box::new(Formula::Forall(
+                    y,
+                    Box::new(Formula::Or(atom(p, x), atom(q, y)))
+                ))
+            )),
+            Formula::Or(
+                Box::new(Formula::Forall(x, atom(p, x))),
+                Box::new(Formula::Forall(y, atom(q, y)))
+            )
+        );
+        // forall x. exists y. p(x) & q(y), whose Skolem function is a constant
+        let formula = Formula::Forall(
+            x,
+            Box::new(Formula::Exists(
+                y,
+                Box::new(Formula::And(atom(p, x), atom(q, y))),
+            )),
+        );
+        let mut fun_alloc = NameAllocator::<Fun>::default();
+        let skolemized = skolemize(formula, &mut fun_alloc);
+        let sk = func_sig(&skolemized).into_iter().collect::<Vec<_>>();
+        assert_eq!(sk.len(), 1);
+        assert_eq!(sk[0].1, 0);
+    }
+}
@@ -1,7 +1,15 @@
-use std::collections::{BTreeMap, HashSet};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet, HashSet},
+    iter, mem,
+    sync::Arc,
+    time::Instant,
+};
+
+use itertools::Itertools;
 
 use crate::{
-    fol::{Fun, Term},
+    fol::{Fun, NameAllocator, Sort, Term},
     interleave::Interleave,
     lazy_sequence::LazySequence,
     tuple_iterator::TupleIterator,
@@ -13,9 +21,13 @@ pub(crate) struct HerbrandUniverse<I: Iterator<Item = Term>> {
     next_idx: usize,
 }
 
+/// The Herbrand universe of the functions of `func_sig`, all of the sort of `constant`, enumerated
+/// by interleaving the applications of each function. `constant` is added to the functions if
+/// none of them is a constant.
 pub(crate) fn herbrand_universe(
     mut func_sig: HashSet<(Fun, usize)>,
-) -> HerbrandUniverse<Box<dyn Iterator<Item = Term>>> {
+    constant: Fun,
+) -> HerbrandUniverse<Box<dyn Iterator<Item = Term> + Send>> {
     let mut exists_func_with_arity_0 = false;
     for (_, arity) in &func_sig {
         if *arity == 0 {
@@ -23,7 +35,7 @@ pub(crate) fn herbrand_universe(
         }
     }
     if !exists_func_with_arity_0 {
-        func_sig.insert((Fun::default(), 0));
+        func_sig.insert((constant, 0));
     }
     let mut res = BTreeMap::<_, HashSet<_>>::new();
     for (fun, arity) in func_sig.into_iter() {
@@ -61,14 +73,15 @@ impl<T: Iterator<Item = Term>> Iterator for HerbrandUniverse<T> {
     type Item = Term;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.next_idx < self.elems.vec.borrow().len() {
-            let elem = self.elems.vec.borrow()[self.next_idx].clone();
+        // Not locked while the next term is built from the previous ones
+        let elem = self.elems.vec.lock().unwrap().get(self.next_idx).cloned();
+        if let Some(elem) = elem {
             self.next_idx += 1;
             Some(elem)
         } else {
             match self.higher_arity_terms_iter.next() {
                 Some(elem) => {
-                    self.elems.vec.borrow_mut().push(elem.clone());
+                    self.elems.vec.lock().unwrap().push(elem.clone());
                     self.next_idx += 1;
                     Some(elem)
                 }
@@ -77,3 +90,464 @@ impl<T: Iterator<Item = Term>> Iterator for HerbrandUniverse<T> {
         }
     }
 }
+
+/// The order in which the Herbrand engine instantiates variables with terms.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum TermOrder {
+    /// Interleave the applications of each function to the terms enumerated so far. Over several
+    /// sorts, instances are generated by depth instead.
+    #[default]
+    Interleaved,
+    /// Generate every instance whose terms have depth at most k before any deeper one
+    Depth,
+    /// Generate every instance whose terms have size at most k before any larger one
+    Size,
+}
+
+impl TermOrder {
+    /// The level of `term`: its depth, or its size when ordering by size.
+    pub(crate) fn level(self, term: &Term) -> usize {
+        match self {
+            TermOrder::Interleaved | TermOrder::Depth => term.depth(),
+            TermOrder::Size => term.size(),
+        }
+    }
+
+    /// The level of constants, the lowest one.
+    pub(crate) fn first_level(self) -> usize {
+        match self {
+            TermOrder::Interleaved | TermOrder::Depth => 0,
+            TermOrder::Size => 1,
+        }
+    }
+}
+
+/// The Herbrand universe of a many-sorted signature as levels of the terms of equal depth or size,
+/// by sort, built on demand until `deadline`.
+pub(crate) struct LeveledUniverse {
+    order: TermOrder,
+    /// The functions by the sort of their values, with the sorts of their arguments
+    funs: BTreeMap<Sort, Vec<(Fun, Vec<Sort>)>>,
+    /// The terms of each level by sort, for every sort of `funs`
+    levels: Vec<Arc<BTreeMap<Sort, Vec<Term>>>>, // shared with the instances
+    deadline: Option<Instant>,
+}
+
+/// The terms of `sort` in `level`.
+fn terms_of_sort(level: &BTreeMap<Sort, Vec<Term>>, sort: Sort) -> &[Term] {
+    level.get(&sort).map_or(&[], Vec::as_slice)
+}
+
+impl LeveledUniverse {
+    /// The universe of the functions of `func_sig`, given with the sorts of their arguments. The
+    /// sorts of `sorts` and of the arguments that have no constant are given one from
+    /// `fun_alloc`.
+    pub(crate) fn new(
+        func_sig: HashSet<(Fun, Vec<Sort>)>,
+        sorts: impl IntoIterator<Item = Sort>,
+        fun_alloc: &mut NameAllocator<Fun>,
+        order: TermOrder,
+        deadline: Option<Instant>,
+    ) -> Self {
+        let mut funs = BTreeMap::<_, Vec<_>>::new();
+        let mut sorts = sorts.into_iter().collect::<BTreeSet<_>>();
+        for (fun, args) in func_sig {
+            sorts.extend(args.iter().copied());
+            funs.entry(fun.sort()).or_default().push((fun, args));
+        }
+        for sort in sorts {
+            let funs = funs.entry(sort).or_default();
+            if funs.iter().all(|(_, args)| !args.is_empty()) {
+                funs.push((fun_alloc.alloc().with_sort(sort), vec![]));
+            }
+        }
+        for funs in funs.values_mut() {
+            funs.sort_by_key(|(_, args)| args.len());
+        }
+        LeveledUniverse {
+            order,
+            funs,
+            levels: Vec::new(),
+            deadline,
+        }
+    }
+
+    /// The highest level with terms of `sort`, or `None` if there are terms of `sort` in every
+    /// level.
+    pub(crate) fn max_level(&self, sort: Sort) -> Option<usize> {
+        self.max_level_within(sort, &mut Vec::new())
+    }
+
+    /// The highest level with terms of `sort`, within terms of the sorts `outer`.
+    fn max_level_within(&self, sort: Sort, outer: &mut Vec<Sort>) -> Option<usize> {
+        // Terms with subterms of their own sort are arbitrarily deep
+        if outer.contains(&sort) {
+            return None;
+        }
+        outer.push(sort);
+        let mut max = self.order.first_level();
+        for (_, args) in self.funs.get(&sort).into_iter().flatten() {
+            if args.is_empty() {
+                continue;
+            }
+            let mut levels = Vec::new();
+            for arg in args {
+                match self.max_level_within(*arg, outer) {
+                    Some(level) => levels.push(level),
+                    None => {
+                        outer.pop();
+                        return None;
+                    }
+                }
+            }
+            let level = match self.order {
+                TermOrder::Interleaved | TermOrder::Depth => levels.into_iter().max().unwrap(),
+                TermOrder::Size => levels.into_iter().sum(),
+            };
+            max = max.max(level + 1);
+        }
+        outer.pop();
+        Some(max)
+    }
+
+    /// The number of terms of `sort` in `level`, or `None` if it does not fit in `usize`. The
+    /// terms are not built.
+    pub(crate) fn count(&self, level: usize, sort: Sort) -> Option<usize> {
+        // The number of tuples of terms of `args`, given the number of terms of each sort
+        let count_tuples = |args: &[Sort], counts: &BTreeMap<Sort, usize>| {
+            args.iter().try_fold(1usize, |tuples, arg| {
+                tuples.checked_mul(counts.get(arg).copied().unwrap_or(0))
+            })
+        };
+        match self.order {
+            TermOrder::Interleaved | TermOrder::Depth => {
+                // The numbers of terms of depth at most d, from the numbers of arguments
+                let mut up_to = BTreeMap::new();
+                let mut below = BTreeMap::new();
+                for _ in 0..=level {
+                    let mut next = BTreeMap::new();
+                    for (sort, funs) in &self.funs {
+                        let mut count = 0usize;
+                        for (_, args) in funs {
+                            count = count.checked_add(count_tuples(args, &up_to)?)?;
+                        }
+                        next.insert(*sort, count);
+                    }
+                    below = mem::replace(&mut up_to, next);
+                }
+                let count = |counts: &BTreeMap<Sort, usize>| counts.get(&sort).copied();
+                Some(count(&up_to).unwrap_or(0) - count(&below).unwrap_or(0))
+            }
+            TermOrder::Size => {
+                // counts[n] the numbers of terms of size n
+                let mut counts = vec![BTreeMap::<Sort, usize>::new()];
+                for size in 1..=level {
+                    let mut next = BTreeMap::new();
+                    for (sort, funs) in &self.funs {
+                        let mut count = 0usize;
+                        for (_, args) in funs {
+                            // tuples[m] tuples of the first arguments of total size m
+                            let mut tuples = vec![0usize; size];
+                            tuples[0] = 1;
+                            for arg in args {
+                                let mut longer = vec![0usize; size];
+                                for (total, longer) in longer.iter_mut().enumerate() {
+                                    for last in 1..=total {
+                                        let terms = counts[last].get(arg).copied().unwrap_or(0);
+                                        *longer = longer.checked_add(
+                                            terms.checked_mul(tuples[total - last])?,
+                                        )?;
+                                    }
+                                }
+                                tuples = longer;
+                            }
+                            count = count.checked_add(tuples[size - 1])?;
+                        }
+                        next.insert(*sort, count);
+                    }
+                    counts.push(next);
+                }
+                Some(counts[level].get(&sort).copied().unwrap_or(0))
+            }
+        }
+    }
+
+    /// The terms of `level` by sort, or `None` if the deadline passed before they were all built.
+    pub(crate) fn level(&mut self, level: usize) -> Option<&BTreeMap<Sort, Vec<Term>>> {
+        let first = self.order.first_level();
+        while self.levels.len() <= level {
+            let level = self.levels.len();
+            let mut terms = BTreeMap::new();
+            for (sort, funs) in &self.funs {
+                let mut sort_terms = Vec::new();
+                for (fun, args) in funs {
+                    let tuples: Box<dyn Iterator<Item = Vec<Term>>> = if args.is_empty() {
+                        Box::new((level == first).then(Vec::new).into_iter())
+                    } else if level <= first {
+                        Box::new(iter::empty())
+                    } else {
+                        match self.order {
+                            TermOrder::Interleaved | TermOrder::Depth => {
+                                Box::new(tuples_of_level(&self.levels, args, level - 1))
+                            }
+                            TermOrder::Size => Box::new(
+                                tuples_of_size(&self.levels, args, level - 1, self.deadline)?
+                                    .into_iter(),
+                            ),
+                        }
+                    };
+                    for args in tuples {
+                        if self
+                            .deadline
+                            .is_some_and(|deadline| Instant::now() >= deadline)
+                        {
+                            return None;
+                        }
+                        sort_terms.push(Term::Fun(*fun, args));
+                    }
+                }
+                debug_assert_eq!(Some(sort_terms.len()), self.count(level, *sort));
+                terms.insert(*sort, sort_terms);
+            }
+            self.levels.push(Arc::new(terms));
+        }
+        Some(&*self.levels[level])
+    }
+}
+
+/// The tuples of terms of `levels` up to `top` of the sorts `sorts` with a term of level `top`.
+fn tuples_of_level(
+    levels: &[Arc<BTreeMap<Sort, Vec<Term>>>],
+    sorts: &[Sort],
+    top: usize,
+) -> impl Iterator<Item = Vec<Term>> {
+    let lower = sorts
+        .iter()
+        .map(|sort| {
+            levels[..top]
+                .iter()
+                .flat_map(|level| terms_of_sort(level, *sort))
+                .cloned()
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let top = sorts
+        .iter()
+        .map(|sort| terms_of_sort(&levels[top], *sort).to_vec())
+        .collect::<Vec<_>>();
+    let all = lower
+        .iter()
+        .zip(&top)
+        .map(|(lower, top)| lower.iter().chain(top).cloned().collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let arity = sorts.len();
+    // By the position of the first term of level top
+    (0..arity).flat_map(move |first_top| {
+        (0..arity)
+            .map(|idx| match idx.cmp(&first_top) {
+                Ordering::Less => lower[idx].clone(),
+                Ordering::Equal => top[idx].clone(),
+                Ordering::Greater => all[idx].clone(),
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .multi_cartesian_product()
+    })
+}
+
+/// The tuples of terms of `levels` of the sorts `sorts` and of sizes adding up to `size`, or
+/// `None` if `deadline` passed before they were all built.
+fn tuples_of_size(
+    levels: &[Arc<BTreeMap<Sort, Vec<Term>>>],
+    sorts: &[Sort],
+    size: usize,
+    deadline: Option<Instant>,
+) -> Option<Vec<Vec<Term>>> {
+    let (sort, rest_sorts) = match sorts.split_first() {
+        Some(split) => split,
+        None => return Some(if size == 0 { vec![vec![]] } else { vec![] }),
+    };
+    let mut res = Vec::new();
+    for first in 1..=size.saturating_sub(rest_sorts.len()) {
+        let rest = tuples_of_size(levels, rest_sorts, size - first, deadline)?;
+        for term in terms_of_sort(&levels[first], *sort) {
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return None;
+            }
+            for rest in &rest {
+                res.push(
+                    Some(term.clone())
+                        .into_iter()
+                        .chain(rest.iter().cloned())
+                        .collect(),
+                );
+            }
+        }
+    }
+    Some(res)
+}
+
+/// The instances of clauses whose variables have the sorts `clause_sorts`, in the order of the
+/// largest level of their terms, so that an instance comes only once all those of lower levels
+/// did. The instances end after the highest level of the sorts of the variables, if they all have
+/// one, or early if the deadline of `universe` passes.
+pub(crate) fn leveled_instances(
+    mut universe: LeveledUniverse,
+    clause_sorts: Vec<Vec<Sort>>,
+) -> impl Iterator<Item = (usize, Vec<Term>)> {
+    let first = universe.order.first_level();
+    let last = clause_sorts.iter().flatten().try_fold(first, |last, sort| {
+        Some(last.max(universe.max_level(*sort)?))
+    });
+    (first..)
+        .take_while(move |level| last.is_none_or(|last| *level <= last))
+        .map_while(move |level| {
+            universe.level(level)?;
+            Some((level, universe.levels.clone()))
+        })
+        .flat_map(move |(level, levels)| {
+            let clause_sorts = clause_sorts.clone();
+            clause_sorts
+                .into_iter()
+                .enumerate()
+                .flat_map(move |(clause_idx, sorts)| {
+                    let tuples: Box<dyn Iterator<Item = Vec<Term>> + Send> = if sorts.is_empty() {
+                        Box::new((level == first).then(Vec::new).into_iter())
+                    } else {
+                        Box::new(tuples_of_level(&levels, &sorts, level))
+                    };
+                    tuples.map(move |terms| (clause_idx, terms))
+                })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::time::Instant;
+
+    use crate::fol::{Fun, NameAllocator, Sort, Term};
+
+    use super::{leveled_instances, tuples_of_size, LeveledUniverse, TermOrder};
+
+    #[test]
+    fn counts_levels_exactly() {
+        let mut fun_alloc = NameAllocator::default();
+        let (c, f, g) = (fun_alloc.alloc(), fun_alloc.alloc(), fun_alloc.alloc());
+        let s = Sort::default();
+        let func_sig = vec![(c, vec![]), (f, vec![s]), (g, vec![s, s])]
+            .into_iter()
+            .collect::<HashSet<_>>();
+        for (order, counts) in [
+            (TermOrder::Depth, vec![1, 2, 10, 170]),
+            (TermOrder::Size, vec![0, 1, 1, 2, 4, 9, 21]),
+        ] {
+            let mut universe =
+                LeveledUniverse::new(func_sig.clone(), [s], &mut fun_alloc, order, None);
+            assert_eq!(universe.max_level(s), None);
+            for (level, count) in counts.into_iter().enumerate() {
+                assert_eq!(universe.count(level, s), Some(count));
+                let terms = universe.level(level).unwrap()[&s].clone();
+                assert_eq!(terms.len(), count);
+                assert!(terms.iter().all(|term| order.level(term) == level));
+                assert_eq!(terms.iter().collect::<HashSet<_>>().len(), count);
+            }
+        }
+        // The number of terms of depth 7 is about 2^120
+        let universe = LeveledUniverse::new(func_sig, [s], &mut fun_alloc, TermOrder::Depth, None);
+        assert_eq!(universe.count(7, s), None);
+    }
+
+    #[test]
+    fn stops_building_tuples_at_the_deadline() {
+        let mut fun_alloc = NameAllocator::default();
+        let (c, f) = (fun_alloc.alloc(), fun_alloc.alloc());
+        let s = Sort::default();
+        let func_sig = vec![(c, vec![]), (f, vec![s])].into_iter().collect();
+        let mut universe =
+            LeveledUniverse::new(func_sig, [s], &mut fun_alloc, TermOrder::Size, None);
+        universe.level(3).unwrap();
+        // c and f(f(c)), f(c) and f(c), and f(f(c)) and c
+        let tuples = tuples_of_size(&universe.levels, &[s, s], 4, None);
+        assert_eq!(tuples.map(|tuples| tuples.len()), Some(3));
+        assert_eq!(
+            tuples_of_size(&universe.levels, &[s, s], 4, Some(Instant::now())),
+            None
+        );
+    }
+
+    #[test]
+    fn generates_instances_by_levels() {
+        let mut fun_alloc = NameAllocator::default();
+        let (c, d, f) = (fun_alloc.alloc(), fun_alloc.alloc(), fun_alloc.alloc());
+        let s = Sort::default();
+        let func_sig = vec![(c, vec![]), (d, vec![]), (f, vec![s])]
+            .into_iter()
+            .collect::<HashSet<_>>();
+        let universe = LeveledUniverse::new(func_sig, [s], &mut fun_alloc, TermOrder::Depth, None);
+        let instances = leveled_instances(universe, vec![vec![], vec![s; 2]])
+            .take(17)
+            .collect::<Vec<_>>();
+        // The ground clause, then the 4 pairs of constants and the 12 pairs of terms of depth at
+        // most 1 with one of depth 1
+        assert_eq!(instances[0], (0, vec![]));
+        let depths = instances[1..]
+            .iter()
+            .map(|(_, terms)| terms.iter().map(|term| term.depth()).max().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(depths, [vec![0; 4], vec![1; 12]].concat());
+        assert_eq!(instances.iter().collect::<HashSet<_>>().len(), 17);
+        // Without functions, the instances are finitely many
+        let func_sig = vec![(c, vec![]), (d, vec![])].into_iter().collect();
+        let universe = LeveledUniverse::new(func_sig, [s], &mut fun_alloc, TermOrder::Size, None);
+        assert_eq!(
+            leveled_instances(universe, vec![vec![], vec![s; 2]]).count(),
+            5
+        );
+    }
+
+    #[test]
+    fn instantiates_variables_with_terms_of_their_sort() {
+        let mut sort_alloc = NameAllocator::<Sort>::default();
+        let (s, t, u) = (sort_alloc.alloc(), sort_alloc.alloc(), sort_alloc.alloc());
+        let mut fun_alloc = NameAllocator::<Fun>::default();
+        let c = fun_alloc.alloc().with_sort(s);
+        let (d, e) = (
+            fun_alloc.alloc().with_sort(t),
+            fun_alloc.alloc().with_sort(t),
+        );
+        let f = fun_alloc.alloc().with_sort(s);
+        let g = fun_alloc.alloc().with_sort(u);
+        // c, f(d) and f(e) of sort s, d and e of sort t, and g(g(...)) of sort u without a
+        // constant
+        let func_sig = vec![
+            (c, vec![]),
+            (d, vec![]),
+            (e, vec![]),
+            (f, vec![t]),
+            (g, vec![u]),
+        ]
+        .into_iter()
+        .collect::<HashSet<_>>();
+        for order in [TermOrder::Depth, TermOrder::Size] {
+            let universe =
+                LeveledUniverse::new(func_sig.clone(), [s, t, u], &mut fun_alloc, order, None);
+            assert_eq!(universe.max_level(s), Some(order.first_level() + 1));
+            assert_eq!(universe.max_level(t), Some(order.first_level()));
+            assert_eq!(universe.max_level(u), None);
+            let instances = leveled_instances(universe, vec![vec![s, t]]).collect::<Vec<_>>();
+            assert_eq!(instances.len(), 6);
+            assert!(instances
+                .iter()
+                .all(|(_, terms)| terms[0].sort() == s && terms[1].sort() == t));
+        }
+        let mut universe =
+            LeveledUniverse::new(func_sig, [u], &mut fun_alloc, TermOrder::Depth, None);
+        assert_eq!(universe.level(1).unwrap()[&u].len(), 1);
+        // The constant given to u is fresh
+        assert!(matches!(
+            &universe.level(0).unwrap()[&u][..],
+            [Term::Fun(fun, args)] if args.is_empty() && ![c, d, e, f, g].contains(fun)
+        ));
+    }
+}
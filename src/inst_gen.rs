@@ -0,0 +1,371 @@
+use std::collections::{HashMap, HashSet};
+use std::mem;
+
+use crate::{
+    cnf::{clausify, Clause, Literal},
+    fol::{self, func_sig, skolemize, Fun, NameAllocator, Rel, Sort, Term, Var},
+    pl,
+    pl_sat_solver::Solver,
+    prover::{
+        drop_in_background, remove_universal_prefix, solve_modulo_equality, Proof, ProverConfig,
+        RelToVar, Statistics, UnknownReason, Verdict,
+    },
+    resolution::equality_axioms,
+    substitution::{mgu, Substitution},
+};
+
+/// An instance of a clause: the terms of the variables of the clause, which may have variables of
+/// their own, distinct from those of the other instances.
+struct Instance {
+    clause_idx: usize,
+    terms: Vec<Term>,
+    literals: Vec<Literal>,
+}
+
+fn rename_term(
+    term: &Term,
+    renaming: &mut HashMap<Var, Var>,
+    var_alloc: &mut NameAllocator<Var>,
+) -> Term {
+    match term {
+        Term::Var(var) => Term::Var(
+            *renaming
+                .entry(*var)
+                .or_insert_with(|| var_alloc.alloc().with_sort(var.sort())),
+        ),
+        Term::Fun(fun, args) => Term::Fun(
+            *fun,
+            args.iter()
+                .map(|arg| rename_term(arg, renaming, var_alloc))
+                .collect(),
+        ),
+    }
+}
+
+/// The terms with their variables renamed by `var_alloc` in the order they occur.
+fn rename(terms: &[Term], var_alloc: &mut NameAllocator<Var>) -> Vec<Term> {
+    let mut renaming = HashMap::new();
+    terms
+        .iter()
+        .map(|term| rename_term(term, &mut renaming, var_alloc))
+        .collect()
+}
+
+/// The term with every variable replaced by the constant of its sort in `constants`.
+fn ground(term: &Term, constants: &HashMap<Sort, Fun>) -> Term {
+    match term {
+        Term::Var(var) => Term::Fun(constants[&var.sort()], vec![]),
+        Term::Fun(fun, args) => Term::Fun(
+            *fun,
+            args.iter().map(|arg| ground(arg, constants)).collect(),
+        ),
+    }
+}
+
+/// Decides `formula`, in which `equality` (if any) is interpreted as equality, within the limits
+/// of `config`, recording its work in `stats`. Instances of the clauses of the skolemized
+/// negation are generated as in Inst-Gen: the instances grounded by mapping every variable to a
+/// single constant of its sort are solved, and as long as their model selects unifiable complementary
+/// literals, the instances of the unifier are added. Equality is axiomatized.
+pub(crate) fn is_tautology(
+    formula: fol::Formula,
+    fun_alloc: &mut NameAllocator<Fun>,
+    rel_alloc: &mut NameAllocator<Rel>,
+    equality: Option<Rel>,
+    config: &ProverConfig,
+    stats: &mut Statistics,
+) -> Verdict {
+    let input = (formula.clone(), fun_alloc.clone(), rel_alloc.clone());
+    let formula = skolemize(fol::Formula::Not(Box::new(formula)), fun_alloc);
+    let (_, matrix) = remove_universal_prefix(formula);
+    let mut clauses = clausify(&matrix, rel_alloc);
+    if let Some(equality) = equality {
+        let literals = clauses
+            .iter()
+            .map(|clause| clause.literals.clone())
+            .collect::<Vec<_>>();
+        clauses.extend(
+            equality_axioms(equality, &literals)
+                .into_iter()
+                .map(Clause::new),
+        );
+    }
+    let mut constants = func_sig(&matrix)
+        .into_iter()
+        .filter(|(_, arity)| *arity == 0)
+        .map(|(fun, _)| (fun.sort(), fun))
+        .collect::<HashMap<_, _>>();
+    for var in clauses.iter().flat_map(|clause| &clause.vars) {
+        constants
+            .entry(var.sort())
+            .or_insert_with(|| fun_alloc.alloc().with_sort(var.sort()));
+    }
+
+    let mut rel_to_var = RelToVar::default();
+    let mut solver = Solver::new();
+    solver.set_limits(config.max_conflicts, config.deadline);
+    let mut var_alloc = NameAllocator::default();
+    let mut instances = Vec::new();
+    // The instances up to renaming, with their variables renamed from 0
+    let mut seen = HashSet::new();
+    // The grounded instances, as in a proof
+    let mut ground_instances = Vec::new();
+    let mut lemmas = Vec::new();
+    // Why instances were left out for having too deep or large terms, so that saturation proves
+    // nothing
+    let mut left_out = None;
+    let mut new_instances = clauses
+        .iter()
+        .enumerate()
+        .map(|(clause_idx, clause)| {
+            let terms = clause.vars.iter().map(|var| Term::Var(*var)).collect();
+            (clause_idx, terms)
+        })
+        .collect::<Vec<(usize, Vec<Term>)>>();
+    // The pairs of selected literals already unified, as the indices of their instances and of
+    // the literals in them
+    let mut unified = HashSet::new();
+    macro_rules! unknown {
+        ($reason:expr) => {{
+            drop_in_background((
+                solver,
+                rel_to_var,
+                instances,
+                seen,
+                ground_instances,
+                unified,
+                new_instances,
+            ));
+            Verdict::Unknown($reason)
+        }};
+    }
+    loop {
+        let instance_count = instances.len();
+        for (clause_idx, terms) in mem::take(&mut new_instances) {
+            if config.deadline_passed() {
+                return unknown!(UnknownReason::Timeout);
+            }
+            if !seen.insert((clause_idx, rename(&terms, &mut NameAllocator::default()))) {
+                continue;
+            }
+            if config
+                .max_term_depth
+                .is_some_and(|max| terms.iter().any(|term| term.depth() > max))
+            {
+                left_out = Some(UnknownReason::TermDepthExceeded);
+                continue;
+            }
+            if config
+                .max_term_size
+                .is_some_and(|max| terms.iter().any(|term| term.size() > max))
+            {
+                left_out = Some(UnknownReason::TermSizeExceeded);
+                continue;
+            }
+            if config
+                .max_instances
+                .is_some_and(|max| instances.len() >= max)
+            {
+                // The instances generated so far may still be contradictory
+                return match solve_modulo_equality(
+                    &mut solver,
+                    &mut rel_to_var,
+                    None,
+                    &mut lemmas,
+                    stats,
+                    config,
+                ) {
+                    Ok(false) => Verdict::Valid(Some(Proof {
+                        formula: input.0,
+                        fun_alloc: input.1,
+                        rel_alloc: input.2,
+                        clauses,
+                        instances: ground_instances,
+                        equality,
+                        lemmas,
+                    })),
+                    Ok(true) => unknown!(UnknownReason::InstanceBudgetExhausted),
+                    Err(reason) => unknown!(reason),
+                };
+            }
+            let clause = &clauses[clause_idx];
+            let terms = rename(&terms, &mut var_alloc);
+            let ground_terms = terms
+                .iter()
+                .map(|term| ground(term, &constants))
+                .collect::<Vec<_>>();
+            let interp = clause
+                .vars
+                .iter()
+                .copied()
+                .zip(ground_terms.iter().cloned())
+                .collect();
+            let pl_clause = rel_to_var.clause(clause, &interp);
+            if !pl::clause_is_tautology(&pl_clause) {
+                solver.add_clause(&pl_clause);
+            }
+            let interp = clause
+                .vars
+                .iter()
+                .copied()
+                .zip(terms.iter().cloned())
+                .collect::<Substitution>();
+            instances.push(Instance {
+                clause_idx,
+                terms,
+                literals: clause
+                    .literals
+                    .iter()
+                    .map(|literal| Literal {
+                        args: literal.args.iter().map(|arg| interp.apply(arg)).collect(),
+                        ..literal.clone()
+                    })
+                    .collect(),
+            });
+            ground_instances.push((clause_idx, ground_terms));
+            stats.instances += 1;
+        }
+        if instances.len() == instance_count {
+            // The model of the grounded instances extends to a model of the clauses
+            return left_out.map_or(Verdict::Invalid(None), Verdict::Unknown);
+        }
+        match solve_modulo_equality(
+            &mut solver,
+            &mut rel_to_var,
+            None,
+            &mut lemmas,
+            stats,
+            config,
+        ) {
+            Ok(false) => {
+                return Verdict::Valid(Some(Proof {
+                    formula: input.0,
+                    fun_alloc: input.1,
+                    rel_alloc: input.2,
+                    clauses,
+                    instances: ground_instances,
+                    equality,
+                    lemmas,
+                }))
+            }
+            Ok(true) => {}
+            Err(reason) => return unknown!(reason),
+        }
+        // Select in each instance a literal true in the model of the grounded instances
+        let model = solver.model();
+        let mut selected = HashMap::<(Rel, bool), Vec<(usize, usize)>>::new();
+        for (instance_idx, instance) in instances.iter().enumerate() {
+            if config.deadline_passed() {
+                return unknown!(UnknownReason::Timeout);
+            }
+            let literal = instance.literals.iter().position(|literal| {
+                let args = literal
+                    .args
+                    .iter()
+                    .map(|arg| ground(arg, &constants))
+                    .collect();
+                let var = rel_to_var.translate(literal.rel, args);
+                model.get(&var).copied().unwrap_or(false) == literal.positive
+            });
+            if let Some(literal_idx) = literal {
+                let literal = &instance.literals[literal_idx];
+                selected
+                    .entry((literal.rel, literal.positive))
+                    .or_default()
+                    .push((instance_idx, literal_idx));
+            }
+        }
+        for ((rel, positive), literals) in &selected {
+            if !positive {
+                continue;
+            }
+            for a in literals {
+                for b in selected.get(&(*rel, false)).into_iter().flatten() {
+                    if config.deadline_passed() {
+                        return unknown!(UnknownReason::Timeout);
+                    }
+                    if !unified.insert((*a, *b)) {
+                        continue;
+                    }
+                    let unifier = mgu(
+                        &Term::Fun(Fun::default(), instances[a.0].literals[a.1].args.clone()),
+                        &Term::Fun(Fun::default(), instances[b.0].literals[b.1].args.clone()),
+                    );
+                    if let Some(unifier) = unifier {
+                        for instance in [&instances[a.0], &instances[b.0]] {
+                            let terms = instance
+                                .terms
+                                .iter()
+                                .map(|term| unifier.apply(term))
+                                .collect();
+                            new_instances.push((instance.clause_idx, terms));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fol::builders::{forall, implies};
+    use crate::fol::{Formula, NameAllocator, Term};
+    use crate::prover::{ProverConfig, Statistics, Verdict};
+
+    use super::is_tautology;
+
+    #[test]
+    fn instantiates_as_unification_requires() {
+        let mut var_alloc = NameAllocator::default();
+        let x = var_alloc.alloc();
+        let mut fun_alloc = NameAllocator::default();
+        let (a, f) = (fun_alloc.alloc(), fun_alloc.alloc());
+        let mut rel_alloc = NameAllocator::default();
+        let (p, q) = (rel_alloc.alloc(), rel_alloc.alloc());
+        let a = Term::Fun(a, vec![]);
+        let fffa = (0..3).fold(a.clone(), |term, _| Term::Fun(f, vec![term]));
+        // p(a) and p(x) -> p(f(x)) entail p(f(f(f(a))))
+        let chain = |conclusion| {
+            implies(
+                Formula::And(
+                    Box::new(Formula::Rel(p, vec![a.clone()])),
+                    Box::new(forall(
+                        x,
+                        implies(
+                            Formula::Rel(p, vec![Term::Var(x)]),
+                            Formula::Rel(p, vec![Term::Fun(f, vec![Term::Var(x)])]),
+                        ),
+                    )),
+                ),
+                conclusion,
+            )
+        };
+        let mut stats = Statistics::default();
+        let verdict = is_tautology(
+            chain(Formula::Rel(p, vec![fffa.clone()])),
+            &mut fun_alloc,
+            &mut rel_alloc,
+            None,
+            &ProverConfig::default(),
+            &mut stats,
+        );
+        let proof = match verdict {
+            Verdict::Valid(Some(proof)) => proof,
+            verdict => panic!("unexpected {:?}", verdict),
+        };
+        assert!(proof.certificate().is_ok());
+        // The clauses, grounded by a, and their instances of x = f(a) and f(f(a))
+        assert_eq!(stats.instances, 5);
+        // Nothing follows about q, and p(x) -> p(f(x)) has infinitely many instances otherwise
+        let verdict = is_tautology(
+            chain(Formula::Rel(q, vec![fffa])),
+            &mut fun_alloc,
+            &mut rel_alloc,
+            None,
+            &ProverConfig::default(),
+            &mut Statistics::default(),
+        );
+        assert_eq!(verdict, Verdict::Invalid(None));
+    }
+}
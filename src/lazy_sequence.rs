@@ -1,18 +1,18 @@
-use std::{cell::RefCell, rc::Rc};
+use std::sync::{Arc, Mutex};
 
 pub(crate) struct LazySequence<T> {
-    pub(crate) vec: Rc<RefCell<Vec<T>>>,
+    pub(crate) vec: Arc<Mutex<Vec<T>>>,
 }
 
 pub(crate) struct LazySequenceIterator<T> {
-    seq: Rc<RefCell<Vec<T>>>,
+    seq: Arc<Mutex<Vec<T>>>,
     next_idx: usize,
 }
 
 impl<T> LazySequence<T> {
     pub(crate) fn new(vec: Vec<T>) -> Self {
         Self {
-            vec: Rc::new(RefCell::new(vec)),
+            vec: Arc::new(Mutex::new(vec)),
         }
     }
 
@@ -24,11 +24,11 @@ impl<T> LazySequence<T> {
     }
 }
 
-impl<'a, T: Clone> Iterator for LazySequenceIterator<T> {
+impl<T: Clone> Iterator for LazySequenceIterator<T> {
     type Item = T;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let vec = self.seq.borrow_mut();
+        let vec = self.seq.lock().unwrap();
         if self.next_idx < vec.len() {
             let res = vec[self.next_idx].clone();
             self.next_idx += 1;
@@ -0,0 +1,358 @@
+//! A prover for first-order logic with equality.
+//!
+//! Formulas are `fol::Formula`s, whose symbols are allocated by a `Translator`, either directly
+//! or by translating formulas of the `parser` crate. `is_tautology` and `is_satisfiable` decide
+//! them with the engine of the given `Options`:
+//!
+//! ```
+//! use parser::formula::parse_formula;
+//! use prover::{is_tautology, Options, Translator, Verdict};
+//!
+//! let formula = r#"Implies (Forall "x" (Rel "p" [Var "x"])) (Rel "p" [Fun "c" []])"#;
+//! let formula = parse_formula(formula).unwrap();
+//! let mut translator = Translator::new();
+//! assert!(translator.check_sorts(&formula).is_ok());
+//! let formula = translator.translate(*formula);
+//! assert!(matches!(
+//!     is_tautology(formula, &mut translator, &Options::default()),
+//!     Verdict::Valid(Some(_))
+//! ));
+//! ```
+//!
+//! Variables and functions are annotated with sorts, and variables are instantiated with terms
+//! of their sort only. The sorts of a parsed formula are declared to the `Translator`, which
+//! checks them.
+
+mod cnf;
+mod congruence_closure;
+pub mod fol;
+mod herbrand_universe;
+mod inst_gen;
+mod interleave;
+mod lazy_sequence;
+mod model_finder;
+mod pl;
+mod pl_sat_solver;
+mod proof_checker;
+mod prover;
+mod resolution;
+pub mod substitution;
+mod translator;
+mod tuple_iterator;
+
+pub use crate::cnf::{Clause, Literal};
+pub use crate::congruence_closure::GroundLiteral;
+pub use crate::herbrand_universe::TermOrder;
+pub use crate::model_finder::{Elem, Model};
+pub use crate::prover::{Certificate, Proof, ProverConfig, Statistics, UnknownReason, Verdict};
+pub use crate::translator::{Namer, Translator, EQUALITY};
+
+#[derive(PartialEq, Eq, Clone, Copy, Default, Debug)]
+pub enum Engine {
+    #[default]
+    /// Ground Herbrand instances with a SAT solver and search for finite countermodels
+    Herbrand,
+    /// Saturate the clausal form under binary resolution and factoring
+    Resolution,
+    /// Generate the instances of the clausal form required by the models of its instances
+    /// grounded by a single constant (Inst-Gen)
+    InstGen,
+}
+
+/// How formulas are decided.
+#[derive(PartialEq, Eq, Clone, Copy, Default, Debug)]
+pub struct Options {
+    pub engine: Engine,
+    pub config: ProverConfig,
+}
+
+/// Decides whether `formula`, whose symbols are named by `translator`, is valid. The relation
+/// `EQUALITY` is interpreted as equality, and Skolem functions and the relations defining parts of
+/// the clausal form are allocated by `translator`.
+pub fn is_tautology(
+    formula: fol::Formula,
+    translator: &mut Translator,
+    options: &Options,
+) -> Verdict {
+    is_tautology_with_statistics(formula, translator, options).0
+}
+
+/// Like `is_tautology`, also returning what the engine did. The resolution engine records no
+/// statistics.
+pub fn is_tautology_with_statistics(
+    formula: fol::Formula,
+    translator: &mut Translator,
+    options: &Options,
+) -> (Verdict, Statistics) {
+    let equality = translator.equality();
+    let mut stats = Statistics::default();
+    let (fun_alloc, rel_alloc) = translator.fresh_symbols();
+    let verdict = match options.engine {
+        Engine::Herbrand => prover::is_tautology(
+            formula,
+            fun_alloc,
+            rel_alloc,
+            equality,
+            &options.config,
+            &mut stats,
+        ),
+        Engine::InstGen => inst_gen::is_tautology(
+            formula,
+            fun_alloc,
+            rel_alloc,
+            equality,
+            &options.config,
+            &mut stats,
+        ),
+        Engine::Resolution => {
+            match resolution::is_tautology(formula, fun_alloc, rel_alloc, equality, &options.config)
+            {
+                resolution::Outcome::Refuted => Verdict::Valid(None),
+                resolution::Outcome::Saturated => Verdict::Invalid(None),
+                resolution::Outcome::Unknown(reason) => Verdict::Unknown(reason),
+            }
+        }
+    };
+    (verdict, stats)
+}
+
+/// Whether a formula has a model, as decided by `is_satisfiable`.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Satisfiability {
+    /// A model of the formula, from the engines giving one
+    Satisfiable(Option<Model>),
+    /// A proof that the negation of the formula is valid, from the engines giving one
+    Unsatisfiable(Option<Proof>),
+    Unknown(UnknownReason),
+}
+
+/// Decides whether `formula` has a model, by deciding whether its negation is valid.
+pub fn is_satisfiable(
+    formula: fol::Formula,
+    translator: &mut Translator,
+    options: &Options,
+) -> Satisfiability {
+    match is_tautology(fol::Formula::Not(Box::new(formula)), translator, options) {
+        Verdict::Valid(proof) => Satisfiability::Unsatisfiable(proof),
+        Verdict::Invalid(model) => Satisfiability::Satisfiable(model),
+        Verdict::Unknown(reason) => Satisfiability::Unknown(reason),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{
+        is_satisfiable, is_tautology, is_tautology_with_statistics, Engine, Options, ProverConfig,
+        Satisfiability, TermOrder, UnknownReason, Verdict,
+    };
+    use crate::fol::{Formula, Term};
+    use crate::translator::{Translator, EQUALITY};
+
+    #[test]
+    fn gives_up_at_limits() {
+        let limited_with_statistics = |config| {
+            let mut translator = Translator::new();
+            let (x, f, c) = (
+                translator.var("x"),
+                translator.fun("f"),
+                translator.rel("c"),
+            );
+            let a = Term::Fun(translator.fun("a"), vec![]);
+            let ffa = Term::Fun(f, vec![Term::Fun(f, vec![a.clone()])]);
+            // c(a) and c(x) -> c(f(x)) entail c(f(f(a))), which needs the instance x = f(a)
+            let formula = Formula::Implies(
+                Box::new(Formula::And(
+                    Box::new(Formula::Rel(c, vec![a])),
+                    Box::new(Formula::Forall(
+                        x,
+                        Box::new(Formula::Implies(
+                            Box::new(Formula::Rel(c, vec![Term::Var(x)])),
+                            Box::new(Formula::Rel(c, vec![Term::Fun(f, vec![Term::Var(x)])])),
+                        )),
+                    )),
+                )),
+                Box::new(Formula::Rel(c, vec![ffa])),
+            );
+            let options = Options {
+                config,
+                ..Options::default()
+            };
+            let (verdict, stats) = is_tautology_with_statistics(formula, &mut translator, &options);
+            match verdict {
+                Verdict::Valid(_) => (Ok(()), stats),
+                Verdict::Unknown(reason) => (Err(reason), stats),
+                verdict => panic!("unexpected {:?}", verdict),
+            }
+        };
+        let limited = |config| limited_with_statistics(config).0;
+        assert_eq!(limited(ProverConfig::default()), Ok(()));
+        assert_eq!(
+            limited(ProverConfig {
+                max_term_depth: Some(1),
+                ..ProverConfig::default()
+            }),
+            Ok(())
+        );
+        assert_eq!(
+            limited(ProverConfig {
+                max_term_depth: Some(0),
+                ..ProverConfig::default()
+            }),
+            Err(UnknownReason::TermDepthExceeded)
+        );
+        // By levels, the limit is reached only once every shallower instance was generated
+        for (order, max_term_depth, max_term_size) in [
+            (TermOrder::Depth, Some(0), None),
+            (TermOrder::Size, None, Some(1)),
+        ] {
+            let (result, stats) = limited_with_statistics(ProverConfig {
+                max_term_depth,
+                max_term_size,
+                term_order: order,
+                ..ProverConfig::default()
+            });
+            assert!(result.is_err());
+            assert_eq!(stats.complete_level, Some(order.first_level()));
+            let config = ProverConfig {
+                max_term_depth: max_term_depth.map(|max| max + 1),
+                max_term_size: max_term_size.map(|max| max + 1),
+                term_order: order,
+                ..ProverConfig::default()
+            };
+            assert_eq!(limited(config), Ok(()));
+        }
+        assert_eq!(
+            limited(ProverConfig {
+                max_term_size: Some(1),
+                ..ProverConfig::default()
+            }),
+            Err(UnknownReason::TermSizeExceeded)
+        );
+        assert_eq!(
+            limited(ProverConfig {
+                max_instances: Some(1),
+                ..ProverConfig::default()
+            }),
+            Err(UnknownReason::InstanceBudgetExhausted)
+        );
+        assert_eq!(
+            limited(ProverConfig {
+                deadline: Some(Instant::now()),
+                ..ProverConfig::default()
+            }),
+            Err(UnknownReason::Timeout)
+        );
+    }
+
+    #[test]
+    fn instantiates_variables_by_sort() {
+        for engine in [Engine::Herbrand, Engine::Resolution, Engine::InstGen] {
+            let satisfiability = |sorted: bool| {
+                // The equality axioms keep resolution from saturating
+                let options = Options {
+                    engine,
+                    config: ProverConfig {
+                        deadline: Some(Instant::now() + Duration::from_millis(200)),
+                        ..ProverConfig::default()
+                    },
+                };
+                let mut translator = Translator::new();
+                let (s, t) = if sorted {
+                    (translator.sort("s"), translator.sort("t"))
+                } else {
+                    Default::default()
+                };
+                let (x, y) = (
+                    translator.var("x").with_sort(s),
+                    translator.var("y").with_sort(s),
+                );
+                let a = Term::Fun(translator.fun("a").with_sort(s), vec![]);
+                let b = Term::Fun(translator.fun("b").with_sort(t), vec![]);
+                let c = Term::Fun(translator.fun("c").with_sort(t), vec![]);
+                let eq = translator.rel(EQUALITY);
+                // The sort s has a single element, which does not keep b and c of t apart
+                let formula = Formula::And(
+                    Box::new(Formula::Forall(
+                        x,
+                        Box::new(Formula::Forall(
+                            y,
+                            Box::new(Formula::Rel(eq, vec![Term::Var(x), Term::Var(y)])),
+                        )),
+                    )),
+                    Box::new(Formula::And(
+                        Box::new(Formula::Rel(eq, vec![a.clone(), a])),
+                        Box::new(Formula::Not(Box::new(Formula::Rel(eq, vec![b, c])))),
+                    )),
+                );
+                is_satisfiable(formula, &mut translator, &options)
+            };
+            match satisfiability(true) {
+                Satisfiability::Satisfiable(_) => {}
+                Satisfiability::Unknown(UnknownReason::Timeout) if engine == Engine::Resolution => {
+                }
+                satisfiability => panic!("unexpected {:?}", satisfiability),
+            }
+            assert!(matches!(
+                satisfiability(false),
+                Satisfiability::Unsatisfiable(_)
+            ));
+        }
+    }
+
+    #[test]
+    fn decides_formulas_built_directly() {
+        for engine in [Engine::Herbrand, Engine::Resolution, Engine::InstGen] {
+            let options = Options {
+                engine,
+                ..Options::default()
+            };
+            let mut translator = Translator::new();
+            let (x, f, c) = (
+                translator.var("x"),
+                translator.fun("f"),
+                translator.rel("c"),
+            );
+            let a = Term::Fun(translator.fun("a"), vec![]);
+            let fa = Term::Fun(f, vec![a.clone()]);
+            // c(a) and c(x) -> c(f(x)) entail c(f(a))
+            let formula = Formula::Implies(
+                Box::new(Formula::And(
+                    Box::new(Formula::Rel(c, vec![a.clone()])),
+                    Box::new(Formula::Forall(
+                        x,
+                        Box::new(Formula::Implies(
+                            Box::new(Formula::Rel(c, vec![Term::Var(x)])),
+                            Box::new(Formula::Rel(c, vec![Term::Fun(f, vec![Term::Var(x)])])),
+                        )),
+                    )),
+                )),
+                Box::new(Formula::Rel(c, vec![fa.clone()])),
+            );
+            assert!(matches!(
+                is_tautology(formula, &mut translator, &options),
+                Verdict::Valid(_)
+            ));
+            // c(a) and not c(f(a)) has a model, but not with f(a) = a
+            let formula = Formula::And(
+                Box::new(Formula::Rel(c, vec![a.clone()])),
+                Box::new(Formula::Not(Box::new(Formula::Rel(c, vec![fa.clone()])))),
+            );
+            let model = match is_satisfiable(formula.clone(), &mut translator, &options) {
+                Satisfiability::Satisfiable(model) => model,
+                satisfiability => panic!("unexpected {:?}", satisfiability),
+            };
+            assert_eq!(model.is_some(), engine == Engine::Herbrand);
+            let fixpoint = Formula::Rel(translator.rel(EQUALITY), vec![fa, a]);
+            assert!(matches!(
+                is_satisfiable(
+                    Formula::And(Box::new(formula), Box::new(fixpoint)),
+                    &mut translator,
+                    &options
+                ),
+                Satisfiability::Unsatisfiable(_)
+            ));
+        }
+    }
+}
@@ -1,136 +1,781 @@
+use itertools::Itertools;
+use parser::error::ParseError;
 use parser::formula::*;
+use parser::infix::parse_infix_with;
+use parser::printer::{to_infix, to_native, to_tptp};
+use parser::signature::{Signature, Sorts, Warning};
+use parser::smtlib::{parse_smtlib, Command, BOOL};
+use parser::tptp::parse_tptp_with;
 use std::collections::HashMap;
+use std::env;
 use std::io::{self, Read};
+use std::path::PathBuf;
+use std::process;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
-use crate::prover::is_tautology;
+mod batch;
 
-mod fol;
-mod herbrand_universe;
-mod interleave;
-mod lazy_sequence;
-mod pl;
-mod pl_sat_solver;
-mod prover;
-mod tuple_iterator;
+use prover::fol;
+use prover::substitution::Substitution;
+use prover::{
+    is_satisfiable, is_tautology_with_statistics, Elem, Engine, Model, Namer,
+    Options as ProverOptions, Proof, Satisfiability, TermOrder, Translator, UnknownReason, Verdict,
+    EQUALITY,
+};
 
+/// Translates `pformula`, whose symbols and variables have the sorts `sorts`, after checking
+/// them.
 fn parser_formula_to_fo_formula(
-    pformula: Box<Formula>,
-) -> (
-    fol::Formula,
-    fol::NameAllocator<fol::Var>,
-    fol::NameAllocator<fol::Fun>,
-    fol::NameAllocator<fol::Rel>,
-) {
-    #[derive(Default)]
-    struct Translator {
-        var_alloc: fol::NameAllocator<fol::Var>,
-        vars: HashMap<String, fol::Var>,
-        fun_alloc: fol::NameAllocator<fol::Fun>,
-        funs: HashMap<String, fol::Fun>,
-        rel_alloc: fol::NameAllocator<fol::Rel>,
-        rels: HashMap<String, fol::Rel>,
-    }
+    pformula: Formula,
+    sorts: &Sorts,
+) -> Result<(fol::Formula, Translator), String> {
+    let mut translator = Translator::new();
+    translator.declare(sorts);
+    translator.check_sorts(&pformula)?;
+    Ok((translator.translate(pformula), translator))
+}
 
-    impl Translator {
-        fn translate_var(&mut self, var: String) -> fol::Var {
-            let alloc = &mut self.var_alloc;
-            *self.vars.entry(var).or_insert_with(|| alloc.alloc())
+/// Prints the part of `model` that interprets the symbols of the input formula.
+fn print_model(model: &Model, translator: &Translator) {
+    println!("domain: {}", (0..model.domain_size).format(", "));
+    for (name, fun) in translator.funs().sorted_by_key(|(name, _)| *name) {
+        let table = model
+            .functions
+            .iter()
+            .filter(|((f, _), _)| *f == fun)
+            .map(|((_, args), value)| (args, value))
+            .sorted();
+        for (args, value) in table {
+            if args.is_empty() {
+                println!("{} = {}", name, value);
+            } else {
+                println!("{}({}) = {}", name, args.iter().format(", "), value);
+            }
+        }
+    }
+    for (name, rel) in translator.rels().sorted_by_key(|(name, _)| *name) {
+        let atoms = model
+            .relations
+            .iter()
+            .filter(|(r, _)| *r == rel)
+            .map(|(_, args)| args)
+            .sorted();
+        for args in atoms {
+            if args.is_empty() {
+                println!("{}", name);
+            } else {
+                println!("{}({})", name, args.iter().format(", "));
+            }
         }
+    }
+}
 
-        fn translate_fun(&mut self, fun: String) -> fol::Fun {
-            let alloc = &mut self.fun_alloc;
-            *self.funs.entry(fun).or_insert_with(|| alloc.alloc())
+fn print_term(term: &fol::Term, fun_names: &HashMap<fol::Fun, &String>) -> String {
+    match term {
+        fol::Term::Var(var) => format!("{:?}", var),
+        fol::Term::Fun(fun, args) => {
+            let name = match fun_names.get(fun) {
+                Some(name) => name.to_string(),
+                None => format!("sk{}", fun.index()),
+            };
+            if args.is_empty() {
+                name
+            } else {
+                format!(
+                    "{}({})",
+                    name,
+                    args.iter()
+                        .map(|arg| print_term(arg, fun_names))
+                        .format(", ")
+                )
+            }
         }
+    }
+}
 
-        fn translate_rel(&mut self, rel: String) -> fol::Rel {
-            let alloc = &mut self.rel_alloc;
-            *self.rels.entry(rel).or_insert_with(|| alloc.alloc())
+/// Prints the instances used by `proof` as ground clauses (Skolem functions are named `sk<n>` and
+/// the relations defining parts of the clausal form `def<n>`) followed by their clauses in the
+/// DIMACS format, with the atom of each variable in a comment, and a refutation of them in the
+/// DRAT format, after checking it.
+fn print_proof(proof: &Proof, translator: &Translator) {
+    // Only the instances in an unsatisfiable core are printed
+    let proof = proof.core();
+    let certificate = proof.certificate().unwrap_or_else(|err| {
+        eprintln!("invalid proof: {}", err);
+        process::exit(3);
+    });
+    let fun_names = translator.funs().map(|(name, fun)| (fun, name)).collect();
+    let rel_names = translator
+        .rels()
+        .map(|(name, rel)| (rel, name))
+        .collect::<HashMap<_, _>>();
+    let literal = |positive: bool, rel: &fol::Rel, args: &[fol::Term], interp: &Substitution| {
+        let args = args
+            .iter()
+            .map(|arg| print_term(&interp.apply(arg), &fun_names))
+            .collect::<Vec<_>>();
+        let name = match rel_names.get(rel) {
+            Some(name) => name.to_string(),
+            None => format!("def{}", rel.index()),
+        };
+        match (name.as_str(), positive) {
+            (EQUALITY, true) if args.len() == 2 => format!("{} = {}", args[0], args[1]),
+            (EQUALITY, false) if args.len() == 2 => format!("{} != {}", args[0], args[1]),
+            (name, true) if args.is_empty() => name.to_string(),
+            (name, false) if args.is_empty() => format!("not {}", name),
+            (name, true) => format!("{}({})", name, args.join(", ")),
+            (name, false) => format!("not {}({})", name, args.join(", ")),
+        }
+    };
+    println!("instances:");
+    for (clause_idx, terms) in &proof.instances {
+        let clause = &proof.clauses[*clause_idx];
+        let interp = clause
+            .vars
+            .iter()
+            .copied()
+            .zip(terms.iter().cloned())
+            .collect();
+        println!(
+            "{}",
+            clause
+                .literals
+                .iter()
+                .map(|lit| literal(lit.positive, &lit.rel, &lit.args, &interp))
+                .format(" | ")
+        );
+    }
+    if !proof.lemmas.is_empty() {
+        println!("lemmas:");
+        for lemma in &proof.lemmas {
+            println!(
+                "{}",
+                lemma
+                    .iter()
+                    .map(|lit| literal(lit.positive, &lit.rel, &lit.args, &Substitution::new()))
+                    .format(" | ")
+            );
+        }
+    }
+    let print_clauses = |clauses: &[Vec<i64>]| {
+        for clause in clauses {
+            for lit in clause {
+                print!("{} ", lit);
+            }
+            println!("0");
         }
+    };
+    println!("cnf:");
+    for (idx, (rel, args)) in certificate.atoms.iter().enumerate() {
+        println!(
+            "c {} {}",
+            idx + 1,
+            literal(true, rel, args, &Substitution::new())
+        );
+    }
+    println!(
+        "p cnf {} {}",
+        certificate.atoms.len(),
+        certificate.clauses.len()
+    );
+    print_clauses(&certificate.clauses);
+    println!("refutation:");
+    print_clauses(&certificate.refutation);
+}
 
-        fn translate_term(&mut self, term: Term) -> fol::Term {
-            match term {
-                Term::Var(name) => fol::Term::Var(self.translate_var(name)),
-                Term::Fun(name, terms) => fol::Term::Fun(
-                    self.translate_fun(name),
-                    terms.into_iter().map(|x| self.translate_term(x)).collect(),
+/// Quotes `name` if it is not a simple SMT-LIB symbol.
+fn smt_symbol(name: &str) -> String {
+    let is_simple = !name.starts_with(|c: char| c.is_ascii_digit())
+        && !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "~!@$%^&*_-+=<>.?/".contains(c));
+    if is_simple {
+        name.to_string()
+    } else {
+        format!("|{}|", name)
+    }
+}
+
+/// Prints `model` as the SMT-LIB definitions of the declared symbols. The domain element `n` is
+/// printed as `@n` in every sort, and symbols missing from the model are given any value.
+fn print_smt_model(
+    model: &Model,
+    translator: &Translator,
+    declarations: &[(String, Vec<String>, String)],
+) {
+    let condition = |args: &[Elem]| {
+        let equalities = args
+            .iter()
+            .enumerate()
+            .map(|(idx, elem)| format!("(= x{} @{})", idx, elem))
+            .collect::<Vec<_>>();
+        match equalities.len() {
+            1 => equalities[0].clone(),
+            _ => format!("(and {})", equalities.join(" ")),
+        }
+    };
+    println!("(");
+    for (name, arg_sorts, sort) in declarations {
+        let body = if sort == BOOL {
+            let rows = match translator.find_rel(name) {
+                Some(rel) => model
+                    .relations
+                    .iter()
+                    .filter(|(r, _)| *r == rel)
+                    .map(|(_, args)| args)
+                    .sorted()
+                    .collect(),
+                None => vec![],
+            };
+            match (arg_sorts.len(), rows.len()) {
+                (_, 0) => "false".to_string(),
+                (0, _) => "true".to_string(),
+                (_, 1) => condition(rows[0]),
+                _ => format!(
+                    "(or {})",
+                    rows.into_iter().map(|args| condition(args)).format(" ")
                 ),
             }
+        } else {
+            let rows = match translator.find_fun(name) {
+                Some(fun) => model
+                    .functions
+                    .iter()
+                    .filter(|((f, _), _)| *f == fun)
+                    .map(|((_, args), value)| (args, value))
+                    .sorted()
+                    .collect(),
+                None => vec![],
+            };
+            // The last value is the default one
+            let default = rows.last().map_or(0, |(_, value)| **value);
+            rows.iter()
+                .rev()
+                .filter(|(_, value)| **value != default)
+                .fold(format!("@{}", default), |acc, (args, value)| {
+                    format!("(ite {} @{} {})", condition(args), value, acc)
+                })
+        };
+        println!(
+            "  (define-fun {} ({}) {} {})",
+            smt_symbol(name),
+            arg_sorts
+                .iter()
+                .enumerate()
+                .map(|(idx, sort)| format!("(x{} {})", idx, smt_symbol(sort)))
+                .format(" "),
+            smt_symbol(sort),
+            body
+        );
+    }
+    println!(")");
+}
+
+/// Runs the commands of an SMT-LIB script, deciding the satisfiability of the asserted formulas
+/// at each `check-sat`.
+fn run_smtlib(commands: Vec<Command>, options: &ProverOptions) {
+    let mut sorts = Vec::new();
+    let mut declarations = Vec::new();
+    let mut assertions = Vec::new();
+    // For each `push`, the number of sorts, declarations and assertions before it
+    let mut levels = Vec::new();
+    // The model and its names after a satisfiable `check-sat`, until the assertions change
+    let mut last_model = None;
+    for command in commands {
+        match command {
+            Command::DeclareSort(name) => sorts.push(name),
+            Command::DeclareFun(name, args, sort) => declarations.push((name, args, sort)),
+            Command::Assert(formula, vars) => {
+                assertions.push((formula, vars));
+                last_model = None;
+            }
+            Command::CheckSat => {
+                last_model = None;
+                let conjunction = assertions
+                    .iter()
+                    .map(|(formula, _)| formula.clone())
+                    .fold(Formula::True, |acc, formula| {
+                        Formula::And(Box::new(acc), Box::new(formula))
+                    });
+                let (rels, funs) = declarations
+                    .iter()
+                    .cloned()
+                    .partition::<Vec<_>, _>(|(_, _, sort)| sort == BOOL);
+                let sorts = Sorts {
+                    sorts: sorts.clone(),
+                    funs: funs
+                        .into_iter()
+                        .map(|(name, args, sort)| (name, (args, sort)))
+                        .collect(),
+                    rels: rels
+                        .into_iter()
+                        .map(|(name, args, _)| (name, args))
+                        .collect(),
+                    vars: assertions
+                        .iter()
+                        .flat_map(|(_, vars)| vars.iter().cloned())
+                        .collect(),
+                };
+                let (formula, mut translator) = parser_formula_to_fo_formula(conjunction, &sorts)
+                    .unwrap_or_else(|err| ill_sorted(err));
+                let result = match is_satisfiable(formula, &mut translator, options) {
+                    Satisfiability::Satisfiable(model) => {
+                        last_model = model.map(|model| (model, translator));
+                        Some(true)
+                    }
+                    Satisfiability::Unsatisfiable(_) => Some(false),
+                    Satisfiability::Unknown(_) => None,
+                };
+                match result {
+                    Some(true) => println!("sat"),
+                    Some(false) => println!("unsat"),
+                    None => println!("unknown"),
+                }
+            }
+            Command::GetModel => match &last_model {
+                Some((model, translator)) => print_smt_model(model, translator, &declarations),
+                None => println!("(error \"no model is available\")"),
+            },
+            Command::Push(count) => {
+                for _ in 0..count {
+                    levels.push((sorts.len(), declarations.len(), assertions.len()));
+                }
+                last_model = None;
+            }
+            Command::Pop(count) => {
+                for _ in 0..count {
+                    let (sort_count, declared, asserted) = levels.pop().unwrap();
+                    sorts.truncate(sort_count);
+                    declarations.truncate(declared);
+                    assertions.truncate(asserted);
+                }
+                last_model = None;
+            }
+            Command::Echo(text) => println!("\"{}\"", text.replace('"', "\"\"")),
+            Command::Exit => return,
         }
+    }
+}
 
-        fn translate_formula(&mut self, formula: Formula) -> fol::Formula {
-            match formula {
-                Formula::True => fol::Formula::True,
-                Formula::False => fol::Formula::False,
-                Formula::Rel(name, terms) => fol::Formula::Rel(
-                    self.translate_rel(name),
-                    terms.into_iter().map(|x| self.translate_term(x)).collect(),
-                ),
-                Formula::Not(phi) => fol::Formula::Not(Box::new(self.translate_formula(*phi))),
-                Formula::Or(phi, psi) => fol::Formula::Or(
-                    Box::new(self.translate_formula(*phi)),
-                    Box::new(self.translate_formula(*psi)),
-                ),
-                Formula::And(phi, psi) => fol::Formula::And(
-                    Box::new(self.translate_formula(*phi)),
-                    Box::new(self.translate_formula(*psi)),
-                ),
-                Formula::Implies(phi, psi) => fol::Formula::Implies(
-                    Box::new(self.translate_formula(*phi)),
-                    Box::new(self.translate_formula(*psi)),
-                ),
-                Formula::Iff(phi, psi) => fol::Formula::Iff(
-                    Box::new(self.translate_formula(*phi)),
-                    Box::new(self.translate_formula(*psi)),
-                ),
-                Formula::Exists(var, phi) => {
-                    let old_mapping = self.vars.remove_entry(&var);
-                    let res = fol::Formula::Exists(
-                        self.translate_var(var.clone()),
-                        Box::new(self.translate_formula(*phi)),
-                    );
-                    match old_mapping {
-                        Some((k, v)) => {
-                            self.vars.insert(k, v);
-                        }
-                        None => {
-                            self.vars.remove(&var);
-                        }
-                    };
-                    res
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum Format {
+    /// The `Implies (Rel "p" [Var "x"]) ...` syntax
+    Native,
+    /// The `forall x. p(x) -> ...` syntax
+    Infix,
+    /// TPTP FOF, CNF and TFF problems
+    Tptp,
+    /// SMT-LIB 2 scripts, whose commands are run in order
+    Smtlib,
+}
+
+/// A form of the input printed by `--dump`.
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum Stage {
+    Input,
+    /// The negation normal form of the negated input, which the engines refute
+    Nnf,
+    /// The prenex normal form of the negated input
+    Pnf,
+    /// The skolemized prenex normal form of the negated input
+    Skolem,
+}
+
+#[derive(Default)]
+struct Options {
+    /// The engine and its limits
+    prover: ProverOptions,
+    /// Detected from the input if not given
+    format: Option<Format>,
+    /// Directory against which TPTP `include` directives are resolved
+    include_dir: Option<PathBuf>,
+    /// Print a countermodel after refuting the formula
+    print_model: bool,
+    /// Print a checked proof after proving the formula
+    print_proof: bool,
+    /// Print the result as an SZS status, followed by the proof or the model if there is one
+    szs: bool,
+    /// Print a form of the input instead of proving it
+    dump: Option<Stage>,
+    /// The syntax of `dump`, native by default
+    dump_format: Option<Format>,
+    /// Split symbols used with several kinds or arities instead of rejecting the input
+    permissive: bool,
+    /// The time given to each problem
+    timeout: Option<Duration>,
+    /// The files and directories of problems to solve in a batch instead of reading stdin
+    batch: Vec<PathBuf>,
+    /// Where to write the results of a batch
+    json: Option<PathBuf>,
+    csv: Option<PathBuf>,
+    /// The arguments that configure how a problem is solved, which each problem of a batch is
+    /// solved with in a process of its own
+    solve_args: Vec<String>,
+    /// The problem of a batch that this process solves, reporting a line of results
+    batch_problem: Option<PathBuf>,
+}
+
+/// The SZS status of a formula found valid (`Ok(true)`), not valid (`Ok(false)`) or neither.
+/// Without a conjecture, the formula is the negation of the axioms.
+fn szs_status(result: Result<bool, UnknownReason>, has_conjecture: bool) -> &'static str {
+    match (result, has_conjecture) {
+        (Ok(true), true) => "Theorem",
+        (Ok(true), false) => "Unsatisfiable",
+        (Ok(false), true) => "CounterSatisfiable",
+        (Ok(false), false) => "Satisfiable",
+        (Err(UnknownReason::Timeout), _) => "Timeout",
+        (Err(UnknownReason::InstanceBudgetExhausted), _)
+        | (Err(UnknownReason::TermDepthExceeded), _)
+        | (Err(UnknownReason::TermSizeExceeded), _)
+        | (Err(UnknownReason::ConflictBudgetExhausted), _)
+        | (Err(UnknownReason::ClauseBudgetExhausted), _) => "GaveUp",
+    }
+}
+
+/// Tells a formula in the native syntax, which starts with a constructor such as `Implies`, from
+/// one in the infix syntax.
+fn detect_format(input: &str) -> Format {
+    let input = input.trim();
+    let first_word = input
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .next()
+        .unwrap_or("");
+    match first_word {
+        "Rel" | "Not" | "And" | "Or" | "Implies" | "Iff" | "Forall" | "Exists" => Format::Native,
+        // The constants are the only native formulas that are a single word
+        "T" | "F" if input == first_word => Format::Native,
+        _ => Format::Infix,
+    }
+}
+
+/// Reports an input that cannot be read, with the exit code 4 to tell it from usage errors (1).
+fn input_error(err: ParseError) -> ! {
+    eprintln!("{}", err);
+    process::exit(4);
+}
+
+/// Reports an input whose terms do not have the sorts they are declared with, as an input error.
+fn ill_sorted(message: String) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(4);
+}
+
+/// Parses the value of the option `arg`, which is `expected`.
+fn parse_number<T: FromStr>(arg: &str, value: Option<String>, expected: &str) -> T {
+    match value.map(|value| value.parse()) {
+        Some(Ok(value)) => value,
+        _ => {
+            eprintln!("{} expects {}", arg, expected);
+            process::exit(1);
+        }
+    }
+}
+
+fn parse_args() -> Options {
+    let mut options = Options::default();
+    let raw_args = env::args().skip(1).collect::<Vec<_>>();
+    let mut args = raw_args.clone().into_iter();
+    while let Some(arg) = args.next() {
+        let first = raw_args.len() - args.len() - 1;
+        let solve_arg = arg.starts_with("--")
+            && !["--json", "--csv", "--batch-problem"].contains(&arg.as_str());
+        match arg.as_str() {
+            "--model" => options.print_model = true,
+            "--proof" => options.print_proof = true,
+            "--szs" => options.szs = true,
+            "--permissive" => options.permissive = true,
+            "--dump" => match args.next().as_deref() {
+                Some("input") => options.dump = Some(Stage::Input),
+                Some("nnf") => options.dump = Some(Stage::Nnf),
+                Some("pnf") => options.dump = Some(Stage::Pnf),
+                Some("skolem") => options.dump = Some(Stage::Skolem),
+                _ => {
+                    eprintln!("--dump expects input, nnf, pnf or skolem");
+                    process::exit(1);
+                }
+            },
+            "--dump-format" => match args.next().as_deref() {
+                Some("native") => options.dump_format = Some(Format::Native),
+                Some("infix") => options.dump_format = Some(Format::Infix),
+                Some("tptp") => options.dump_format = Some(Format::Tptp),
+                _ => {
+                    eprintln!("--dump-format expects native, infix or tptp");
+                    process::exit(1);
+                }
+            },
+            "--format" => match args.next().as_deref() {
+                Some("native") => options.format = Some(Format::Native),
+                Some("infix") => options.format = Some(Format::Infix),
+                Some("tptp") => options.format = Some(Format::Tptp),
+                Some("smtlib") => options.format = Some(Format::Smtlib),
+                _ => {
+                    eprintln!("--format expects native, infix, tptp or smtlib");
+                    process::exit(1);
+                }
+            },
+            "--include-dir" => match args.next() {
+                Some(dir) => options.include_dir = Some(PathBuf::from(dir)),
+                None => {
+                    eprintln!("--include-dir expects a directory");
+                    process::exit(1);
+                }
+            },
+            "--timeout" => {
+                let seconds = parse_number::<f64>(&arg, args.next(), "a number of seconds");
+                let timeout = Duration::try_from_secs_f64(seconds).unwrap_or_else(|_| {
+                    eprintln!("--timeout expects a number of seconds");
+                    process::exit(1);
+                });
+                options.timeout = Some(timeout);
+            }
+            "--max-instances" => {
+                options.prover.config.max_instances =
+                    Some(parse_number(&arg, args.next(), "a number of instances"))
+            }
+            "--max-term-depth" => {
+                options.prover.config.max_term_depth =
+                    Some(parse_number(&arg, args.next(), "a term depth"))
+            }
+            "--max-term-size" => {
+                options.prover.config.max_term_size =
+                    Some(parse_number(&arg, args.next(), "a term size"))
+            }
+            "--term-order" => match args.next().as_deref() {
+                Some("interleaved") => options.prover.config.term_order = TermOrder::Interleaved,
+                Some("depth") => options.prover.config.term_order = TermOrder::Depth,
+                Some("size") => options.prover.config.term_order = TermOrder::Size,
+                _ => {
+                    eprintln!("--term-order expects interleaved, depth or size");
+                    process::exit(1);
                 }
-                Formula::Forall(var, phi) => {
-                    let old_mapping = self.vars.remove_entry(&var);
-                    let res = fol::Formula::Forall(
-                        self.translate_var(var.clone()),
-                        Box::new(self.translate_formula(*phi)),
-                    );
-                    match old_mapping {
-                        Some((k, v)) => {
-                            self.vars.insert(k, v);
-                        }
-                        None => {
-                            self.vars.remove(&var);
-                        }
-                    };
-                    res
+            },
+            "--max-conflicts" => {
+                options.prover.config.max_conflicts =
+                    Some(parse_number(&arg, args.next(), "a number of conflicts"))
+            }
+            "--json" | "--csv" => {
+                let path = args.next().map(PathBuf::from).unwrap_or_else(|| {
+                    eprintln!("{} expects a file", arg);
+                    process::exit(1);
+                });
+                if arg == "--json" {
+                    options.json = Some(path);
+                } else {
+                    options.csv = Some(path);
                 }
             }
+            "--engine" => match args.next().as_deref() {
+                Some("herbrand") => options.prover.engine = Engine::Herbrand,
+                Some("resolution") => options.prover.engine = Engine::Resolution,
+                Some("instgen") => options.prover.engine = Engine::InstGen,
+                _ => {
+                    eprintln!("--engine expects herbrand, resolution or instgen");
+                    process::exit(1);
+                }
+            },
+            "--batch-problem" => match args.next() {
+                Some(path) => options.batch_problem = Some(PathBuf::from(path)),
+                None => {
+                    eprintln!("--batch-problem expects a file");
+                    process::exit(1);
+                }
+            },
+            _ if arg.starts_with("--") => {
+                eprintln!("unknown argument: {}", arg);
+                process::exit(1);
+            }
+            _ => options.batch.push(PathBuf::from(arg)),
+        }
+        if solve_arg {
+            let last = raw_args.len() - args.len();
+            options.solve_args.extend_from_slice(&raw_args[first..last]);
         }
     }
+    if options.prover.engine != Engine::Herbrand && options.print_model {
+        eprintln!("--model is only supported by the herbrand engine");
+        process::exit(1);
+    }
+    if options.prover.engine == Engine::Resolution && options.print_proof {
+        eprintln!("--proof is not supported by the resolution engine");
+        process::exit(1);
+    }
+    if options.format == Some(Format::Smtlib)
+        && (options.print_model || options.print_proof || options.szs || options.dump.is_some())
+    {
+        eprintln!("--model, --proof, --szs and --dump are not supported for SMT-LIB scripts");
+        process::exit(1);
+    }
+    if options.batch.is_empty() && (options.json.is_some() || options.csv.is_some()) {
+        eprintln!("--json and --csv expect problem files or directories");
+        process::exit(1);
+    }
+    if !options.batch.is_empty()
+        && (options.print_model || options.print_proof || options.szs || options.dump.is_some())
+    {
+        eprintln!("--model, --proof, --szs and --dump are not supported in batch mode");
+        process::exit(1);
+    }
+    options
+}
 
-    let mut translator = Translator::default();
-    (
-        translator.translate_formula(*pformula),
-        translator.var_alloc,
-        translator.fun_alloc,
-        translator.rel_alloc,
-    )
+/// A parsed input.
+enum Problem {
+    /// A formula whose validity is decided. Without a conjecture, a TPTP problem asks whether its
+    /// axioms are unsatisfiable.
+    Formula {
+        formula: Box<Formula>,
+        sorts: Sorts,
+        has_conjecture: bool,
+        warnings: Vec<Warning>,
+    },
+    /// An SMT-LIB script, whose commands are run in order
+    Script(Vec<Command>),
+}
+
+fn read_problem(input: &str, format: Format, options: &Options) -> Result<Problem, ParseError> {
+    let mut signature = if options.permissive {
+        Signature::permissive()
+    } else {
+        Signature::new()
+    };
+    let mut has_conjecture = true;
+    let formula = match format {
+        Format::Native => parse_formula_with(input, &mut signature)?,
+        Format::Infix => parse_infix_with(input, &mut signature)?,
+        Format::Tptp => {
+            // Like other TPTP tools, default to the TPTP environment variable
+            let include_dir = options
+                .include_dir
+                .clone()
+                .or_else(|| env::var_os("TPTP").map(PathBuf::from))
+                .unwrap_or_else(|| PathBuf::from("."));
+            let problem = parse_tptp_with(input, &include_dir, &mut signature)?;
+            has_conjecture = !problem.conjectures.is_empty();
+            problem.into_formula()
+        }
+        Format::Smtlib => return Ok(Problem::Script(parse_smtlib(input)?)),
+    };
+    Ok(Problem::Formula {
+        formula,
+        sorts: signature.sorts().clone(),
+        has_conjecture,
+        warnings: signature.warnings().to_vec(),
+    })
 }
 
 fn main() {
+    let mut options = parse_args();
+    if let Some(path) = &options.batch_problem {
+        batch::report(path, &options);
+        return;
+    }
+    if !options.batch.is_empty() {
+        batch::run(&options);
+        return;
+    }
+    options.prover.config.deadline = options.timeout.map(|timeout| Instant::now() + timeout);
     let mut raw_formula = String::new();
     io::stdin().read_to_string(&mut raw_formula).unwrap();
-    let formula = parse_formula(&raw_formula).unwrap();
-    let (formula, _var_alloc, mut fun_alloc, _rel_alloc) = parser_formula_to_fo_formula(formula);
-    println!("{}", is_tautology(formula, &mut fun_alloc) as u8);
+    let format = options
+        .format
+        .unwrap_or_else(|| detect_format(&raw_formula));
+    let problem =
+        read_problem(&raw_formula, format, &options).unwrap_or_else(|err| input_error(err));
+    let (formula, sorts, has_conjecture) = match problem {
+        Problem::Formula {
+            formula,
+            sorts,
+            has_conjecture,
+            warnings,
+        } => {
+            for warning in warnings {
+                eprintln!("{}", warning);
+            }
+            (formula, sorts, has_conjecture)
+        }
+        Problem::Script(commands) => {
+            run_smtlib(commands, &options.prover);
+            return;
+        }
+    };
+    let (formula, mut translator) =
+        parser_formula_to_fo_formula(*formula, &sorts).unwrap_or_else(|err| ill_sorted(err));
+    if let Some(stage) = options.dump {
+        let (formula, name, role) = match stage {
+            Stage::Input => (formula, "input", "conjecture"),
+            Stage::Nnf => (
+                fol::into_nnf(fol::Formula::Not(Box::new(formula))).0,
+                "nnf",
+                "negated_conjecture",
+            ),
+            Stage::Pnf => (
+                fol::into_pnf(fol::Formula::Not(Box::new(formula))),
+                "pnf",
+                "negated_conjecture",
+            ),
+            Stage::Skolem => (
+                fol::skolemize(fol::Formula::Not(Box::new(formula)), translator.fun_alloc()),
+                "skolem",
+                "negated_conjecture",
+            ),
+        };
+        let formula = Namer::new(&translator).formula(&formula);
+        match options.dump_format.unwrap_or(Format::Native) {
+            Format::Infix => println!("{}", to_infix(&formula)),
+            Format::Tptp => println!("{}", to_tptp(&formula, name, role)),
+            _ => println!("{}", to_native(&formula)),
+        }
+        return;
+    }
+    let (verdict, stats) = is_tautology_with_statistics(formula, &mut translator, &options.prover);
+    if options.szs {
+        let result = match &verdict {
+            Verdict::Valid(_) => Ok(true),
+            Verdict::Invalid(_) => Ok(false),
+            Verdict::Unknown(reason) => Err(*reason),
+        };
+        println!("% SZS status {}", szs_status(result, has_conjecture));
+        match verdict {
+            Verdict::Valid(Some(proof)) => {
+                println!("% SZS output start CNFRefutation");
+                print_proof(&proof, &translator);
+                println!("% SZS output end CNFRefutation");
+            }
+            Verdict::Invalid(Some(model)) => {
+                println!("% SZS output start FiniteModel");
+                print_model(&model, &translator);
+                println!("% SZS output end FiniteModel");
+            }
+            _ => {}
+        }
+        return;
+    }
+    match verdict {
+        Verdict::Valid(proof) => {
+            println!("1");
+            if let Some(proof) = proof.filter(|_| options.print_proof) {
+                print_proof(&proof, &translator);
+            }
+        }
+        Verdict::Invalid(model) => {
+            println!("0");
+            if let Some(model) = model.filter(|_| options.print_model) {
+                print_model(&model, &translator);
+            }
+        }
+        Verdict::Unknown(reason) => {
+            match (stats.complete_level, options.prover.config.term_order) {
+                (Some(level), TermOrder::Size) => {
+                    eprintln!("unknown: {} (complete up to size {})", reason, level)
+                }
+                (Some(level), _) => {
+                    eprintln!("unknown: {} (complete up to depth {})", reason, level)
+                }
+                (None, _) => eprintln!("unknown: {}", reason),
+            }
+            process::exit(2);
+        }
+    }
 }
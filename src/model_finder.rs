@@ -0,0 +1,377 @@
+use std::collections::{HashMap, HashSet};
+
+use itertools::Itertools;
+
+use crate::{
+    fol::{self, func_sig, Fun, Rel, Term},
+    pl::{self, into_ecnf},
+    pl_sat_solver::solve,
+    prover::{ProverConfig, UnknownReason},
+    tuple_iterator::TupleIterator,
+};
+
+pub type Elem = usize;
+
+/// A finite interpretation over the domain {0, ..., domain_size - 1}. Relation atoms not listed in
+/// `relations` are false.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Model {
+    pub domain_size: usize,
+    pub functions: HashMap<(Fun, Vec<Elem>), Elem>,
+    pub relations: HashSet<(Rel, Vec<Elem>)>,
+}
+
+/// Grounds a universally quantified, skolemized formula over the domain {0, ..., domain_size - 1}.
+/// Every function is encoded by its table: a propositional variable for each `f(args) = value`.
+/// The relation `equality` is interpreted as the identity.
+struct Grounder {
+    domain_size: usize,
+    equality: Option<Rel>,
+    fun_vars: HashMap<(Fun, Vec<Elem>, Elem), pl::Var>,
+    rel_vars: HashMap<(Rel, Vec<Elem>), pl::Var>,
+    var_alloc: pl::VarAllocator,
+}
+
+impl Grounder {
+    fn new(domain_size: usize, equality: Option<Rel>) -> Self {
+        Self {
+            domain_size,
+            equality,
+            fun_vars: HashMap::new(),
+            rel_vars: HashMap::new(),
+            var_alloc: pl::VarAllocator::default(),
+        }
+    }
+
+    fn fun_var(&mut self, fun: Fun, args: Vec<Elem>, value: Elem) -> pl::Var {
+        let alloc = &mut self.var_alloc;
+        *self
+            .fun_vars
+            .entry((fun, args, value))
+            .or_insert_with(|| alloc.alloc())
+    }
+
+    fn rel_var(&mut self, rel: Rel, args: Vec<Elem>) -> pl::Var {
+        let alloc = &mut self.var_alloc;
+        *self
+            .rel_vars
+            .entry((rel, args))
+            .or_insert_with(|| alloc.alloc())
+    }
+
+    /// Clauses stating that every function table maps each tuple of arguments to exactly one value.
+    fn function_tables(&mut self, func_sig: &[(Fun, usize)]) -> pl::CNFFormula {
+        let mut res = pl::CNFFormula::new();
+        let constants = func_sig.iter().filter(|(_, arity)| *arity == 0);
+        for (constant_idx, &(fun, _)) in constants.enumerate() {
+            // Symmetry breaking: the i-th constant may only take one of the first i + 1 values
+            let values_limit = (constant_idx + 1).min(self.domain_size);
+            res.insert(
+                (0..values_limit)
+                    .map(|value| pl::Literal::Pos(self.fun_var(fun, vec![], value)))
+                    .collect(),
+            );
+        }
+        for &(fun, arity) in func_sig {
+            for args in TupleIterator::new(0..self.domain_size, arity) {
+                let vars = (0..self.domain_size)
+                    .map(|value| self.fun_var(fun, args.clone(), value))
+                    .collect::<Vec<_>>();
+                if arity > 0 {
+                    res.insert(vars.iter().map(|var| pl::Literal::Pos(*var)).collect());
+                }
+                for (a, b) in vars.iter().tuple_combinations() {
+                    res.insert(
+                        vec![pl::Literal::Neg(*a), pl::Literal::Neg(*b)]
+                            .into_iter()
+                            .collect(),
+                    );
+                }
+            }
+        }
+        res
+    }
+
+    /// Returns every possible value of `term` paired with the table entries that must hold for
+    /// `term` to take it.
+    fn eval_term(
+        &mut self,
+        term: &Term,
+        env: &HashMap<fol::Var, Elem>,
+    ) -> Vec<(Vec<pl::Var>, Elem)> {
+        match term {
+            Term::Var(var) => vec![(vec![], *env.get(var).unwrap())],
+            Term::Fun(fun, args) => {
+                let mut res = Vec::new();
+                for (conds, args) in self.eval_terms(args, env) {
+                    for value in 0..self.domain_size {
+                        let mut conds = conds.clone();
+                        conds.push(self.fun_var(*fun, args.clone(), value));
+                        res.push((conds, value));
+                    }
+                }
+                res
+            }
+        }
+    }
+
+    fn eval_terms(
+        &mut self,
+        terms: &[Term],
+        env: &HashMap<fol::Var, Elem>,
+    ) -> Vec<(Vec<pl::Var>, Vec<Elem>)> {
+        let mut res = vec![(Vec::new(), Vec::new())];
+        for term in terms {
+            res = res
+                .into_iter()
+                .cartesian_product(self.eval_term(term, env))
+                .map(|((mut conds, mut values), (term_conds, value))| {
+                    conds.extend(term_conds);
+                    values.push(value);
+                    (conds, values)
+                })
+                .collect();
+        }
+        res
+    }
+
+    /// Grounds the literal `[not] rel(terms)` as: for every choice of values of the function
+    /// applications in `terms`, if the table entries select them, then the literal holds.
+    fn ground_literal(
+        &mut self,
+        rel: Rel,
+        terms: &[Term],
+        env: &HashMap<fol::Var, Elem>,
+        positive: bool,
+    ) -> pl::Formula {
+        self.eval_terms(terms, env)
+            .into_iter()
+            .map(|(conds, args)| {
+                let literal = if Some(rel) == self.equality && args.len() == 2 {
+                    if (args[0] == args[1]) == positive {
+                        pl::Formula::True
+                    } else {
+                        pl::Formula::False
+                    }
+                } else {
+                    let var = self.rel_var(rel, args);
+                    if positive {
+                        pl::Formula::Var(var)
+                    } else {
+                        pl::Formula::NotVar(var)
+                    }
+                };
+                conds.into_iter().fold(literal, |formula, cond| {
+                    pl::Formula::Or(Box::new(pl::Formula::NotVar(cond)), Box::new(formula))
+                })
+            })
+            .fold(pl::Formula::True, |a, b| {
+                pl::Formula::And(Box::new(a), Box::new(b))
+            })
+    }
+
+    fn ground(&mut self, formula: &fol::Formula, env: &HashMap<fol::Var, Elem>) -> pl::Formula {
+        match formula {
+            fol::Formula::True => pl::Formula::True,
+            fol::Formula::False => pl::Formula::False,
+            fol::Formula::Rel(rel, terms) => self.ground_literal(*rel, terms, env, true),
+            fol::Formula::Not(phi) => match phi.as_ref() {
+                fol::Formula::Rel(rel, terms) => self.ground_literal(*rel, terms, env, false),
+                _ => panic!("expected NNF formula"),
+            },
+            fol::Formula::Or(a, b) => {
+                pl::Formula::Or(Box::new(self.ground(a, env)), Box::new(self.ground(b, env)))
+            }
+            fol::Formula::And(a, b) => {
+                pl::Formula::And(Box::new(self.ground(a, env)), Box::new(self.ground(b, env)))
+            }
+            _ => panic!("expected quantifier-free NNF formula"),
+        }
+    }
+}
+
+/// Number of ground instances of the matrix over a domain of `domain_size` elements, if it fits in
+/// `usize`.
+pub(crate) fn instance_count(domain_size: usize, vars: &[fol::Var]) -> Option<usize> {
+    domain_size.checked_pow(vars.len() as u32)
+}
+
+/// Searches for a model of `Forall vars. matrix` (with `matrix` quantifier-free and in NNF) with
+/// exactly `domain_size` elements, in which `equality` is the identity, within the limits of
+/// `config`.
+pub(crate) fn find_model_of_size(
+    vars: &[fol::Var],
+    matrix: &fol::Formula,
+    domain_size: usize,
+    equality: Option<Rel>,
+    config: &ProverConfig,
+) -> Result<Option<Model>, UnknownReason> {
+    assert!(domain_size > 0);
+    let mut grounder = Grounder::new(domain_size, equality);
+    let func_sig = func_sig(matrix).into_iter().collect::<Vec<_>>();
+    let mut cnf = grounder.function_tables(&func_sig);
+    for values in TupleIterator::new(0..domain_size, vars.len()) {
+        if config.deadline_passed() {
+            return Err(UnknownReason::Timeout);
+        }
+        let env = vars.iter().copied().zip(values).collect();
+        let pl_formula = grounder.ground(matrix, &env);
+        cnf.extend(
+            into_ecnf(pl_formula, &mut grounder.var_alloc)
+                .into_iter()
+                .filter(|clause| !pl::clause_is_tautology(clause)),
+        );
+    }
+    let assignment = match solve(cnf, config)? {
+        Some(assignment) => assignment,
+        None => return Ok(None),
+    };
+    let is_true = |var: &pl::Var| assignment.get(var).copied().unwrap_or(false);
+    Ok(Some(Model {
+        domain_size,
+        functions: grounder
+            .fun_vars
+            .iter()
+            .filter(|(_, var)| is_true(var))
+            .map(|((fun, args, value), _)| ((*fun, args.clone()), *value))
+            .collect(),
+        relations: grounder
+            .rel_vars
+            .iter()
+            .filter(|(_, var)| is_true(var))
+            .map(|(atom, _)| atom.clone())
+            .collect(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        fol::{self, Formula, NameAllocator, Term},
+        tuple_iterator::TupleIterator,
+    };
+
+    use super::{find_model_of_size, Elem, Model};
+    use crate::prover::ProverConfig;
+
+    fn eval_term(model: &Model, term: &Term, env: &HashMap<fol::Var, Elem>) -> Elem {
+        match term {
+            Term::Var(var) => env[var],
+            Term::Fun(fun, args) => {
+                let args = args.iter().map(|arg| eval_term(model, arg, env)).collect();
+                model.functions[&(*fun, args)]
+            }
+        }
+    }
+
+    fn eval(model: &Model, formula: &Formula, env: &HashMap<fol::Var, Elem>) -> bool {
+        match formula {
+            Formula::True => true,
+            Formula::False => false,
+            Formula::Rel(rel, terms) => model.relations.contains(&(
+                *rel,
+                terms
+                    .iter()
+                    .map(|term| eval_term(model, term, env))
+                    .collect(),
+            )),
+            Formula::Not(phi) => !eval(model, phi, env),
+            Formula::Or(a, b) => eval(model, a, env) || eval(model, b, env),
+            Formula::And(a, b) => eval(model, a, env) && eval(model, b, env),
+            _ => panic!("expected quantifier-free NNF formula"),
+        }
+    }
+
+    fn has_model_of_size(vars: &[fol::Var], matrix: &Formula, domain_size: usize) -> bool {
+        match find_model_of_size(vars, matrix, domain_size, None, &ProverConfig::default()).unwrap()
+        {
+            Some(model) => {
+                assert_eq!(model.domain_size, domain_size);
+                for values in TupleIterator::new(0..domain_size, vars.len()) {
+                    let env = vars.iter().copied().zip(values).collect();
+                    assert!(eval(&model, matrix, &env));
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    #[test]
+    fn smallest_model_size() {
+        let x = NameAllocator::default().alloc();
+        let f = NameAllocator::default().alloc();
+        let p = NameAllocator::default().alloc();
+        // Forall x. P(x) <-> not P(f(x))
+        let matrix = Formula::And(
+            Box::new(Formula::Or(
+                Box::new(Formula::Not(Box::new(Formula::Rel(p, vec![Term::Var(x)])))),
+                Box::new(Formula::Not(Box::new(Formula::Rel(
+                    p,
+                    vec![Term::Fun(f, vec![Term::Var(x)])],
+                )))),
+            )),
+            Box::new(Formula::Or(
+                Box::new(Formula::Rel(p, vec![Term::Var(x)])),
+                Box::new(Formula::Rel(p, vec![Term::Fun(f, vec![Term::Var(x)])])),
+            )),
+        );
+        assert!(!has_model_of_size(&[x], &matrix, 1));
+        assert!(has_model_of_size(&[x], &matrix, 2));
+        assert!(has_model_of_size(&[x], &matrix, 3));
+    }
+
+    #[test]
+    fn distinct_constants() {
+        let mut fun_alloc = NameAllocator::default();
+        let (a, b, c) = (fun_alloc.alloc(), fun_alloc.alloc(), fun_alloc.alloc());
+        let r = NameAllocator::default().alloc();
+        let rel = |s, t| Formula::Rel(r, vec![Term::Fun(s, vec![]), Term::Fun(t, vec![])]);
+        let not_rel = |s, t| Formula::Not(Box::new(rel(s, t)));
+        // R is reflexive on a, b, c and does not relate any two of them, so they are distinct
+        let matrix = [not_rel(a, b), not_rel(b, c), rel(b, b), not_rel(a, c)]
+            .iter()
+            .cloned()
+            .fold(rel(a, a), |x, y| Formula::And(Box::new(x), Box::new(y)));
+        let matrix = Formula::And(Box::new(matrix), Box::new(rel(c, c)));
+        assert!(!has_model_of_size(&[], &matrix, 2));
+        assert!(has_model_of_size(&[], &matrix, 3));
+    }
+
+    #[test]
+    fn equality_is_identity() {
+        let mut var_alloc = NameAllocator::default();
+        let (x, y) = (var_alloc.alloc(), var_alloc.alloc());
+        let mut fun_alloc = NameAllocator::default();
+        let (c, d) = (fun_alloc.alloc(), fun_alloc.alloc());
+        let eq = NameAllocator::default().alloc();
+        // c != d and Forall x, y. x = y or x = c or y = c: exactly two elements
+        let equal = |s, t| Box::new(Formula::Rel(eq, vec![s, t]));
+        let matrix = Formula::And(
+            Box::new(Formula::Not(equal(
+                Term::Fun(c, vec![]),
+                Term::Fun(d, vec![]),
+            ))),
+            Box::new(Formula::Or(
+                equal(Term::Var(x), Term::Var(y)),
+                Box::new(Formula::Or(
+                    equal(Term::Var(x), Term::Fun(c, vec![])),
+                    equal(Term::Var(y), Term::Fun(c, vec![])),
+                )),
+            )),
+        );
+        for domain_size in 1..5 {
+            let model = find_model_of_size(
+                &[x, y],
+                &matrix,
+                domain_size,
+                Some(eq),
+                &ProverConfig::default(),
+            )
+            .unwrap();
+            assert_eq!(model.is_some(), domain_size == 2);
+        }
+    }
+}
@@ -44,14 +44,11 @@ fn into_cnf(formula: Formula) -> CNFFormula {
         Formula::NotVar(var) => vec![vec![Literal::Neg(var)].into_iter().collect()]
             .into_iter()
             .collect(),
-        Formula::And(a, b) => into_cnf(*a)
-            .into_iter()
-            .chain(into_cnf(*b).into_iter())
-            .collect(),
+        Formula::And(a, b) => into_cnf(*a).into_iter().chain(into_cnf(*b)).collect(),
         Formula::Or(a, b) => into_cnf(*a)
             .iter()
             .cartesian_product(into_cnf(*b).iter())
-            .map(|(a, b)| a.into_iter().chain(b.into_iter()).cloned().collect())
+            .map(|(a, b)| a.iter().chain(b).cloned().collect())
             .collect(),
     }
 }
@@ -1,195 +1,1016 @@
-use std::collections::{BTreeSet, HashMap, HashSet};
-
-use crate::pl::{clause_is_tautology, CNFClause, CNFFormula, Literal, Var};
-
-pub(crate) fn is_satisfiable(formula: CNFFormula) -> bool {
-    let formula: HashMap<_, _> = formula
-        .into_iter()
-        .filter(|clause| !clause_is_tautology(clause))
-        .enumerate()
-        .collect();
-
-    #[derive(Default, Clone, Debug)]
-    struct VarOccurs {
-        pos: HashSet<usize>, // clause idx
-        neg: HashSet<usize>, // clause idx
-    }
-
-    #[derive(Debug, Clone)]
-    struct Solver {
-        clauses: HashMap<usize, CNFClause>,
-        var_occurs: HashMap<Var, VarOccurs>,
-        var_rank: BTreeSet<(usize, Var)>,
-        len_to_clause: HashMap<usize, HashSet<usize>>, // clause len => clause idx
-        pure_vars: Vec<Var>,
-    }
-
-    impl Solver {
-        fn delete_clause(&mut self, clause_idx: usize, deleted_var: Var) {
-            let clause = self.clauses.remove(&clause_idx).unwrap();
-            {
-                let set = self.len_to_clause.get_mut(&clause.len()).unwrap();
-                assert!(set.remove(&clause_idx));
-                if set.is_empty() {
-                    self.len_to_clause.remove(&clause.len());
-                }
-            }
-            for literal in clause {
-                match literal {
-                    Literal::Pos(var) => {
-                        if var != deleted_var {
-                            let var_occurs = self.var_occurs.get_mut(&var).unwrap();
-                            assert!(self
-                                .var_rank
-                                .remove(&(var_occurs.pos.len() + var_occurs.neg.len(), var)));
-                            assert!(var_occurs.pos.remove(&clause_idx));
-                            self.var_rank
-                                .insert((var_occurs.pos.len() + var_occurs.neg.len(), var));
-                            if var_occurs.pos.is_empty() && !var_occurs.neg.is_empty() {
-                                self.pure_vars.push(var);
-                            }
-                        }
-                    }
-                    Literal::Neg(var) => {
-                        if var != deleted_var {
-                            let var_occurs = self.var_occurs.get_mut(&var).unwrap();
-                            assert!(self
-                                .var_rank
-                                .remove(&(var_occurs.pos.len() + var_occurs.neg.len(), var)));
-                            assert!(var_occurs.neg.remove(&clause_idx));
-                            self.var_rank
-                                .insert((var_occurs.pos.len() + var_occurs.neg.len(), var));
-                            if var_occurs.neg.is_empty() && !var_occurs.pos.is_empty() {
-                                self.pure_vars.push(var);
-                            }
-                        }
-                    }
+use std::{collections::HashMap, mem, ops, time::Instant};
+
+use crate::{
+    pl::{CNFClause, CNFFormula, Literal, Var, VarAllocator},
+    prover::{ProverConfig, UnknownReason},
+};
+
+/// Returns a satisfying assignment of `formula` if there is one, within the conflict and time
+/// limits of `config`. Variables that do not occur in the assignment can take any value.
+pub(crate) fn solve(
+    formula: CNFFormula,
+    config: &ProverConfig,
+) -> Result<Option<HashMap<Var, bool>>, UnknownReason> {
+    let mut solver = Solver::new();
+    solver.set_limits(config.max_conflicts, config.deadline);
+    for clause in formula {
+        solver.add_clause(&clause);
+    }
+    if solver.solve_limited(&[])? {
+        Ok(Some(solver.model()))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Returns a refutation of `formula` if it is unsatisfiable: a sequence of clauses, each following
+/// from `formula` and the previous ones by reverse unit propagation, ending with the empty clause.
+pub(crate) fn refute(formula: CNFFormula) -> Option<Vec<CNFClause>> {
+    let mut solver = Solver::with_proof();
+    for clause in formula {
+        solver.add_clause(&clause);
+    }
+    if solver.solve() {
+        None
+    } else {
+        solver.proof
+    }
+}
+
+/// Returns either a satisfying assignment of `clauses` or an unsatisfiable core: the indices of a
+/// subset of `clauses` that is unsatisfiable on its own. The core is not necessarily minimal.
+/// `var_alloc` must not have allocated any variable of `clauses` yet.
+pub(crate) fn solve_with_core(
+    clauses: &[CNFClause],
+    var_alloc: &mut VarAllocator,
+) -> Result<HashMap<Var, bool>, Vec<usize>> {
+    // The clause `clause[idx]` is only enabled when its selector `selectors[idx]` is assumed
+    let mut solver = Solver::new();
+    let mut selectors = HashMap::new();
+    for (idx, clause) in clauses.iter().enumerate() {
+        let selector = var_alloc.alloc();
+        let mut clause = clause.clone();
+        clause.insert(Literal::Neg(selector));
+        solver.add_clause(&clause);
+        selectors.insert(selector, idx);
+    }
+    let assumptions = selectors
+        .keys()
+        .map(|selector| Literal::Pos(*selector))
+        .collect::<Vec<_>>();
+    if solver.solve_with_assumptions(&assumptions) {
+        let mut model = solver.model();
+        model.retain(|var, _| !selectors.contains_key(var));
+        Ok(model)
+    } else {
+        let mut core = solver
+            .failed_assumptions()
+            .into_iter()
+            .map(|literal| match literal {
+                Literal::Pos(selector) => selectors[&selector],
+                Literal::Neg(_) => unreachable!("selectors are assumed positively"),
+            })
+            .collect::<Vec<_>>();
+        core.sort_unstable();
+        Err(core)
+    }
+}
+
+/// Literal over the solver's internal variable numbering: `var << 1 | negated`.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+struct Lit(u32);
+
+impl Lit {
+    fn new(var: usize, negated: bool) -> Self {
+        Lit((var as u32) << 1 | negated as u32)
+    }
+
+    fn var(self) -> usize {
+        (self.0 >> 1) as usize
+    }
+
+    fn is_negated(self) -> bool {
+        self.0 & 1 == 1
+    }
+
+    fn idx(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl ops::Not for Lit {
+    type Output = Lit;
+
+    fn not(self) -> Lit {
+        Lit(self.0 ^ 1)
+    }
+}
+
+struct Clause {
+    /// `lits[0]` and `lits[1]` are watched; for a reason clause `lits[0]` is the implied literal
+    lits: Vec<Lit>,
+    learnt: bool,
+    deleted: bool,
+    activity: f64,
+}
+
+/// Binary max-heap of variables ordered by activity.
+#[derive(Default)]
+struct VarOrder {
+    heap: Vec<usize>,
+    positions: Vec<Option<usize>>, // var => idx in heap
+}
+
+impl VarOrder {
+    fn contains(&self, var: usize) -> bool {
+        self.positions[var].is_some()
+    }
+
+    fn swap(&mut self, i: usize, j: usize) {
+        self.heap.swap(i, j);
+        self.positions[self.heap[i]] = Some(i);
+        self.positions[self.heap[j]] = Some(j);
+    }
+
+    fn sift_up(&mut self, mut i: usize, activity: &[f64]) {
+        while i > 0 {
+            let parent = (i - 1) / 2;
+            if activity[self.heap[parent]] >= activity[self.heap[i]] {
+                break;
+            }
+            self.swap(i, parent);
+            i = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut i: usize, activity: &[f64]) {
+        loop {
+            let mut largest = i;
+            for child in [2 * i + 1, 2 * i + 2] {
+                if child < self.heap.len()
+                    && activity[self.heap[child]] > activity[self.heap[largest]]
+                {
+                    largest = child;
                 }
             }
+            if largest == i {
+                break;
+            }
+            self.swap(i, largest);
+            i = largest;
+        }
+    }
+
+    fn insert(&mut self, var: usize, activity: &[f64]) {
+        if self.positions.len() <= var {
+            self.positions.resize(var + 1, None);
+        }
+        if self.contains(var) {
+            return;
+        }
+        self.heap.push(var);
+        self.positions[var] = Some(self.heap.len() - 1);
+        self.sift_up(self.heap.len() - 1, activity);
+    }
+
+    fn increased(&mut self, var: usize, activity: &[f64]) {
+        if let Some(i) = self.positions[var] {
+            self.sift_up(i, activity);
+        }
+    }
+
+    fn pop(&mut self, activity: &[f64]) -> Option<usize> {
+        if self.heap.is_empty() {
+            return None;
+        }
+        let last = self.heap.len() - 1;
+        self.swap(0, last);
+        let var = self.heap.pop().unwrap();
+        self.positions[var] = None;
+        self.sift_down(0, activity);
+        Some(var)
+    }
+}
+
+/// The i-th element (from 0) of the Luby sequence 1, 1, 2, 1, 1, 2, 4, ...
+fn luby(mut i: u64) -> u64 {
+    let mut size = 1;
+    let mut seq = 0;
+    while size < i + 1 {
+        seq += 1;
+        size = 2 * size + 1;
+    }
+    while size - 1 != i {
+        size = (size - 1) >> 1;
+        seq -= 1;
+        i %= size;
+    }
+    1 << seq
+}
+
+const VAR_DECAY: f64 = 0.95;
+const CLAUSE_DECAY: f64 = 0.999;
+const RESTART_BASE: u64 = 100;
+/// The deadline is checked at every conflict and whenever this many decisions and propagated
+/// literals have accumulated since the last check, over all the calls.
+const DEADLINE_CHECK_INTERVAL: u64 = 1_000;
+
+/// Conflict-driven clause learning solver with two watched literals, first-UIP learning, EVSIDS
+/// decisions with phase saving, Luby restarts and activity-based learned clause deletion.
+///
+/// The solver is incremental: clauses can be added between calls to `solve()`, and learned
+/// clauses, variable activities and saved phases are kept.
+pub(crate) struct Solver {
+    var_ids: HashMap<Var, usize>,
+    vars: Vec<Var>,
+    clauses: Vec<Clause>,
+    watches: Vec<Vec<usize>>, // lit idx => idx of clauses watching the lit
+    values: Vec<Option<bool>>,
+    levels: Vec<usize>,
+    reasons: Vec<Option<usize>>,
+    trail: Vec<Lit>,
+    trail_lims: Vec<usize>,
+    propagated: usize,
+    activity: Vec<f64>,
+    var_inc: f64,
+    clause_inc: f64,
+    order: VarOrder,
+    phases: Vec<bool>,
+    seen: Vec<bool>,
+    problem_clauses: usize,
+    learnts: usize,
+    max_learnts: f64,
+    /// The assumptions responsible for the last unsatisfiable answer
+    failed_assumptions: Vec<Lit>,
+    /// False once the empty clause has been derived
+    ok: bool,
+    proof: Option<Vec<CNFClause>>,
+    /// The number of conflicts after which a call to `solve_limited()` gives up
+    max_conflicts: Option<u64>,
+    deadline: Option<Instant>,
+    /// The decisions and propagated literals since the deadline was last checked
+    work_since_deadline_check: u64,
+    /// Over all the calls so far
+    pub(crate) conflicts: u64,
+    pub(crate) decisions: u64,
+}
+
+impl Solver {
+    pub(crate) fn new() -> Self {
+        Self::with_proof_option(false)
+    }
+
+    /// Creates a solver recording learned clauses as a refutation in `proof`.
+    fn with_proof() -> Self {
+        Self::with_proof_option(true)
+    }
+
+    fn with_proof_option(produce_proof: bool) -> Self {
+        Self {
+            var_ids: HashMap::new(),
+            vars: Vec::new(),
+            clauses: Vec::new(),
+            watches: Vec::new(),
+            values: Vec::new(),
+            levels: Vec::new(),
+            reasons: Vec::new(),
+            trail: Vec::new(),
+            trail_lims: Vec::new(),
+            propagated: 0,
+            activity: Vec::new(),
+            var_inc: 1.0,
+            clause_inc: 1.0,
+            order: VarOrder::default(),
+            phases: Vec::new(),
+            seen: Vec::new(),
+            problem_clauses: 0,
+            learnts: 0,
+            max_learnts: 0.0,
+            failed_assumptions: Vec::new(),
+            ok: true,
+            proof: if produce_proof {
+                Some(Vec::new())
+            } else {
+                None
+            },
+            max_conflicts: None,
+            deadline: None,
+            work_since_deadline_check: 0,
+            conflicts: 0,
+            decisions: 0,
+        }
+    }
+
+    /// Sets the limits after which `solve_limited()` gives up.
+    pub(crate) fn set_limits(&mut self, max_conflicts: Option<u64>, deadline: Option<Instant>) {
+        self.max_conflicts = max_conflicts;
+        self.deadline = deadline;
+    }
+
+    fn var_id(&mut self, var: Var) -> usize {
+        if let Some(id) = self.var_ids.get(&var) {
+            return *id;
+        }
+        let id = self.vars.len();
+        self.var_ids.insert(var, id);
+        self.vars.push(var);
+        self.watches.push(Vec::new());
+        self.watches.push(Vec::new());
+        self.values.push(None);
+        self.levels.push(0);
+        self.reasons.push(None);
+        self.activity.push(0.0);
+        self.phases.push(false);
+        self.seen.push(false);
+        self.order.insert(id, &self.activity);
+        id
+    }
+
+    fn lit(&mut self, literal: Literal) -> Lit {
+        match literal {
+            Literal::Pos(var) => Lit::new(self.var_id(var), false),
+            Literal::Neg(var) => Lit::new(self.var_id(var), true),
+        }
+    }
+
+    fn literal(&self, lit: Lit) -> Literal {
+        let var = self.vars[lit.var()];
+        if lit.is_negated() {
+            Literal::Neg(var)
+        } else {
+            Literal::Pos(var)
+        }
+    }
+
+    fn value(&self, lit: Lit) -> Option<bool> {
+        self.values[lit.var()].map(|value| value != lit.is_negated())
+    }
+
+    fn decision_level(&self) -> usize {
+        self.trail_lims.len()
+    }
+
+    fn record_lemma(&mut self, lits: &[Lit]) {
+        if self.proof.is_none() {
+            return;
+        }
+        let lemma = lits.iter().map(|lit| self.literal(*lit)).collect();
+        if let Some(proof) = &mut self.proof {
+            proof.push(lemma);
         }
+    }
+
+    fn enqueue(&mut self, lit: Lit, reason: Option<usize>) {
+        let var = lit.var();
+        self.values[var] = Some(!lit.is_negated());
+        self.levels[var] = self.decision_level();
+        self.reasons[var] = reason;
+        self.trail.push(lit);
+    }
+
+    fn attach(&mut self, clause: Clause) -> usize {
+        let idx = self.clauses.len();
+        self.watches[clause.lits[0].idx()].push(idx);
+        self.watches[clause.lits[1].idx()].push(idx);
+        self.clauses.push(clause);
+        idx
+    }
 
-        fn substitute_var(&mut self, var: Var, value: bool) {
-            let VarOccurs { pos, neg } = self.var_occurs.remove(&var).unwrap();
-            assert!(self.var_rank.remove(&(pos.len() + neg.len(), var)));
-            let (to_delete, to_simplify) = if value { (pos, neg) } else { (neg, pos) };
-            for clause_idx in to_delete {
-                self.delete_clause(clause_idx, var);
+    pub(crate) fn add_clause(&mut self, clause: &CNFClause) {
+        if !self.ok {
+            return;
+        }
+        self.cancel_until(0);
+        let mut lits = Vec::with_capacity(clause.len());
+        for literal in clause {
+            let lit = self.lit(*literal);
+            if lits.contains(&!lit) || self.value(lit) == Some(true) {
+                return; // The clause is satisfied
             }
-            for clause_idx in to_simplify {
-                let literal_to_delete = if value {
-                    Literal::Neg(var)
-                } else {
-                    Literal::Pos(var)
-                };
-                let clause = self.clauses.get_mut(&clause_idx).unwrap();
-                assert!(clause.remove(&literal_to_delete));
-                {
-                    let old_clause_len = clause.len() + 1;
-                    let set = self.len_to_clause.get_mut(&old_clause_len).unwrap();
-                    assert!(set.remove(&clause_idx));
-                    if set.is_empty() {
-                        self.len_to_clause.remove(&old_clause_len);
-                    }
+            if self.value(lit).is_none() {
+                lits.push(lit);
+            }
+        }
+        match lits.len() {
+            0 => {
+                self.ok = false;
+                self.record_lemma(&[]);
+            }
+            1 => {
+                self.enqueue(lits[0], None);
+                if self.propagate().is_some() {
+                    self.ok = false;
+                    self.record_lemma(&[]);
+                }
+            }
+            _ => {
+                self.attach(Clause {
+                    lits,
+                    learnt: false,
+                    deleted: false,
+                    activity: 0.0,
+                });
+                self.problem_clauses += 1;
+            }
+        }
+    }
+
+    /// Returns the idx of a conflicting clause, if any.
+    fn propagate(&mut self) -> Option<usize> {
+        while self.propagated < self.trail.len() {
+            let false_lit = !self.trail[self.propagated];
+            self.propagated += 1;
+            let watchers = mem::take(&mut self.watches[false_lit.idx()]);
+            let mut kept = Vec::with_capacity(watchers.len());
+            let mut conflict = None;
+            let mut watchers = watchers.into_iter();
+            for clause_idx in &mut watchers {
+                if self.clauses[clause_idx].deleted {
+                    continue;
+                }
+                let clause = &mut self.clauses[clause_idx].lits;
+                if clause[0] == false_lit {
+                    clause.swap(0, 1);
+                }
+                let first = clause[0];
+                if self.values[first.var()].map(|value| value != first.is_negated()) == Some(true) {
+                    kept.push(clause_idx);
+                    continue;
                 }
-                self.len_to_clause
-                    .entry(clause.len())
-                    .or_insert(HashSet::new())
-                    .insert(clause_idx);
+                let values = &self.values;
+                let replacement = clause.iter().skip(2).position(|lit| {
+                    values[lit.var()].map(|value| value != lit.is_negated()) != Some(false)
+                });
+                if let Some(pos) = replacement {
+                    clause.swap(1, pos + 2);
+                    let new_watch = clause[1];
+                    self.watches[new_watch.idx()].push(clause_idx);
+                    continue;
+                }
+                kept.push(clause_idx);
+                if self.value(first) == Some(false) {
+                    conflict = Some(clause_idx);
+                    break;
+                }
+                self.enqueue(first, Some(clause_idx));
+            }
+            kept.extend(watchers);
+            self.watches[false_lit.idx()] = kept;
+            if conflict.is_some() {
+                return conflict;
             }
         }
+        None
+    }
 
-        fn is_satisfiable(&mut self) -> bool {
-            if self.len_to_clause.contains_key(&0) {
-                return false;
+    fn bump_var(&mut self, var: usize) {
+        self.activity[var] += self.var_inc;
+        if self.activity[var] > 1e100 {
+            for activity in &mut self.activity {
+                *activity *= 1e-100;
             }
-            while !self.pure_vars.is_empty() || self.len_to_clause.contains_key(&1) {
-                while !self.pure_vars.is_empty() {
-                    let pure_var = self.pure_vars.pop().unwrap();
-                    let VarOccurs { pos, neg } = self.var_occurs.remove(&pure_var).unwrap();
-                    assert!(self.var_rank.remove(&(pos.len() + neg.len(), pure_var)));
-                    for &clause_idx in [pos, neg].iter().flatten() {
-                        self.delete_clause(clause_idx, pure_var);
+            self.var_inc *= 1e-100;
+        }
+        self.order.increased(var, &self.activity);
+    }
+
+    fn bump_clause(&mut self, clause_idx: usize) {
+        let clause = &mut self.clauses[clause_idx];
+        clause.activity += self.clause_inc;
+        if clause.activity > 1e20 {
+            for clause in self.clauses.iter_mut().filter(|clause| clause.learnt) {
+                clause.activity *= 1e-20;
+            }
+            self.clause_inc *= 1e-20;
+        }
+    }
+
+    /// Derives the first-UIP clause from the conflict; returns it (asserting literal first, a
+    /// literal of the backjump level second) and the backjump level.
+    fn analyze(&mut self, mut conflict: usize) -> (Vec<Lit>, usize) {
+        let mut learnt = vec![Lit(0)];
+        let mut pending = 0;
+        let mut implied = None;
+        let mut trail_idx = self.trail.len();
+        loop {
+            if self.clauses[conflict].learnt {
+                self.bump_clause(conflict);
+            }
+            let skip = if implied.is_some() { 1 } else { 0 };
+            for i in skip..self.clauses[conflict].lits.len() {
+                let lit = self.clauses[conflict].lits[i];
+                let var = lit.var();
+                if !self.seen[var] && self.levels[var] > 0 {
+                    self.seen[var] = true;
+                    self.bump_var(var);
+                    if self.levels[var] >= self.decision_level() {
+                        pending += 1;
+                    } else {
+                        learnt.push(lit);
                     }
                 }
+            }
+            loop {
+                trail_idx -= 1;
+                if self.seen[self.trail[trail_idx].var()] {
+                    break;
+                }
+            }
+            let lit = self.trail[trail_idx];
+            self.seen[lit.var()] = false;
+            implied = Some(lit);
+            pending -= 1;
+            if pending == 0 {
+                break;
+            }
+            conflict = self.reasons[lit.var()].unwrap();
+        }
+        learnt[0] = !implied.unwrap();
+        for lit in &learnt[1..] {
+            self.seen[lit.var()] = false;
+        }
+        let mut backjump_level = 0;
+        if learnt.len() > 1 {
+            let max_idx = (1..learnt.len())
+                .max_by_key(|i| self.levels[learnt[*i].var()])
+                .unwrap();
+            learnt.swap(1, max_idx);
+            backjump_level = self.levels[learnt[1].var()];
+        }
+        (learnt, backjump_level)
+    }
 
-                if let Some(unit_literal) = self.len_to_clause.get(&1).map(|unit_clauses| {
-                    self.clauses
-                        .get(unit_clauses.iter().next().unwrap())
-                        .unwrap()
-                        .iter()
-                        .next()
-                        .unwrap()
-                        .clone()
-                }) {
-                    match unit_literal {
-                        Literal::Pos(var) => self.substitute_var(var, true),
-                        Literal::Neg(var) => self.substitute_var(var, false),
-                    }
-                    if self.len_to_clause.contains_key(&0) {
-                        return false;
+    fn cancel_until(&mut self, level: usize) {
+        if self.decision_level() <= level {
+            return;
+        }
+        let lim = self.trail_lims[level];
+        for lit in self.trail.drain(lim..).rev() {
+            let var = lit.var();
+            self.values[var] = None;
+            self.reasons[var] = None;
+            self.phases[var] = !lit.is_negated();
+            self.order.insert(var, &self.activity);
+        }
+        self.trail_lims.truncate(level);
+        self.propagated = lim;
+    }
+
+    fn is_locked(&self, clause_idx: usize) -> bool {
+        let first = self.clauses[clause_idx].lits[0];
+        self.value(first) == Some(true) && self.reasons[first.var()] == Some(clause_idx)
+    }
+
+    /// Deletes the less active half of the learned clauses, keeping binary and reason clauses.
+    fn reduce_learnts(&mut self) {
+        let mut learnts = (0..self.clauses.len())
+            .filter(|idx| self.clauses[*idx].learnt && !self.clauses[*idx].deleted)
+            .collect::<Vec<_>>();
+        learnts.sort_by(|a, b| {
+            self.clauses[*a]
+                .activity
+                .partial_cmp(&self.clauses[*b].activity)
+                .unwrap()
+        });
+        for &idx in &learnts[..learnts.len() / 2] {
+            if self.clauses[idx].lits.len() > 2 && !self.is_locked(idx) {
+                self.clauses[idx].deleted = true;
+                self.clauses[idx].lits = Vec::new();
+                self.learnts -= 1;
+            }
+        }
+    }
+
+    fn pick_branch_lit(&mut self) -> Option<Lit> {
+        while let Some(var) = self.order.pop(&self.activity) {
+            if self.values[var].is_none() {
+                return Some(Lit::new(var, !self.phases[var]));
+            }
+        }
+        None
+    }
+
+    /// Returns whether the added clauses are satisfiable.
+    pub(crate) fn solve(&mut self) -> bool {
+        self.solve_with_assumptions(&[])
+    }
+
+    /// Returns whether the added clauses are satisfiable with all `assumptions` true. The
+    /// assumptions hold only during this call.
+    pub(crate) fn solve_with_assumptions(&mut self, assumptions: &[Literal]) -> bool {
+        self.solve_limited(assumptions)
+            .expect("the solver should have no limits")
+    }
+
+    /// Like `solve_with_assumptions()`, but gives up once a limit set by `set_limits()` is
+    /// reached.
+    pub(crate) fn solve_limited(&mut self, assumptions: &[Literal]) -> Result<bool, UnknownReason> {
+        let assumptions = assumptions
+            .iter()
+            .map(|literal| self.lit(*literal))
+            .collect::<Vec<_>>();
+        self.cancel_until(0);
+        self.failed_assumptions.clear();
+        self.search(&assumptions)
+    }
+
+    fn deadline_passed(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Accounts for `work` more decisions and propagated literals, checking the deadline once
+    /// enough have accumulated.
+    fn deadline_passed_after(&mut self, work: u64) -> bool {
+        self.work_since_deadline_check += work;
+        if self.work_since_deadline_check < DEADLINE_CHECK_INTERVAL {
+            return false;
+        }
+        self.work_since_deadline_check = 0;
+        self.deadline_passed()
+    }
+
+    /// Collects in `failed_assumptions` the assumptions that imply `!lit` by unit propagation, and
+    /// `lit` itself.
+    fn analyze_final(&mut self, lit: Lit) {
+        self.failed_assumptions.push(lit);
+        if self.levels[lit.var()] == 0 {
+            return;
+        }
+        self.seen[lit.var()] = true;
+        for trail_idx in (self.trail_lims[0]..self.trail.len()).rev() {
+            let var = self.trail[trail_idx].var();
+            if !self.seen[var] {
+                continue;
+            }
+            match self.reasons[var] {
+                // Decisions below the assumption levels are assumptions
+                None => self.failed_assumptions.push(self.trail[trail_idx]),
+                Some(reason) => {
+                    for lit in &self.clauses[reason].lits[1..] {
+                        if self.levels[lit.var()] > 0 {
+                            self.seen[lit.var()] = true;
+                        }
                     }
                 }
             }
+            self.seen[var] = false;
+        }
+        self.seen[lit.var()] = false;
+    }
 
-            if self.len_to_clause.is_empty() {
-                return true;
+    fn search(&mut self, assumptions: &[Lit]) -> Result<bool, UnknownReason> {
+        if !self.ok {
+            return Ok(false);
+        }
+        self.max_learnts = self
+            .max_learnts
+            .max(self.problem_clauses as f64 / 3.0)
+            .max(1_000.0);
+        let mut restarts = 0;
+        let mut conflicts_to_restart = RESTART_BASE * luby(restarts);
+        let mut conflicts = 0;
+        loop {
+            let propagated = self.propagated;
+            let conflict = self.propagate();
+            if self.deadline_passed_after(self.propagated.saturating_sub(propagated) as u64) {
+                return Err(UnknownReason::Timeout);
             }
-            // Now we have to make a guess
-            let mut self_clone = self.clone();
-            assert_eq!(self.var_occurs.len(), self.var_rank.len());
-            let some_var = self.var_rank.iter().next_back().unwrap().1;
-            // let some_var = *self.var_occurs.iter().next().unwrap().0;
-            self_clone.substitute_var(some_var, true);
-            if self_clone.is_satisfiable() {
-                return true;
+            if let Some(conflict) = conflict {
+                if self.decision_level() == 0 {
+                    self.ok = false;
+                    self.record_lemma(&[]);
+                    return Ok(false);
+                }
+                conflicts += 1;
+                self.conflicts += 1;
+                if self.max_conflicts.is_some_and(|max| conflicts > max) {
+                    return Err(UnknownReason::ConflictBudgetExhausted);
+                }
+                if self.deadline_passed() {
+                    return Err(UnknownReason::Timeout);
+                }
+                let (learnt, backjump_level) = self.analyze(conflict);
+                self.cancel_until(backjump_level);
+                self.record_lemma(&learnt);
+                if learnt.len() == 1 {
+                    self.enqueue(learnt[0], None);
+                } else {
+                    let asserting = learnt[0];
+                    let clause_idx = self.attach(Clause {
+                        lits: learnt,
+                        learnt: true,
+                        deleted: false,
+                        activity: 0.0,
+                    });
+                    self.bump_clause(clause_idx);
+                    self.learnts += 1;
+                    self.enqueue(asserting, Some(clause_idx));
+                }
+                self.var_inc /= VAR_DECAY;
+                self.clause_inc /= CLAUSE_DECAY;
+                conflicts_to_restart = conflicts_to_restart.saturating_sub(1);
+            } else {
+                if conflicts_to_restart == 0 {
+                    self.cancel_until(0);
+                    restarts += 1;
+                    conflicts_to_restart = RESTART_BASE * luby(restarts);
+                    self.max_learnts *= 1.1;
+                }
+                if self.learnts as f64 >= self.max_learnts {
+                    self.reduce_learnts();
+                }
+                // Assumptions are the first decisions
+                let mut decision = None;
+                while self.decision_level() < assumptions.len() {
+                    let lit = assumptions[self.decision_level()];
+                    match self.value(lit) {
+                        Some(true) => self.trail_lims.push(self.trail.len()),
+                        Some(false) => {
+                            self.analyze_final(lit);
+                            return Ok(false);
+                        }
+                        None => {
+                            decision = Some(lit);
+                            break;
+                        }
+                    }
+                }
+                let decision = match decision.or_else(|| self.pick_branch_lit()) {
+                    Some(lit) => lit,
+                    None => return Ok(true),
+                };
+                self.decisions += 1;
+                if self.deadline_passed_after(1) {
+                    return Err(UnknownReason::Timeout);
+                }
+                self.trail_lims.push(self.trail.len());
+                self.enqueue(decision, None);
             }
-            self.substitute_var(some_var, false);
-            self.is_satisfiable()
         }
     }
 
-    let mut var_occurs = HashMap::new();
-    let mut len_to_clause = HashMap::new();
-    for (&idx, clause) in formula.iter() {
-        for literal in clause {
-            match literal {
-                Literal::Pos(var) => {
-                    var_occurs
-                        .entry(*var)
-                        .or_insert(VarOccurs::default())
-                        .pos
-                        .insert(idx);
+    /// A subset of the assumptions of the last call to `solve_with_assumptions()` that returned
+    /// false which is unsatisfiable together with the clauses. Empty if the clauses alone are
+    /// unsatisfiable.
+    pub(crate) fn failed_assumptions(&self) -> Vec<Literal> {
+        self.failed_assumptions
+            .iter()
+            .map(|lit| self.literal(*lit))
+            .collect()
+    }
+
+    /// The satisfying assignment found by the last call to `solve()` that returned true; valid
+    /// until the next clause is added.
+    pub(crate) fn model(&self) -> HashMap<Var, bool> {
+        self.vars
+            .iter()
+            .zip(&self.values)
+            .map(|(var, value)| (*var, value.unwrap_or(false)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{
+        fol::NameAllocator,
+        pl::{CNFFormula, Literal},
+        proof_checker::check_refutation,
+        prover::{ProverConfig, UnknownReason},
+    };
+
+    use super::{luby, refute, solve, solve_with_core, Solver};
+
+    /// Checks whether `formula` is satisfiable, without limits.
+    fn is_satisfiable(formula: CNFFormula) -> bool {
+        let mut solver = Solver::new();
+        for clause in formula {
+            solver.add_clause(&clause);
+        }
+        solver.solve()
+    }
+
+    /// Pigeonhole principle: `pigeons` pigeons in `holes` holes, each hole holding at most one.
+    fn pigeonhole(pigeons: usize, holes: usize) -> CNFFormula {
+        let mut var_alloc = NameAllocator::default();
+        let vars = (0..pigeons)
+            .map(|_| (0..holes).map(|_| var_alloc.alloc()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let mut cnf = CNFFormula::new();
+        for pigeon in &vars {
+            cnf.insert(pigeon.iter().map(|var| Literal::Pos(*var)).collect());
+        }
+        for (a, pigeon) in vars.iter().enumerate() {
+            for other in &vars[a + 1..] {
+                for (x, y) in pigeon.iter().zip(other) {
+                    cnf.insert(
+                        vec![Literal::Neg(*x), Literal::Neg(*y)]
+                            .into_iter()
+                            .collect(),
+                    );
+                }
+            }
+        }
+        cnf
+    }
+
+    #[test]
+    fn random_3sat_agrees_with_brute_force() {
+        let mut seed = 12345u64;
+        let mut rand = |bound: usize| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % bound
+        };
+        let mut var_alloc = NameAllocator::default();
+        let vars = (0..10).map(|_| var_alloc.alloc()).collect::<Vec<_>>();
+        for _ in 0..200 {
+            let clauses = 30 + rand(30);
+            let cnf = (0..clauses)
+                .map(|_| {
+                    (0..3)
+                        .map(|_| match rand(2) {
+                            0 => Literal::Pos(vars[rand(vars.len())]),
+                            _ => Literal::Neg(vars[rand(vars.len())]),
+                        })
+                        .collect()
+                })
+                .collect::<CNFFormula>();
+            let brute_force = (0..1u32 << vars.len()).any(|mask| {
+                let value = |var| mask >> vars.iter().position(|v| *v == var).unwrap() & 1 == 1;
+                cnf.iter().all(|clause| {
+                    clause.iter().any(|lit| match lit {
+                        Literal::Pos(var) => value(*var),
+                        Literal::Neg(var) => !value(*var),
+                    })
+                })
+            });
+            assert_eq!(is_satisfiable(cnf.clone()), brute_force);
+            if !brute_force {
+                assert!(check_refutation(&cnf, &refute(cnf.clone()).unwrap()).is_ok());
+            }
+            let clauses = cnf.iter().cloned().collect::<Vec<_>>();
+            let mut selector_alloc = NameAllocator::default();
+            for _ in 0..vars.len() {
+                selector_alloc.alloc();
+            }
+            match solve_with_core(&clauses, &mut selector_alloc) {
+                Ok(model) => {
+                    assert!(brute_force);
+                    for clause in &clauses {
+                        assert!(clause.iter().any(|lit| match lit {
+                            Literal::Pos(var) => model.get(var).copied().unwrap_or(false),
+                            Literal::Neg(var) => !model.get(var).copied().unwrap_or(false),
+                        }));
+                    }
                 }
-                Literal::Neg(var) => {
-                    var_occurs
-                        .entry(*var)
-                        .or_insert(VarOccurs::default())
-                        .neg
-                        .insert(idx);
+                Err(core) => {
+                    assert!(!brute_force);
+                    let core = core.into_iter().map(|idx| clauses[idx].clone()).collect();
+                    assert!(solve(core, &ProverConfig::default()).unwrap().is_none());
                 }
             }
         }
-        len_to_clause
-            .entry(clause.len())
-            .or_insert(HashSet::new())
-            .insert(idx);
     }
 
-    let mut pure_vars = Vec::new();
-    let mut var_rank = BTreeSet::new();
-    for (&var, vo) in &var_occurs {
-        if vo.pos.is_empty() || vo.neg.is_empty() {
-            pure_vars.push(var);
+    #[test]
+    fn limits_interrupt_the_search() {
+        let config = ProverConfig {
+            max_conflicts: Some(10),
+            ..ProverConfig::default()
+        };
+        assert_eq!(
+            solve(pigeonhole(7, 6), &config),
+            Err(UnknownReason::ConflictBudgetExhausted)
+        );
+        let config = ProverConfig {
+            deadline: Some(Instant::now()),
+            ..ProverConfig::default()
+        };
+        assert_eq!(
+            solve(pigeonhole(7, 6), &config),
+            Err(UnknownReason::Timeout)
+        );
+        // Without limits, the same solver can still answer
+        let mut solver = Solver::new();
+        for clause in pigeonhole(7, 6) {
+            solver.add_clause(&clause);
         }
-        var_rank.insert((vo.pos.len() + vo.neg.len(), var));
+        solver.set_limits(Some(10), None);
+        assert!(solver.solve_limited(&[]).is_err());
+        solver.set_limits(None, None);
+        assert!(!solver.solve());
+    }
+
+    #[test]
+    fn stops_soon_after_the_deadline() {
+        let timeout = Duration::from_millis(50);
+        let start = Instant::now();
+        let config = ProverConfig {
+            deadline: Some(start + timeout),
+            ..ProverConfig::default()
+        };
+        assert_eq!(
+            solve(pigeonhole(11, 10), &config),
+            Err(UnknownReason::Timeout)
+        );
+        assert!(start.elapsed() < timeout + Duration::from_millis(200));
     }
 
-    Solver {
-        clauses: formula,
-        var_rank,
-        var_occurs,
-        len_to_clause,
-        pure_vars,
+    #[test]
+    fn luby_sequence() {
+        assert_eq!(
+            (0..15).map(luby).collect::<Vec<_>>(),
+            vec![1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8]
+        );
+    }
+
+    #[test]
+    fn pigeonhole_satisfiability() {
+        for holes in 1..6 {
+            assert!(is_satisfiable(pigeonhole(holes, holes)));
+            assert!(!is_satisfiable(pigeonhole(holes + 1, holes)));
+        }
+    }
+
+    #[test]
+    fn model_satisfies_formula() {
+        let cnf = pigeonhole(6, 6);
+        let model = solve(cnf.clone(), &ProverConfig::default())
+            .unwrap()
+            .unwrap();
+        for clause in cnf {
+            assert!(clause.iter().any(|lit| match lit {
+                Literal::Pos(var) => model[var],
+                Literal::Neg(var) => !model[var],
+            }));
+        }
+    }
+
+    #[test]
+    fn refutation_is_checkable() {
+        let cnf = pigeonhole(6, 5);
+        let refutation = refute(cnf.clone()).unwrap();
+        assert!(check_refutation(&cnf, &refutation).is_ok());
+        assert!(refute(pigeonhole(5, 5)).is_none());
+    }
+
+    #[test]
+    fn incremental_solving() {
+        let mut solver = Solver::new();
+        let mut cnf = CNFFormula::new();
+        // Adding the clauses of 5 pigeons in 4 holes one by one becomes unsatisfiable only at
+        // the last clause
+        let clauses = pigeonhole(5, 4);
+        let last = clauses.len() - 1;
+        for (idx, clause) in clauses.into_iter().enumerate() {
+            solver.add_clause(&clause);
+            cnf.insert(clause);
+            if idx < last {
+                assert!(solver.solve());
+                let model = solver.model();
+                for clause in &cnf {
+                    assert!(clause.iter().any(|lit| match lit {
+                        Literal::Pos(var) => model.get(var).copied().unwrap_or(false),
+                        Literal::Neg(var) => !model.get(var).copied().unwrap_or(false),
+                    }));
+                }
+            }
+        }
+        assert!(!solver.solve());
+    }
+
+    #[test]
+    fn assumptions_are_temporary() {
+        let cnf = pigeonhole(4, 4);
+        let mut solver = Solver::new();
+        for clause in &cnf {
+            solver.add_clause(clause);
+        }
+        // Forbidding every pigeon from the first hole leaves 4 pigeons in 3 holes
+        let first_hole = cnf
+            .iter()
+            .filter(|clause| clause.len() == 4)
+            .map(|clause| match clause.iter().min().unwrap() {
+                Literal::Pos(var) => Literal::Neg(*var),
+                Literal::Neg(_) => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert!(solver.solve_with_assumptions(&first_hole[..1]));
+        assert!(
+            !solver.model()[&match first_hole[0] {
+                Literal::Neg(var) => var,
+                Literal::Pos(_) => unreachable!(),
+            }]
+        );
+        assert!(!solver.solve_with_assumptions(&first_hole));
+        let failed = solver.failed_assumptions();
+        assert!(failed.iter().all(|lit| first_hole.contains(lit)));
+        assert!(!solver.solve_with_assumptions(&failed));
+        assert!(solver.solve());
     }
-    .is_satisfiable()
 }
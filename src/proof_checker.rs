@@ -0,0 +1,425 @@
+use std::collections::HashMap;
+
+use itertools::Itertools;
+
+use crate::{
+    cnf::{clausify, Clause},
+    congruence_closure::{is_consistent, GroundLiteral},
+    fol::{skolemize, Formula, Rel, Term},
+    pl::{self, neg, CNFClause, CNFFormula, Literal},
+    prover::{remove_universal_prefix, Proof},
+    resolution::equality_axioms,
+    substitution::Substitution,
+};
+
+fn substitute(term: &Term, interp: &Substitution) -> Result<Term, String> {
+    match term {
+        Term::Var(var) => interp
+            .get(*var)
+            .cloned()
+            .ok_or_else(|| format!("variable {:?} is not instantiated", var)),
+        Term::Fun(fun, args) => Ok(Term::Fun(
+            *fun,
+            args.iter()
+                .map(|arg| substitute(arg, interp))
+                .collect::<Result<_, _>>()?,
+        )),
+    }
+}
+
+fn is_ground(term: &Term) -> bool {
+    match term {
+        Term::Var(_) => false,
+        Term::Fun(_, args) => args.iter().all(is_ground),
+    }
+}
+
+#[derive(Default)]
+struct Grounder {
+    atoms: HashMap<(Rel, Vec<Term>), pl::Var>,
+    var_alloc: pl::VarAllocator,
+}
+
+impl Grounder {
+    fn atom(&mut self, rel: Rel, terms: &[Term], interp: &Substitution) -> Result<pl::Var, String> {
+        let terms = terms
+            .iter()
+            .map(|term| substitute(term, interp))
+            .collect::<Result<_, _>>()?;
+        let alloc = &mut self.var_alloc;
+        Ok(*self
+            .atoms
+            .entry((rel, terms))
+            .or_insert_with(|| alloc.alloc()))
+    }
+
+    fn ground(&mut self, clause: &Clause, interp: &Substitution) -> Result<CNFClause, String> {
+        clause
+            .literals
+            .iter()
+            .map(|literal| {
+                let var = self.atom(literal.rel, &literal.args, interp)?;
+                Ok(if literal.positive {
+                    Literal::Pos(var)
+                } else {
+                    Literal::Neg(var)
+                })
+            })
+            .collect()
+    }
+}
+
+/// Translates each of the instances of `proof` into clauses, and its lemmas into clauses. The
+/// numbering of propositional variables depends only on `proof`, so refutations can refer to it.
+/// Also returns the allocator of the variables.
+pub(crate) fn ground_each_instance(
+    proof: &Proof,
+) -> Result<(Vec<CNFFormula>, CNFFormula, pl::VarAllocator), String> {
+    let (instances, lemmas, grounder) = ground(proof)?;
+    Ok((instances, lemmas, grounder.var_alloc))
+}
+
+/// The ground atoms of the variables of the clauses of `proof`, by the index of the variable.
+pub(crate) fn ground_atoms(proof: &Proof) -> Result<Vec<(Rel, Vec<Term>)>, String> {
+    let (_, _, grounder) = ground(proof)?;
+    Ok(grounder
+        .atoms
+        .into_iter()
+        .sorted_by_key(|(_, var)| var.index())
+        .map(|(atom, _)| atom)
+        .collect())
+}
+
+fn ground(proof: &Proof) -> Result<(Vec<CNFFormula>, CNFFormula, Grounder), String> {
+    let mut grounder = Grounder::default();
+    let mut instances = Vec::new();
+    for (idx, (clause_idx, terms)) in proof.instances.iter().enumerate() {
+        let clause = proof
+            .clauses
+            .get(*clause_idx)
+            .ok_or_else(|| format!("instance {} is of no clause", idx))?;
+        if terms.len() != clause.vars.len() || !terms.iter().all(is_ground) {
+            return Err(format!("instance {} is not a tuple of ground terms", idx));
+        }
+        if clause
+            .vars
+            .iter()
+            .zip(terms)
+            .any(|(var, term)| var.sort() != term.sort())
+        {
+            return Err(format!("instance {} is ill-sorted", idx));
+        }
+        let interp = clause
+            .vars
+            .iter()
+            .copied()
+            .zip(terms.iter().cloned())
+            .collect();
+        let clause = grounder.ground(clause, &interp)?;
+        instances.push(Some(clause).into_iter().collect());
+    }
+    let mut lemmas = CNFFormula::new();
+    for (idx, lemma) in proof.lemmas.iter().enumerate() {
+        let mut clause = CNFClause::new();
+        for literal in lemma {
+            if !literal.args.iter().all(is_ground) {
+                return Err(format!("lemma {} is not ground", idx));
+            }
+            let var = grounder.atom(literal.rel, &literal.args, &Substitution::new())?;
+            clause.insert(if literal.positive {
+                Literal::Pos(var)
+            } else {
+                Literal::Neg(var)
+            });
+        }
+        lemmas.insert(clause);
+    }
+    Ok((instances, lemmas, grounder))
+}
+
+/// Translates the instances and lemmas of `proof` into clauses, numbered as by
+/// `ground_each_instance()`.
+pub(crate) fn ground_instances(proof: &Proof) -> Result<CNFFormula, String> {
+    let (instances, lemmas, _) = ground_each_instance(proof)?;
+    Ok(instances.into_iter().flatten().chain(lemmas).collect())
+}
+
+#[derive(Default)]
+struct RupChecker {
+    clauses: Vec<CNFClause>,
+    occurs: HashMap<Literal, Vec<usize>>, // literal => idx of clauses containing it
+    units: Vec<Literal>,
+    has_empty_clause: bool,
+}
+
+impl RupChecker {
+    fn add(&mut self, clause: CNFClause) {
+        for lit in &clause {
+            self.occurs
+                .entry(*lit)
+                .or_default()
+                .push(self.clauses.len());
+        }
+        match clause.len() {
+            0 => self.has_empty_clause = true,
+            1 => self.units.extend(clause.iter()),
+            _ => {}
+        }
+        self.clauses.push(clause);
+    }
+
+    /// Checks whether falsifying `clause` leads to a conflict by unit propagation.
+    fn is_rup(&self, clause: &CNFClause) -> bool {
+        if self.has_empty_clause {
+            return true;
+        }
+        let mut assignment = HashMap::new();
+        let mut queue = clause
+            .iter()
+            .map(|lit| neg(*lit))
+            .chain(self.units.iter().copied())
+            .collect::<Vec<_>>();
+        let value = |assignment: &HashMap<pl::Var, bool>, lit: &Literal| match lit {
+            Literal::Pos(var) => assignment.get(var).copied(),
+            Literal::Neg(var) => assignment.get(var).map(|value| !value),
+        };
+        while let Some(lit) = queue.pop() {
+            match value(&assignment, &lit) {
+                Some(true) => continue,
+                Some(false) => return true,
+                None => match lit {
+                    Literal::Pos(var) => assignment.insert(var, true),
+                    Literal::Neg(var) => assignment.insert(var, false),
+                },
+            };
+            for &clause_idx in self.occurs.get(&neg(lit)).into_iter().flatten() {
+                let clause = &self.clauses[clause_idx];
+                if clause
+                    .iter()
+                    .any(|lit| value(&assignment, lit) == Some(true))
+                {
+                    continue;
+                }
+                let mut unassigned = clause
+                    .iter()
+                    .filter(|lit| value(&assignment, lit).is_none());
+                match (unassigned.next(), unassigned.next()) {
+                    (None, _) => return true,
+                    (Some(unit), None) => queue.push(*unit),
+                    _ => {}
+                }
+            }
+        }
+        false
+    }
+
+    /// Checks whether `clause` has the resolution asymmetric tautology property on its first
+    /// literal: every resolvent on it is a RUP clause.
+    fn is_rat(&self, clause: &CNFClause) -> bool {
+        let pivot = match clause.iter().next() {
+            Some(pivot) => *pivot,
+            None => return false,
+        };
+        self.occurs
+            .get(&neg(pivot))
+            .into_iter()
+            .flatten()
+            .all(|&clause_idx| {
+                let resolvent = clause
+                    .iter()
+                    .chain(
+                        self.clauses[clause_idx]
+                            .iter()
+                            .filter(|lit| **lit != neg(pivot)),
+                    )
+                    .copied()
+                    .collect();
+                self.is_rup(&resolvent)
+            })
+    }
+}
+
+/// Verifies that `refutation` is a DRAT refutation (without deletions) of `cnf`.
+pub(crate) fn check_refutation(cnf: &CNFFormula, refutation: &[CNFClause]) -> Result<(), String> {
+    let mut checker = RupChecker::default();
+    for clause in cnf {
+        checker.add(clause.clone());
+    }
+    for (idx, lemma) in refutation.iter().enumerate() {
+        if !checker.is_rup(lemma) && !checker.is_rat(lemma) {
+            return Err(format!("lemma {} is neither RUP nor RAT", idx));
+        }
+        checker.add(lemma.clone());
+    }
+    if refutation.iter().any(|lemma| lemma.is_empty()) {
+        Ok(())
+    } else {
+        Err("the refutation does not derive the empty clause".to_string())
+    }
+}
+
+/// Verifies that the clauses of `proof` are the clausal form of the skolemized negation of its
+/// formula, derived again from it, possibly followed by the equality axioms.
+fn check_clauses(proof: &Proof) -> Result<(), String> {
+    let formula = skolemize(
+        Formula::Not(Box::new(proof.formula.clone())),
+        &mut proof.fun_alloc.clone(),
+    );
+    let (_, matrix) = remove_universal_prefix(formula);
+    let clauses = clausify(&matrix, &mut proof.rel_alloc.clone());
+    if !proof.clauses.starts_with(&clauses) {
+        return Err("the clauses are not the clausal form of the formula".to_string());
+    }
+    let axioms = &proof.clauses[clauses.len()..];
+    if axioms.is_empty() {
+        return Ok(());
+    }
+    let literals = clauses
+        .into_iter()
+        .map(|clause| clause.literals)
+        .collect::<Vec<_>>();
+    let expected = match proof.equality {
+        Some(equality) => equality_axioms(equality, &literals),
+        None => vec![],
+    };
+    // The axioms come in no particular order
+    if axioms.len() != expected.len()
+        || !axioms
+            .iter()
+            .all(|axiom| expected.contains(&axiom.literals))
+    {
+        return Err("the clauses beyond the clausal form are not the equality axioms".to_string());
+    }
+    Ok(())
+}
+
+/// Verifies that the clauses of `proof` are those of its formula, that its lemmas are valid in
+/// the theory of equality and that `refutation` refutes the clauses of the instances and lemmas
+/// recorded in `proof`.
+pub(crate) fn check_proof(proof: &Proof, refutation: &[CNFClause]) -> Result<(), String> {
+    check_clauses(proof)?;
+    for (idx, lemma) in proof.lemmas.iter().enumerate() {
+        let negation = lemma.iter().map(GroundLiteral::negated).collect::<Vec<_>>();
+        match proof.equality {
+            Some(equality) if !is_consistent(&negation, equality) => {}
+            _ => return Err(format!("lemma {} is not valid", idx)),
+        }
+    }
+    let cnf = ground_instances(proof)?;
+    check_refutation(&cnf, refutation)
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+
+    use crate::{
+        cnf::clausify,
+        fol::{builders::forall, skolemize, Formula, NameAllocator, Term},
+        pl::{CNFClause, Literal},
+        prover::{remove_universal_prefix, Proof},
+    };
+
+    use super::check_proof;
+
+    fn proof() -> Proof {
+        let mut var_alloc = NameAllocator::default();
+        let (x, y) = (var_alloc.alloc(), var_alloc.alloc());
+        let mut fun_alloc = NameAllocator::default();
+        let (c, d) = (fun_alloc.alloc(), fun_alloc.alloc());
+        let mut rel_alloc = NameAllocator::default();
+        let r = rel_alloc.alloc();
+        // The negation of (R(x) or R(y)) and (not R(x) or not R(y)) and (R(x) or not R(y))
+        let literal = |positive, var| {
+            let atom = Formula::Rel(r, vec![Term::Var(var)]);
+            if positive {
+                atom
+            } else {
+                Formula::Not(Box::new(atom))
+            }
+        };
+        let clause = |x_positive, y_positive| {
+            forall(
+                x,
+                forall(
+                    y,
+                    Formula::Or(
+                        Box::new(literal(x_positive, x)),
+                        Box::new(literal(y_positive, y)),
+                    ),
+                ),
+            )
+        };
+        let formula = Formula::Not(Box::new(Formula::And(
+            Box::new(clause(true, true)),
+            Box::new(Formula::And(
+                Box::new(clause(false, false)),
+                Box::new(clause(true, false)),
+            )),
+        )));
+        let negation = skolemize(
+            Formula::Not(Box::new(formula.clone())),
+            &mut fun_alloc.clone(),
+        );
+        let clauses = clausify(&remove_universal_prefix(negation).1, &mut rel_alloc.clone());
+        let c = Term::Fun(c, vec![]);
+        let d = Term::Fun(d, vec![]);
+        // Every instance over c and d
+        let instances = clauses
+            .iter()
+            .enumerate()
+            .flat_map(|(clause_idx, clause)| {
+                (0..clause.vars.len())
+                    .map(|_| vec![c.clone(), d.clone()])
+                    .multi_cartesian_product()
+                    .map(move |terms| (clause_idx, terms))
+            })
+            .collect();
+        Proof {
+            formula,
+            fun_alloc,
+            rel_alloc,
+            clauses,
+            instances,
+            equality: None,
+            lemmas: vec![],
+        }
+    }
+
+    #[test]
+    fn accepts_solver_refutation() {
+        let proof = proof();
+        assert!(check_proof(&proof, &proof.refutation()).is_ok());
+        // The variables of the certificate are R(c) and R(d)
+        let certificate = proof.certificate().unwrap();
+        assert_eq!(certificate.atoms.len(), 2);
+        assert!(certificate
+            .clauses
+            .iter()
+            .flatten()
+            .all(|lit| (1..=2).contains(&lit.abs())));
+    }
+
+    #[test]
+    fn rejects_refutation_of_satisfiable_instances() {
+        let mut proof = proof();
+        assert!(check_proof(&proof, &[]).is_err());
+        // The instances of the first clause alone are satisfiable, so no refutation can be correct
+        proof.instances.retain(|(clause_idx, _)| *clause_idx == 0);
+        let empty_clause = CNFClause::new();
+        assert!(check_proof(&proof, std::slice::from_ref(&empty_clause)).is_err());
+        let mut var_alloc = NameAllocator::default();
+        for _ in 0..4 {
+            let lemma = vec![Literal::Pos(var_alloc.alloc())].into_iter().collect();
+            assert!(check_proof(&proof, &[lemma, empty_clause.clone()]).is_err());
+        }
+    }
+
+    #[test]
+    fn rejects_clauses_not_of_the_formula() {
+        let mut proof = proof();
+        let refutation = proof.refutation();
+        proof.clauses[0].literals.pop();
+        assert!(check_proof(&proof, &refutation).is_err());
+    }
+}
@@ -1,14 +1,24 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt, thread,
+    time::Instant,
+};
 
 use crate::{
-    fol::{self, func_sig, skolemize, Fun, NameAllocator, Rel, Term},
-    herbrand_universe::herbrand_universe,
-    pl::{self, into_ecnf},
-    pl_sat_solver::is_satisfiable,
+    cnf::{clausify, Clause, Literal},
+    congruence_closure::{conflict, CongruenceClosure, GroundLiteral},
+    fol::{self, func_sig, skolemize, Fun, NameAllocator, Rel, Sort, Term},
+    herbrand_universe::{herbrand_universe, leveled_instances, LeveledUniverse, TermOrder},
+    interleave::Interleave,
+    model_finder::{find_model_of_size, instance_count, Model},
+    pl,
+    pl_sat_solver::{refute, solve_with_core, Solver},
+    proof_checker::{check_proof, ground_atoms, ground_each_instance, ground_instances},
+    substitution::Substitution,
     tuple_iterator::TupleIterator,
 };
 
-fn remove_universal_prefix(formula: fol::Formula) -> (Vec<fol::Var>, fol::Formula) {
+pub(crate) fn remove_universal_prefix(formula: fol::Formula) -> (Vec<fol::Var>, fol::Formula) {
     match formula {
         fol::Formula::Forall(var, phi) => {
             let (mut vars, formula) = remove_universal_prefix(*phi);
@@ -20,109 +30,564 @@ fn remove_universal_prefix(formula: fol::Formula) -> (Vec<fol::Var>, fol::Formul
 }
 
 #[derive(Default)]
-struct RelToVar {
+pub(crate) struct RelToVar {
     rel_to_var: HashMap<(Rel, Vec<Term>), pl::Var>,
     var_alloc: pl::VarAllocator,
 }
 
 impl RelToVar {
-    fn translate(&mut self, rel: Rel, terms: Vec<Term>) -> pl::Var {
+    pub(crate) fn translate(&mut self, rel: Rel, terms: Vec<Term>) -> pl::Var {
         let alloc = &mut self.var_alloc;
         *self
             .rel_to_var
             .entry((rel, terms))
             .or_insert_with(|| alloc.alloc())
     }
+
+    fn literal(&mut self, literal: &GroundLiteral) -> pl::Literal {
+        let var = self.translate(literal.rel, literal.args.clone());
+        if literal.positive {
+            pl::Literal::Pos(var)
+        } else {
+            pl::Literal::Neg(var)
+        }
+    }
+
+    /// The instance of `clause` in which its variables are `interp`.
+    pub(crate) fn clause(&mut self, clause: &Clause, interp: &Substitution) -> pl::CNFClause {
+        clause
+            .literals
+            .iter()
+            .map(
+                |Literal {
+                     positive,
+                     rel,
+                     args,
+                 }| {
+                    let var =
+                        self.translate(*rel, args.iter().map(|arg| interp.apply(arg)).collect());
+                    if *positive {
+                        pl::Literal::Pos(var)
+                    } else {
+                        pl::Literal::Neg(var)
+                    }
+                },
+            )
+            .collect()
+    }
+
+    /// The ground literals made true by `assignment`, over the atoms it assigns.
+    fn ground_literals(&self, assignment: &HashMap<pl::Var, bool>) -> Vec<GroundLiteral> {
+        self.rel_to_var
+            .iter()
+            .filter_map(|((rel, args), var)| {
+                Some(GroundLiteral {
+                    positive: *assignment.get(var)?,
+                    rel: *rel,
+                    args: args.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Reads the model off a satisfying assignment of the ground instances over a finite Herbrand
+    /// `universe`, i.e. one consisting of constants only. With equality, the elements are the
+    /// classes of equal constants.
+    fn herbrand_model(
+        &self,
+        universe: &[Term],
+        assignment: &HashMap<pl::Var, bool>,
+        equality: Option<Rel>,
+    ) -> Model {
+        let classes = match equality {
+            Some(equality) => {
+                let mut closure = CongruenceClosure::new(equality);
+                for literal in self.ground_literals(assignment) {
+                    closure.assert_literal(&literal);
+                }
+                let nodes = universe
+                    .iter()
+                    .map(|term| closure.term(term))
+                    .collect::<Vec<_>>();
+                nodes.into_iter().map(|node| closure.find(node)).collect()
+            }
+            None => (0..universe.len()).collect::<Vec<_>>(),
+        };
+        let mut class_elems = HashMap::new();
+        for class in &classes {
+            let elem = class_elems.len();
+            class_elems.entry(*class).or_insert(elem);
+        }
+        let elems = universe
+            .iter()
+            .zip(&classes)
+            .map(|(term, class)| (term, class_elems[class]))
+            .collect::<HashMap<_, _>>();
+        Model {
+            domain_size: class_elems.len(),
+            functions: universe
+                .iter()
+                .map(|term| match term {
+                    Term::Fun(fun, args) if args.is_empty() => ((*fun, vec![]), elems[term]),
+                    _ => panic!("expected a constant"),
+                })
+                .collect(),
+            relations: self
+                .rel_to_var
+                .iter()
+                .filter(|((rel, _), var)| {
+                    Some(*rel) != equality && assignment.get(var).copied().unwrap_or(false)
+                })
+                .map(|((rel, terms), _)| (*rel, terms.iter().map(|term| elems[term]).collect()))
+                .collect(),
+        }
+    }
 }
 
-pub(crate) fn is_tautology(formula: fol::Formula, fun_alloc: &mut NameAllocator<Fun>) -> bool {
-    let formula = skolemize(fol::Formula::Not(Box::new(formula)), fun_alloc);
-    let (vars, formula) = remove_universal_prefix(formula);
+/// Solves the ground instances added to `solver` modulo the theory of `equality`: as long as the
+/// propositional model is inconsistent with it, the negation of the inconsistent set of its
+/// literals explaining the conflict, which need not be minimal, is added as a lemma (and recorded
+/// in `lemmas`). Gives up once the deadline of `config` has passed.
+pub(crate) fn solve_modulo_equality(
+    solver: &mut Solver,
+    rel_to_var: &mut RelToVar,
+    equality: Option<Rel>,
+    lemmas: &mut Vec<Vec<GroundLiteral>>,
+    stats: &mut Statistics,
+    config: &ProverConfig,
+) -> Result<bool, UnknownReason> {
+    loop {
+        if config.deadline_passed() {
+            return Err(UnknownReason::Timeout);
+        }
+        stats.sat_calls += 1;
+        let res = solver.solve_limited(&[]);
+        stats.conflicts = solver.conflicts;
+        stats.decisions = solver.decisions;
+        if !res? {
+            return Ok(false);
+        }
+        let equality = match equality {
+            Some(equality) => equality,
+            None => return Ok(true),
+        };
+        let literals = rel_to_var.ground_literals(&solver.model());
+        let lemma = match conflict(&literals, equality) {
+            Some(core) => core.iter().map(GroundLiteral::negated).collect::<Vec<_>>(),
+            None => return Ok(true),
+        };
+        if config.deadline_passed() {
+            return Err(UnknownReason::Timeout);
+        }
+        let clause = lemma
+            .iter()
+            .map(|literal| rel_to_var.literal(literal))
+            .collect();
+        solver.add_clause(&clause);
+        lemmas.push(lemma);
+    }
+}
 
-    fn interp_term(term: &Term, interp: &HashMap<fol::Var, Term>) -> Term {
-        match term {
-            Term::Var(var) => interp.get(&var).unwrap().clone(),
-            Term::Fun(fun, args) => Term::Fun(
-                *fun,
-                args.into_iter()
-                    .map(|term| interp_term(term, interp))
-                    .collect(),
-            ),
+/// Frees `state` on another thread, as freeing the instances of a long search can take long enough
+/// to overrun the deadline.
+pub(crate) fn drop_in_background<T: Send + 'static>(state: T) {
+    thread::spawn(move || drop(state));
+}
+
+/// A domain size is searched for a countermodel only once the Herbrand prefix has this many times
+/// more instances than the grounding over that domain (model finding instances are much larger).
+const MODEL_FINDER_INSTANCE_RATIO: usize = 64;
+
+/// Limits on the search, after which the engines give up with `Verdict::Unknown`. There is no
+/// limit by default.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub struct ProverConfig {
+    /// The wall-clock time at which the engines give up
+    pub deadline: Option<Instant>,
+    /// The number of Herbrand instances the Herbrand engine may generate
+    pub max_instances: Option<usize>,
+    /// The depth of the terms the Herbrand engine may instantiate variables with, constants
+    /// having depth 0
+    pub max_term_depth: Option<usize>,
+    /// The size of the terms the Herbrand engine may instantiate variables with, constants
+    /// having size 1
+    pub max_term_size: Option<usize>,
+    /// The order in which the Herbrand engine instantiates variables. Ordered by depth or size,
+    /// reaching the limit on it means that every instance below the limit was refuted in vain.
+    pub term_order: TermOrder,
+    /// The number of conflicts after which a call to the SAT solver gives up
+    pub max_conflicts: Option<u64>,
+}
+
+impl ProverConfig {
+    pub(crate) fn deadline_passed(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+/// What the Herbrand engine did to reach its verdict.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub struct Statistics {
+    /// The Herbrand instances generated
+    pub instances: usize,
+    /// The calls to the SAT solver on the instances, and its conflicts and decisions
+    pub sat_calls: usize,
+    pub conflicts: u64,
+    pub decisions: u64,
+    /// The largest domain searched for a countermodel, 0 if none was
+    pub model_size: usize,
+    /// The largest level of terms (depth or size, by the term order) whose instances were all
+    /// generated, when they are generated by levels
+    pub complete_level: Option<usize>,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum UnknownReason {
+    /// The deadline passed.
+    Timeout,
+    /// The number of Herbrand instances reached the limit.
+    InstanceBudgetExhausted,
+    /// The next Herbrand instance needs terms deeper than the limit.
+    TermDepthExceeded,
+    /// The next Herbrand instance needs terms larger than the limit.
+    TermSizeExceeded,
+    /// A call to the SAT solver reached the limit of conflicts.
+    ConflictBudgetExhausted,
+    /// The number of clauses kept by the resolution prover exceeded the hard-coded budget.
+    ClauseBudgetExhausted,
+}
+
+impl fmt::Display for UnknownReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UnknownReason::Timeout => write!(f, "timeout"),
+            UnknownReason::InstanceBudgetExhausted => write!(f, "instance budget exhausted"),
+            UnknownReason::TermDepthExceeded => write!(f, "term depth limit exceeded"),
+            UnknownReason::TermSizeExceeded => write!(f, "term size limit exceeded"),
+            UnknownReason::ConflictBudgetExhausted => write!(f, "conflict budget exhausted"),
+            UnknownReason::ClauseBudgetExhausted => write!(f, "clause budget exhausted"),
         }
     }
+}
 
-    fn into_pl_formula(
-        formula: &fol::Formula,
-        interp: &HashMap<fol::Var, Term>,
-        rel_to_var: &mut RelToVar,
-    ) -> pl::Formula {
-        match formula {
-            fol::Formula::True => pl::Formula::True,
-            fol::Formula::False => pl::Formula::False,
-            fol::Formula::Rel(rel, terms) => pl::Formula::Var(
-                rel_to_var.translate(
-                    *rel,
-                    terms
-                        .into_iter()
-                        .map(|term| interp_term(term, interp))
-                        .collect(),
-                ),
-            ),
-            fol::Formula::Not(phi) => match phi.as_ref() {
-                fol::Formula::Rel(rel, terms) => pl::Formula::NotVar(
-                    rel_to_var.translate(
-                        *rel,
-                        terms
-                            .into_iter()
-                            .map(|term| interp_term(term, interp))
-                            .collect(),
-                    ),
-                ),
-                _ => panic!("expected NNF formula"),
-            },
-            fol::Formula::Or(a, b) => pl::Formula::Or(
-                Box::new(into_pl_formula(a, interp, rel_to_var)),
-                Box::new(into_pl_formula(b, interp, rel_to_var)),
-            ),
-            fol::Formula::And(a, b) => pl::Formula::And(
-                Box::new(into_pl_formula(a, interp, rel_to_var)),
-                Box::new(into_pl_formula(b, interp, rel_to_var)),
-            ),
-            _ => panic!("expected NNF formula"),
+/// The Herbrand instances `instances` of the clausal form `clauses` of the skolemized negation of
+/// the valid formula `formula` whose conjunction is unsatisfiable, together with the clauses
+/// `lemmas` valid in the theory of `equality`. An instance is the index of its clause and the
+/// terms of the variables of the clause.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Proof {
+    pub formula: fol::Formula,
+    /// The allocators of the Skolem functions and of the relations of the definitional form, as
+    /// they were before `clauses` were derived from `formula`
+    pub fun_alloc: NameAllocator<Fun>,
+    pub rel_alloc: NameAllocator<Rel>,
+    pub clauses: Vec<Clause>,
+    pub instances: Vec<(usize, Vec<Term>)>,
+    pub equality: Option<Rel>,
+    pub lemmas: Vec<Vec<GroundLiteral>>,
+}
+
+impl Proof {
+    /// Refutation of the clauses of the instances, numbered as by `ground_instances()`.
+    pub(crate) fn refutation(&self) -> Vec<pl::CNFClause> {
+        let cnf = ground_instances(self).unwrap();
+        refute(cnf).expect("the instances should be unsatisfiable")
+    }
+
+    /// The certificate of the proof, once `check_proof()` accepts its refutation.
+    pub fn certificate(&self) -> Result<Certificate, String> {
+        let refutation = self.refutation();
+        check_proof(self, &refutation)?;
+        let mut clauses = ground_instances(self)?.into_iter().collect::<Vec<_>>();
+        clauses.sort();
+        Ok(Certificate {
+            atoms: ground_atoms(self)?,
+            clauses: dimacs(&clauses),
+            refutation: dimacs(&refutation),
+        })
+    }
+
+    /// The proof restricted to the instances whose clauses form an unsatisfiable core, i.e. the
+    /// instances the refutation actually needs.
+    pub fn core(&self) -> Proof {
+        let (cnfs, lemmas, mut var_alloc) = ground_each_instance(self).unwrap();
+        // Lemmas are owned by no instance
+        let (owners, clauses): (Vec<_>, Vec<_>) = cnfs
+            .into_iter()
+            .enumerate()
+            .flat_map(|(instance_idx, cnf)| {
+                cnf.into_iter()
+                    .map(move |clause| (Some(instance_idx), clause))
+            })
+            .chain(lemmas.into_iter().map(|clause| (None, clause)))
+            .unzip();
+        let core = solve_with_core(&clauses, &mut var_alloc)
+            .expect_err("the instances should be unsatisfiable");
+        let used = core
+            .into_iter()
+            .filter_map(|clause_idx| owners[clause_idx])
+            .collect::<BTreeSet<_>>();
+        Proof {
+            formula: self.formula.clone(),
+            fun_alloc: self.fun_alloc.clone(),
+            rel_alloc: self.rel_alloc.clone(),
+            clauses: self.clauses.clone(),
+            instances: used
+                .into_iter()
+                .map(|instance_idx| self.instances[instance_idx].clone())
+                .collect(),
+            equality: self.equality,
+            lemmas: self.lemmas.clone(),
         }
     }
+}
+
+/// A DRAT certificate of a proof, with the literals of its clauses numbered from 1 as in the
+/// DIMACS format: the clauses of the instances and lemmas over the variables of the ground atoms
+/// `atoms`, and their refutation.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Certificate {
+    pub atoms: Vec<(Rel, Vec<Term>)>,
+    pub clauses: Vec<Vec<i64>>,
+    pub refutation: Vec<Vec<i64>>,
+}
+
+fn dimacs(clauses: &[pl::CNFClause]) -> Vec<Vec<i64>> {
+    clauses
+        .iter()
+        .map(|clause| {
+            clause
+                .iter()
+                .map(|literal| match literal {
+                    pl::Literal::Pos(var) => var.index() as i64 + 1,
+                    pl::Literal::Neg(var) => -(var.index() as i64 + 1),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Verdict {
+    /// The formula is valid: a finite set of its Herbrand instances is unsatisfiable. Engines
+    /// other than the Herbrand one give no proof.
+    Valid(Option<Proof>),
+    /// The formula is not valid: the whole (finite) Herbrand expansion is satisfiable or its
+    /// negation has a finite model. The model interprets the original and the Skolem symbols.
+    /// Engines other than the Herbrand one give no model.
+    Invalid(Option<Model>),
+    /// The prover gave up before reaching either conclusion.
+    Unknown(UnknownReason),
+}
+
+/// Decides `formula`, in which `equality` (if any) is interpreted as equality, within the limits
+/// of `config`, recording its work in `stats`. The clauses of the skolemized negation of
+/// `formula` are instantiated each over its own variables, interleaving the clauses.
+pub(crate) fn is_tautology(
+    formula: fol::Formula,
+    fun_alloc: &mut NameAllocator<Fun>,
+    rel_alloc: &mut NameAllocator<Rel>,
+    equality: Option<Rel>,
+    config: &ProverConfig,
+    stats: &mut Statistics,
+) -> Verdict {
+    let input = (formula.clone(), fun_alloc.clone(), rel_alloc.clone());
+    let formula = skolemize(fol::Formula::Not(Box::new(formula)), fun_alloc);
+    let sorts = fol::sorts(&formula);
+    let (vars, formula) = remove_universal_prefix(formula);
+    let clauses = clausify(&formula, rel_alloc);
 
     let mut rel_to_var = RelToVar::default();
-    let mut ecnf_prefix = pl::CNFFormula::new();
+    let mut solver = Solver::new();
+    solver.set_limits(config.max_conflicts, config.deadline);
     let mut prefix_size = 0usize;
     let mut next_prefix_size_to_check = 2usize;
-    let mut last_checked_prefix_size = 2usize;
-    for var_terms in TupleIterator::new(herbrand_universe(func_sig(&formula)), vars.len()) {
-        let pl_formula = into_pl_formula(
-            &formula,
-            &vars.iter().copied().zip(var_terms.into_iter()).collect(),
-            &mut rel_to_var,
-        );
-        ecnf_prefix.extend(
-            into_ecnf(pl_formula, &mut rel_to_var.var_alloc)
-                .into_iter()
-                .filter(|clause| !pl::clause_is_tautology(clause)),
-        );
+    let mut next_domain_size = 1usize;
+    let mut instances = Vec::new();
+    let mut lemmas = Vec::new();
+    macro_rules! valid {
+        () => {
+            Verdict::Valid(Some(Proof {
+                formula: input.0,
+                fun_alloc: input.1,
+                rel_alloc: input.2,
+                clauses,
+                instances,
+                equality,
+                lemmas,
+            }))
+        };
+    }
+    // The interleaved universe has a single sort
+    let (single_sort, order) = match (sorts.len(), config.term_order) {
+        (0, order) => (Some(Sort::default()), order),
+        (1, order) => (sorts.first().copied(), order),
+        (_, TermOrder::Interleaved) => (None, TermOrder::Depth),
+        (_, order) => (None, order),
+    };
+    // Added to the interleaved universe if the formula has no constant
+    let constant = single_sort.map(|sort| fun_alloc.alloc().with_sort(sort));
+    let mut clause_instances: Box<dyn Iterator<Item = (usize, Vec<Term>)> + Send> = match order {
+        TermOrder::Interleaved => Box::new(Interleave::new(
+            clauses
+                .iter()
+                .enumerate()
+                .map(|(clause_idx, clause)| {
+                    let universe = herbrand_universe(func_sig(&formula), constant.unwrap());
+                    let tuples = TupleIterator::new(universe, clause.vars.len());
+                    (tuples.map(move |terms| (clause_idx, terms)), 1)
+                })
+                .collect(),
+        )),
+        order => Box::new(leveled_instances(
+            LeveledUniverse::new(
+                fol::sorted_func_sig(&formula),
+                vars.iter().map(|var| var.sort()),
+                fun_alloc,
+                order,
+                config.deadline,
+            ),
+            clauses
+                .iter()
+                .map(|clause| clause.vars.iter().map(|var| var.sort()).collect())
+                .collect(),
+        )),
+    };
+    macro_rules! unknown {
+        ($reason:expr) => {{
+            drop_in_background((clause_instances, solver, rel_to_var, instances));
+            Verdict::Unknown($reason)
+        }};
+    }
+    while let Some((clause_idx, var_terms)) = clause_instances.next() {
+        if config.deadline_passed() {
+            return unknown!(UnknownReason::Timeout);
+        }
+        if order != TermOrder::Interleaved {
+            // The instances of the lower levels have all been generated
+            let level = var_terms.iter().map(|term| order.level(term)).max();
+            if let Some(complete) = level.and_then(|level| level.checked_sub(1)) {
+                if complete >= order.first_level() {
+                    stats.complete_level = Some(complete);
+                }
+            }
+        }
+        let limit = if config
+            .max_instances
+            .is_some_and(|max| instances.len() >= max)
+        {
+            Some(UnknownReason::InstanceBudgetExhausted)
+        } else if config
+            .max_term_depth
+            .is_some_and(|max| var_terms.iter().any(|term| term.depth() > max))
+        {
+            Some(UnknownReason::TermDepthExceeded)
+        } else if config
+            .max_term_size
+            .is_some_and(|max| var_terms.iter().any(|term| term.size() > max))
+        {
+            Some(UnknownReason::TermSizeExceeded)
+        } else {
+            None
+        };
+        if let Some(reason) = limit {
+            // The instances generated so far may still be contradictory
+            return match solve_modulo_equality(
+                &mut solver,
+                &mut rel_to_var,
+                equality,
+                &mut lemmas,
+                stats,
+                config,
+            ) {
+                Ok(false) => valid!(),
+                Ok(true) => unknown!(reason),
+                Err(reason) => unknown!(reason),
+            };
+        }
+        let clause = &clauses[clause_idx];
+        let interp = clause
+            .vars
+            .iter()
+            .copied()
+            .zip(var_terms.iter().cloned())
+            .collect();
+        let pl_clause = rel_to_var.clause(clause, &interp);
+        instances.push((clause_idx, var_terms));
+        stats.instances += 1;
+        if !pl::clause_is_tautology(&pl_clause) {
+            solver.add_clause(&pl_clause);
+        }
 
         prefix_size += 1;
         if prefix_size == next_prefix_size_to_check {
-            if !is_satisfiable(ecnf_prefix.clone()) {
-                return true;
+            match solve_modulo_equality(
+                &mut solver,
+                &mut rel_to_var,
+                equality,
+                &mut lemmas,
+                stats,
+                config,
+            ) {
+                Ok(false) => return valid!(),
+                Ok(true) => {}
+                Err(reason) => return unknown!(reason),
             }
-            if prefix_size / vars.len() > 1_000 {
-                return false;
+            // Interleave the search for a refutation with the search for a finite countermodel
+            if instance_count(next_domain_size, &vars).is_some_and(|count| {
+                count.saturating_mul(MODEL_FINDER_INSTANCE_RATIO) <= prefix_size
+            }) {
+                stats.model_size = next_domain_size;
+                match find_model_of_size(&vars, &formula, next_domain_size, equality, config) {
+                    Ok(Some(model)) => return Verdict::Invalid(Some(model)),
+                    Ok(None) => {}
+                    Err(reason) => return unknown!(reason),
+                }
+                next_domain_size += 1;
             }
-            next_prefix_size_to_check = next_prefix_size_to_check * 3;
-            last_checked_prefix_size = prefix_size;
+            next_prefix_size_to_check *= 3;
         }
     }
-    if prefix_size == last_checked_prefix_size {
-        return false;
+    if config.deadline_passed() {
+        // The instances by levels may have ended for the deadline
+        return unknown!(UnknownReason::Timeout);
+    }
+    // The Herbrand universe is finite, so the whole expansion has been generated
+    match solve_modulo_equality(
+        &mut solver,
+        &mut rel_to_var,
+        equality,
+        &mut lemmas,
+        stats,
+        config,
+    ) {
+        Ok(false) => return valid!(),
+        Ok(true) => {}
+        Err(reason) => return unknown!(reason),
+    }
+    let constant = match constant {
+        Some(constant) => constant,
+        // The Herbrand model interprets every sort, and a `Model` has a single domain
+        None => return Verdict::Invalid(None),
+    };
+    if func_sig(&formula).iter().any(|(_, arity)| *arity > 0) {
+        // Only a ground formula is fully expanded over an infinite universe, and it has a model
+        // no larger than its number of subterms
+        let mut size = next_domain_size;
+        loop {
+            stats.model_size = size;
+            match find_model_of_size(&vars, &formula, size, equality, config) {
+                Ok(Some(model)) => return Verdict::Invalid(Some(model)),
+                Ok(None) => size += 1,
+                Err(reason) => return unknown!(reason),
+            }
+        }
     }
-    !is_satisfiable(ecnf_prefix)
+    let universe = herbrand_universe(func_sig(&formula), constant).collect::<Vec<_>>();
+    Verdict::Invalid(Some(rel_to_var.herbrand_model(
+        &universe,
+        &solver.model(),
+        equality,
+    )))
 }
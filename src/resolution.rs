@@ -0,0 +1,477 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::mem;
+
+use crate::{
+    cnf::{clausify, Literal},
+    fol::{self, skolemize, Fun, NameAllocator, Rel, Sort, Term},
+    prover::{drop_in_background, remove_universal_prefix, ProverConfig, UnknownReason},
+    substitution::Substitution,
+};
+
+/// Every `AGE_RATIO`-th given clause is the oldest passive clause instead of the lightest one,
+/// which makes the given clause loop fair.
+const AGE_RATIO: usize = 5;
+
+/// The saturation gives up once this many clauses have been kept.
+const MAX_CLAUSES: usize = 1_000_000;
+
+/// Disjunction of literals whose variables are implicitly universally quantified.
+type Clause = Vec<Literal>;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub(crate) enum Outcome {
+    /// The empty clause was derived from the clausal form of the negation: the formula is valid.
+    Refuted,
+    /// The clause set is saturated without the empty clause: the formula is not valid.
+    Saturated,
+    /// The prover gave up before reaching either conclusion.
+    Unknown(UnknownReason),
+}
+
+fn unify_literals(a: &Literal, b: &Literal, subst: &mut Substitution) -> bool {
+    a.rel == b.rel
+        && a.args.len() == b.args.len()
+        && a.args.iter().zip(&b.args).all(|(x, y)| subst.unify(x, y))
+}
+
+fn apply_literal(literal: &Literal, subst: &Substitution) -> Literal {
+    Literal {
+        positive: literal.positive,
+        rel: literal.rel,
+        args: literal.args.iter().map(|arg| subst.apply(arg)).collect(),
+    }
+}
+
+/// Checks whether some instance of the literals `c` (extending `subst`) is a subset of `d`.
+fn subsumes_with(c: &[Literal], d: &[Literal], subst: &Substitution) -> bool {
+    match c.split_first() {
+        None => true,
+        Some((literal, rest)) => d.iter().any(|other| {
+            let mut subst = subst.clone();
+            literal.positive == other.positive
+                && literal.rel == other.rel
+                && literal.args.len() == other.args.len()
+                && literal
+                    .args
+                    .iter()
+                    .zip(&other.args)
+                    .all(|(x, y)| subst.match_term(x, y))
+                && subsumes_with(rest, d, &subst)
+        }),
+    }
+}
+
+fn subsumes(c: &[Literal], d: &[Literal]) -> bool {
+    c.len() <= d.len() && subsumes_with(c, d, &Substitution::new())
+}
+
+fn is_tautological(clause: &[Literal]) -> bool {
+    clause.iter().any(|a| {
+        a.positive
+            && clause
+                .iter()
+                .any(|b| !b.positive && a.rel == b.rel && a.args == b.args)
+    })
+}
+
+/// Renames the variables of `clause` to the variables allocated after the first `skip` ones, in
+/// order of first occurrence.
+fn rename(clause: &[Literal], skip: usize) -> Clause {
+    fn rename_term(
+        term: &Term,
+        renaming: &mut HashMap<fol::Var, fol::Var>,
+        var_alloc: &mut NameAllocator<fol::Var>,
+    ) -> Term {
+        match term {
+            Term::Var(var) => Term::Var(
+                *renaming
+                    .entry(*var)
+                    .or_insert_with(|| var_alloc.alloc().with_sort(var.sort())),
+            ),
+            Term::Fun(fun, args) => Term::Fun(
+                *fun,
+                args.iter()
+                    .map(|arg| rename_term(arg, renaming, var_alloc))
+                    .collect(),
+            ),
+        }
+    }
+    let mut var_alloc = NameAllocator::default();
+    for _ in 0..skip {
+        var_alloc.alloc();
+    }
+    let mut renaming = HashMap::new();
+    clause
+        .iter()
+        .map(|literal| Literal {
+            positive: literal.positive,
+            rel: literal.rel,
+            args: literal
+                .args
+                .iter()
+                .map(|arg| rename_term(arg, &mut renaming, &mut var_alloc))
+                .collect(),
+        })
+        .collect()
+}
+
+/// Removes duplicate literals and renames the variables canonically.
+fn normalize(clause: Clause) -> Clause {
+    let mut res = Clause::new();
+    for literal in clause {
+        if !res.contains(&literal) {
+            res.push(literal);
+        }
+    }
+    rename(&res, 0)
+}
+
+/// Number of variables of a normalized clause.
+fn var_count(clause: &[Literal]) -> usize {
+    fn max_index(term: &Term) -> Option<usize> {
+        match term {
+            Term::Var(var) => Some(var.index()),
+            Term::Fun(_, args) => args.iter().filter_map(max_index).max(),
+        }
+    }
+    clause
+        .iter()
+        .flat_map(|literal| literal.args.iter().filter_map(max_index))
+        .max()
+        .map_or(0, |index| index + 1)
+}
+
+fn weight(clause: &[Literal]) -> usize {
+    fn term_weight(term: &Term) -> usize {
+        match term {
+            Term::Var(_) => 1,
+            Term::Fun(_, args) => 1 + args.iter().map(term_weight).sum::<usize>(),
+        }
+    }
+    clause
+        .iter()
+        .map(|literal| 1 + literal.args.iter().map(term_weight).sum::<usize>())
+        .sum()
+}
+
+/// Factors of `clause`: instances by the most general unifier of two of its literals of the same
+/// sign, with the second one removed.
+fn factors(clause: &[Literal]) -> Vec<Clause> {
+    let mut res = Vec::new();
+    for (i, a) in clause.iter().enumerate() {
+        for (j, b) in clause.iter().enumerate().skip(i + 1) {
+            let mut subst = Substitution::new();
+            if a.positive == b.positive && unify_literals(a, b, &mut subst) {
+                res.push(
+                    clause
+                        .iter()
+                        .enumerate()
+                        .filter(|(k, _)| *k != j)
+                        .map(|(_, literal)| apply_literal(literal, &subst))
+                        .collect(),
+                );
+            }
+        }
+    }
+    res
+}
+
+/// Binary resolvents of the normalized clauses `a` and `b`.
+fn resolvents(a: &[Literal], b: &[Literal]) -> Vec<Clause> {
+    let b = rename(b, var_count(a));
+    let mut res = Vec::new();
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            let mut subst = Substitution::new();
+            if x.positive != y.positive && unify_literals(x, y, &mut subst) {
+                res.push(
+                    a.iter()
+                        .enumerate()
+                        .filter(|(k, _)| *k != i)
+                        .chain(b.iter().enumerate().filter(|(k, _)| *k != j))
+                        .map(|(_, literal)| apply_literal(literal, &subst))
+                        .collect(),
+                );
+            }
+        }
+    }
+    res
+}
+
+#[derive(Default)]
+struct Saturation {
+    clauses: Vec<Clause>,
+    active: Vec<usize>,
+    /// Passive clauses by weight and by age (the age of a clause is its idx)
+    by_weight: BTreeSet<(usize, usize)>,
+    by_age: BTreeSet<usize>,
+    given_count: usize,
+}
+
+impl Saturation {
+    /// Adds `clause` to the passive clauses unless it is redundant. Returns whether it is empty.
+    fn add(&mut self, clause: Clause) -> bool {
+        let clause = normalize(clause);
+        if clause.is_empty() {
+            return true;
+        }
+        if is_tautological(&clause) {
+            return false;
+        }
+        let idx = self.clauses.len();
+        self.by_weight.insert((weight(&clause), idx));
+        self.by_age.insert(idx);
+        self.clauses.push(clause);
+        false
+    }
+
+    fn select(&mut self) -> Option<usize> {
+        self.given_count += 1;
+        let idx = if self.given_count.is_multiple_of(AGE_RATIO) {
+            self.by_age.iter().next().copied()?
+        } else {
+            self.by_weight.iter().next()?.1
+        };
+        self.by_age.remove(&idx);
+        self.by_weight.remove(&(weight(&self.clauses[idx]), idx));
+        Some(idx)
+    }
+
+    fn saturate(&mut self, config: &ProverConfig) -> Outcome {
+        // Checked at each step over the active clauses too, as there can be many of them
+        macro_rules! check_deadline {
+            () => {
+                if config.deadline_passed() {
+                    return Outcome::Unknown(UnknownReason::Timeout);
+                }
+            };
+        }
+        'given: while let Some(given_idx) = self.select() {
+            if self.clauses.len() > MAX_CLAUSES {
+                return Outcome::Unknown(UnknownReason::ClauseBudgetExhausted);
+            }
+            check_deadline!();
+            let given = self.clauses[given_idx].clone();
+            for idx in &self.active {
+                check_deadline!();
+                if subsumes(&self.clauses[*idx], &given) {
+                    continue 'given;
+                }
+            }
+            let mut active = Vec::with_capacity(self.active.len() + 1);
+            for idx in mem::take(&mut self.active) {
+                check_deadline!();
+                if !subsumes(&given, &self.clauses[idx]) {
+                    active.push(idx);
+                }
+            }
+            active.push(given_idx);
+            self.active = active;
+            let mut inferred = factors(&given);
+            for idx in &self.active {
+                check_deadline!();
+                inferred.extend(resolvents(&given, &self.clauses[*idx]));
+            }
+            for clause in inferred {
+                check_deadline!();
+                if self.add(clause) {
+                    return Outcome::Refuted;
+                }
+            }
+        }
+        Outcome::Saturated
+    }
+}
+
+/// Reflexivity, symmetry and transitivity of `equality` in each sort, and congruence for the
+/// function and relation symbols of `clauses`, as they are applied in them.
+pub(crate) fn equality_axioms(equality: Rel, clauses: &[Clause]) -> Vec<Clause> {
+    fn fun_sig(term: &Term, sig: &mut HashSet<(Fun, Vec<Sort>)>, sorts: &mut BTreeSet<Sort>) {
+        sorts.insert(term.sort());
+        if let Term::Fun(fun, args) = term {
+            sig.insert((*fun, args.iter().map(Term::sort).collect()));
+            for arg in args {
+                fun_sig(arg, sig, sorts);
+            }
+        }
+    }
+    let mut funs = HashSet::new();
+    let mut rels = HashSet::new();
+    let mut sorts = BTreeSet::new();
+    for literal in clauses.iter().flatten() {
+        rels.insert((
+            literal.rel,
+            literal.args.iter().map(Term::sort).collect::<Vec<_>>(),
+        ));
+        for arg in &literal.args {
+            fun_sig(arg, &mut funs, &mut sorts);
+        }
+    }
+    let mut var_alloc = NameAllocator::default();
+    let max_arity = funs
+        .iter()
+        .map(|(_, args)| args.len())
+        .chain(rels.iter().map(|(_, args)| args.len()))
+        .max();
+    let xs = (0..max_arity.unwrap_or(0).max(3))
+        .map(|_| var_alloc.alloc())
+        .collect::<Vec<fol::Var>>();
+    let ys = (0..xs.len())
+        .map(|_| var_alloc.alloc())
+        .collect::<Vec<fol::Var>>();
+    // The variables of `vars` with the sorts `sorts`
+    let sorted = |vars: &[fol::Var], sorts: &[Sort]| {
+        vars.iter()
+            .zip(sorts)
+            .map(|(var, sort)| Term::Var(var.with_sort(*sort)))
+            .collect::<Vec<_>>()
+    };
+    let literal = |positive, rel, args: &[Term]| Literal {
+        positive,
+        rel,
+        args: args.to_vec(),
+    };
+    let equal = |positive, x: &Term, y: &Term| literal(positive, equality, &[x.clone(), y.clone()]);
+    // The premises x_1 = y_1, ..., x_n = y_n of the congruence axioms
+    let premises = |xs: &[Term], ys: &[Term]| {
+        xs.iter()
+            .zip(ys)
+            .map(|(x, y)| equal(false, x, y))
+            .collect::<Clause>()
+    };
+    let mut res = Vec::new();
+    for sort in sorts {
+        let xs = sorted(&xs, &[sort; 3]);
+        let (x, y, z) = (&xs[0], &xs[1], &xs[2]);
+        res.extend(vec![
+            vec![equal(true, x, x)],
+            vec![equal(false, x, y), equal(true, y, x)],
+            vec![equal(false, x, y), equal(false, y, z), equal(true, x, z)],
+        ]);
+    }
+    for (fun, args) in funs {
+        let (xs, ys) = (sorted(&xs, &args), sorted(&ys, &args));
+        let mut clause = premises(&xs, &ys);
+        clause.push(equal(true, &Term::Fun(fun, xs), &Term::Fun(fun, ys)));
+        res.push(clause);
+    }
+    for (rel, args) in rels {
+        if rel != equality {
+            let (xs, ys) = (sorted(&xs, &args), sorted(&ys, &args));
+            let mut clause = premises(&xs, &ys);
+            clause.push(literal(false, rel, &xs));
+            clause.push(literal(true, rel, &ys));
+            res.push(clause);
+        }
+    }
+    res
+}
+
+/// Decides `formula`, in which `equality` (if any) is interpreted as equality, by saturating the
+/// clausal form of its skolemized negation under binary resolution and factoring, with a given
+/// clause loop. Equality is axiomatized. Only the deadline of `config` applies.
+pub(crate) fn is_tautology(
+    formula: fol::Formula,
+    fun_alloc: &mut NameAllocator<Fun>,
+    rel_alloc: &mut NameAllocator<Rel>,
+    equality: Option<Rel>,
+    config: &ProverConfig,
+) -> Outcome {
+    let formula = skolemize(fol::Formula::Not(Box::new(formula)), fun_alloc);
+    let (_, matrix) = remove_universal_prefix(formula);
+    let mut clauses = clausify(&matrix, rel_alloc)
+        .into_iter()
+        .map(|clause| clause.literals)
+        .collect::<Vec<_>>();
+    if let Some(equality) = equality {
+        clauses.extend(equality_axioms(equality, &clauses));
+    }
+    let mut saturation = Saturation::default();
+    for clause in clauses {
+        if saturation.add(clause) {
+            return Outcome::Refuted;
+        }
+    }
+    let outcome = saturation.saturate(config);
+    if let Outcome::Unknown(_) = outcome {
+        drop_in_background(saturation);
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fol::builders::{exists, forall, implies};
+    use crate::fol::{Formula, NameAllocator, Term};
+    use crate::prover::ProverConfig;
+
+    use super::{is_tautology, Outcome};
+
+    #[test]
+    fn drinker_paradox() {
+        let mut var_alloc = NameAllocator::default();
+        let (x, y) = (var_alloc.alloc(), var_alloc.alloc());
+        let mut rel_alloc = NameAllocator::default();
+        let d = rel_alloc.alloc();
+        let drinks = |var| Formula::Rel(d, vec![Term::Var(var)]);
+        // Exists x. (D(x) -> Forall y. D(y))
+        let formula = exists(x, implies(drinks(x), forall(y, drinks(y))));
+        assert_eq!(
+            is_tautology(
+                formula,
+                &mut NameAllocator::default(),
+                &mut rel_alloc,
+                None,
+                &ProverConfig::default()
+            ),
+            Outcome::Refuted
+        );
+    }
+
+    #[test]
+    fn factoring_is_needed() {
+        let mut var_alloc = NameAllocator::default();
+        let (x, y) = (var_alloc.alloc(), var_alloc.alloc());
+        let mut rel_alloc = NameAllocator::default();
+        let p = rel_alloc.alloc();
+        let rel = |var| Box::new(Formula::Rel(p, vec![Term::Var(var)]));
+        // (Forall x, y. P(x) or P(y)) -> Exists x, y. P(x) and P(y)
+        let formula = implies(
+            forall(x, forall(y, Formula::Or(rel(x), rel(y)))),
+            exists(x, exists(y, Formula::And(rel(x), rel(y)))),
+        );
+        assert_eq!(
+            is_tautology(
+                formula,
+                &mut NameAllocator::default(),
+                &mut rel_alloc,
+                None,
+                &ProverConfig::default()
+            ),
+            Outcome::Refuted
+        );
+    }
+
+    #[test]
+    fn saturates_non_theorems() {
+        let x = NameAllocator::default().alloc();
+        let mut rel_alloc = NameAllocator::default();
+        let (p, q) = (rel_alloc.alloc(), rel_alloc.alloc());
+        let rel = |rel, var| Formula::Rel(rel, vec![Term::Var(var)]);
+        // (Forall x. P(x) -> Q(x)) -> Forall x. Q(x) -> P(x)
+        let formula = implies(
+            forall(x, implies(rel(p, x), rel(q, x))),
+            forall(x, implies(rel(q, x), rel(p, x))),
+        );
+        assert_eq!(
+            is_tautology(
+                formula,
+                &mut NameAllocator::default(),
+                &mut rel_alloc,
+                None,
+                &ProverConfig::default()
+            ),
+            Outcome::Saturated
+        );
+    }
+}
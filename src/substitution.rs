@@ -0,0 +1,235 @@
+use std::{collections::HashMap, iter::FromIterator};
+
+use crate::fol::{Term, Var};
+
+/// A simultaneous substitution of terms for variables.
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub struct Substitution {
+    map: HashMap<Var, Term>,
+}
+
+impl FromIterator<(Var, Term)> for Substitution {
+    fn from_iter<I: IntoIterator<Item = (Var, Term)>>(iter: I) -> Self {
+        Self {
+            map: iter.into_iter().collect(),
+        }
+    }
+}
+
+fn occurs(var: Var, term: &Term) -> bool {
+    match term {
+        Term::Var(other) => var == *other,
+        Term::Fun(_, args) => args.iter().any(|arg| occurs(var, arg)),
+    }
+}
+
+impl Substitution {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, var: Var) -> Option<&Term> {
+        self.map.get(&var)
+    }
+
+    pub fn apply(&self, term: &Term) -> Term {
+        match term {
+            Term::Var(var) => self.map.get(var).cloned().unwrap_or(Term::Var(*var)),
+            Term::Fun(fun, args) => {
+                Term::Fun(*fun, args.iter().map(|arg| self.apply(arg)).collect())
+            }
+        }
+    }
+
+    /// The substitution applying `self` and then `other`.
+    pub fn compose(&self, other: &Substitution) -> Substitution {
+        let mut map = self
+            .map
+            .iter()
+            .map(|(var, term)| (*var, other.apply(term)))
+            .filter(|(var, term)| *term != Term::Var(*var))
+            .collect::<HashMap<_, _>>();
+        for (var, term) in &other.map {
+            map.entry(*var).or_insert_with(|| term.clone());
+        }
+        Substitution { map }
+    }
+
+    /// Binds `var`, which must not occur in `self`, and keeps `self` idempotent.
+    fn bind(&mut self, var: Var, term: Term) {
+        let binding = vec![(var, term.clone())]
+            .into_iter()
+            .collect::<Substitution>();
+        for value in self.map.values_mut() {
+            *value = binding.apply(value);
+        }
+        self.map.insert(var, term);
+    }
+
+    /// Extends the idempotent substitution `self` to a most general unifier of `self(a)` and
+    /// `self(b)`, binding variables only to terms of their sort. On failure `self` is left
+    /// partially extended.
+    pub fn unify(&mut self, a: &Term, b: &Term) -> bool {
+        match (self.apply(a), self.apply(b)) {
+            (Term::Var(x), Term::Var(y)) if x == y => true,
+            (Term::Var(var), term) | (term, Term::Var(var)) => {
+                if var.sort() != term.sort() || occurs(var, &term) {
+                    false
+                } else {
+                    self.bind(var, term);
+                    true
+                }
+            }
+            (Term::Fun(f, xs), Term::Fun(g, ys)) => {
+                f == g && xs.len() == ys.len() && xs.iter().zip(&ys).all(|(x, y)| self.unify(x, y))
+            }
+        }
+    }
+
+    /// Extends `self` so that it maps `pattern` to `term`, treating the variables of `term` as
+    /// constants. On failure `self` is left partially extended.
+    pub fn match_term(&mut self, pattern: &Term, term: &Term) -> bool {
+        match (pattern, term) {
+            (Term::Var(var), _) => match self.map.get(var) {
+                Some(bound) => bound == term,
+                None if var.sort() != term.sort() => false,
+                None => {
+                    self.map.insert(*var, term.clone());
+                    true
+                }
+            },
+            (Term::Fun(f, xs), Term::Fun(g, ys)) => {
+                f == g
+                    && xs.len() == ys.len()
+                    && xs.iter().zip(ys).all(|(x, y)| self.match_term(x, y))
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Most general unifier of `a` and `b`, if they are unifiable.
+pub fn mgu(a: &Term, b: &Term) -> Option<Substitution> {
+    let mut subst = Substitution::new();
+    if subst.unify(a, b) {
+        Some(subst)
+    } else {
+        None
+    }
+}
+
+/// Substitution mapping `pattern` to `term`, if `term` is an instance of `pattern`.
+pub fn matching(pattern: &Term, term: &Term) -> Option<Substitution> {
+    let mut subst = Substitution::new();
+    if subst.match_term(pattern, term) {
+        Some(subst)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fol::{Fun, NameAllocator, Term, Var};
+
+    use super::{matching, mgu, Substitution};
+
+    struct Symbols {
+        x: Term,
+        y: Term,
+        z: Term,
+        f: Fun,
+        g: Fun,
+        c: Term,
+    }
+
+    impl Symbols {
+        fn new() -> Self {
+            let mut var_alloc = NameAllocator::<Var>::default();
+            let mut fun_alloc = NameAllocator::<Fun>::default();
+            Self {
+                x: Term::Var(var_alloc.alloc()),
+                y: Term::Var(var_alloc.alloc()),
+                z: Term::Var(var_alloc.alloc()),
+                f: fun_alloc.alloc(),
+                g: fun_alloc.alloc(),
+                c: Term::Fun(fun_alloc.alloc(), vec![]),
+            }
+        }
+
+        fn f(&self, args: Vec<Term>) -> Term {
+            Term::Fun(self.f, args)
+        }
+
+        fn g(&self, args: Vec<Term>) -> Term {
+            Term::Fun(self.g, args)
+        }
+    }
+
+    fn var(term: &Term) -> Var {
+        match term {
+            Term::Var(var) => *var,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn most_general_unifier() {
+        let s = Symbols::new();
+        // f(x, g(y)) = f(g(z), x)
+        let a = s.f(vec![s.x.clone(), s.g(vec![s.y.clone()])]);
+        let b = s.f(vec![s.g(vec![s.z.clone()]), s.x.clone()]);
+        let subst = mgu(&a, &b).unwrap();
+        assert_eq!(subst.apply(&a), subst.apply(&b));
+        // Any other unifier, e.g. {x -> g(c), y -> c, z -> c}, is an instance of it
+        let ground = vec![(var(&s.y), s.c.clone()), (var(&s.z), s.c.clone())]
+            .into_iter()
+            .collect::<Substitution>();
+        let unifier = subst.compose(&ground);
+        assert_eq!(unifier.apply(&a), unifier.apply(&b));
+        assert_eq!(
+            unifier.apply(&a),
+            s.f(vec![s.g(vec![s.c.clone()]), s.g(vec![s.c.clone()])])
+        );
+    }
+
+    #[test]
+    fn occurs_check() {
+        let s = Symbols::new();
+        assert!(mgu(&s.x, &s.f(vec![s.x.clone()])).is_none());
+        assert!(mgu(
+            &s.f(vec![s.x.clone(), s.y.clone()]),
+            &s.f(vec![s.y.clone(), s.g(vec![s.x.clone()])])
+        )
+        .is_none());
+        assert!(mgu(&s.f(vec![s.x.clone()]), &s.g(vec![s.x.clone()])).is_none());
+        assert_eq!(mgu(&s.x, &s.x), Some(Substitution::new()));
+    }
+
+    #[test]
+    fn composition_applies_in_order() {
+        let s = Symbols::new();
+        let first = vec![(var(&s.x), s.f(vec![s.y.clone()]))]
+            .into_iter()
+            .collect::<Substitution>();
+        let second = vec![(var(&s.y), s.c.clone()), (var(&s.x), s.z.clone())]
+            .into_iter()
+            .collect::<Substitution>();
+        let composed = first.compose(&second);
+        for term in [&s.x, &s.y, &s.z] {
+            assert_eq!(composed.apply(term), second.apply(&first.apply(term)));
+        }
+    }
+
+    #[test]
+    fn one_way_matching() {
+        let s = Symbols::new();
+        let pattern = s.f(vec![s.x.clone(), s.x.clone()]);
+        let term = s.f(vec![s.g(vec![s.y.clone()]), s.g(vec![s.y.clone()])]);
+        let subst = matching(&pattern, &term).unwrap();
+        assert_eq!(subst.apply(&pattern), term);
+        // Variables of the term are not instantiated
+        assert!(matching(&term, &pattern).is_none());
+        assert!(matching(&pattern, &s.f(vec![s.y.clone(), s.z.clone()])).is_none());
+    }
+}
@@ -0,0 +1,449 @@
+use std::collections::{HashMap, HashSet};
+
+use parser::formula::{Formula, Term};
+use parser::signature::{Sorts, INDIVIDUAL};
+
+use crate::fol::{self, Fun, NameAllocator, NextName, Rel, Sort, Var};
+
+/// The name of the relation interpreted as equality.
+pub const EQUALITY: &str = "=";
+
+/// Names the symbols of `fol::Formula`s: translates formulas of the parser, whose symbols are
+/// strings, and allocates the symbols of formulas built directly. Variables and functions are
+/// annotated with the sorts they are declared with, `INDIVIDUAL` being the default sort.
+#[derive(Default)]
+pub struct Translator {
+    sort_alloc: NameAllocator<Sort>,
+    sort_names: HashMap<String, Sort>,
+    declared: Sorts,
+    var_alloc: NameAllocator<Var>,
+    vars: HashMap<String, Var>,
+    fun_alloc: NameAllocator<Fun>,
+    funs: HashMap<String, Fun>,
+    rel_alloc: NameAllocator<Rel>,
+    rels: HashMap<String, Rel>,
+}
+
+impl Translator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The sort `name`, allocated on its first use. `INDIVIDUAL` is the default sort.
+    pub fn sort(&mut self, name: &str) -> Sort {
+        if name == INDIVIDUAL {
+            return Sort::default();
+        }
+        let alloc = &mut self.sort_alloc;
+        // The default sort is not allocated
+        *self
+            .sort_names
+            .entry(name.to_string())
+            .or_insert_with(|| alloc.alloc().next_name())
+    }
+
+    /// Declares the sorts of the symbols and variables of the formulas to translate, which must
+    /// be used before any of them.
+    pub fn declare(&mut self, sorts: &Sorts) {
+        for sort in &sorts.sorts {
+            self.sort(sort);
+        }
+        self.declared = sorts.clone();
+    }
+
+    /// Checks that the arguments of the functions and relations of `formula` have the sorts
+    /// these are declared with, and that the sides of an equality have the same sort. Undeclared
+    /// symbols take and give `INDIVIDUAL`s.
+    pub fn check_sorts(&self, formula: &Formula) -> Result<(), String> {
+        match formula {
+            Formula::True | Formula::False => Ok(()),
+            Formula::Rel(name, args) if name == EQUALITY && args.len() == 2 => {
+                let (a, b) = (self.term_sort(&args[0])?, self.term_sort(&args[1])?);
+                if a != b {
+                    return Err(format!(
+                        "the sides of an equality have the sorts `{}` and `{}`",
+                        a, b
+                    ));
+                }
+                Ok(())
+            }
+            Formula::Rel(name, args) => {
+                self.check_args(name, self.declared.rels.get(name).map(Vec::as_slice), args)
+            }
+            Formula::Not(phi) | Formula::Exists(_, phi) | Formula::Forall(_, phi) => {
+                self.check_sorts(phi)
+            }
+            Formula::Or(a, b)
+            | Formula::And(a, b)
+            | Formula::Implies(a, b)
+            | Formula::Iff(a, b) => {
+                self.check_sorts(a)?;
+                self.check_sorts(b)
+            }
+        }
+    }
+
+    /// The sort of `term`, whose arguments are checked.
+    fn term_sort(&self, term: &Term) -> Result<&str, String> {
+        match term {
+            Term::Var(name) => Ok(self
+                .declared
+                .vars
+                .get(name)
+                .map_or(INDIVIDUAL, String::as_str)),
+            Term::Fun(name, args) => match self.declared.funs.get(name) {
+                Some((arg_sorts, sort)) => {
+                    self.check_args(name, Some(arg_sorts), args)?;
+                    Ok(sort)
+                }
+                None => {
+                    self.check_args(name, None, args)?;
+                    Ok(INDIVIDUAL)
+                }
+            },
+        }
+    }
+
+    /// Checks that `args` have the sorts `arg_sorts` of the arguments of `name`, or are all
+    /// `INDIVIDUAL`s if it is not declared.
+    fn check_args(
+        &self,
+        name: &str,
+        arg_sorts: Option<&[String]>,
+        args: &[Term],
+    ) -> Result<(), String> {
+        if arg_sorts.is_some_and(|arg_sorts| arg_sorts.len() != args.len()) {
+            return Err(format!(
+                "`{}` is declared with {} arguments but applied to {}",
+                name,
+                arg_sorts.unwrap().len(),
+                args.len()
+            ));
+        }
+        for (idx, arg) in args.iter().enumerate() {
+            let expected = arg_sorts.map_or(INDIVIDUAL, |arg_sorts| arg_sorts[idx].as_str());
+            let sort = self.term_sort(arg)?;
+            if sort != expected {
+                return Err(format!(
+                    "argument {} of `{}` has the sort `{}` instead of `{}`",
+                    idx + 1,
+                    name,
+                    sort,
+                    expected
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The variable `name` free in the translated formulas, allocated on its first use.
+    pub fn var(&mut self, name: &str) -> Var {
+        self.translate_var(name.to_string())
+    }
+
+    /// The function `name`, allocated on its first use.
+    pub fn fun(&mut self, name: &str) -> Fun {
+        self.translate_fun(name.to_string())
+    }
+
+    /// The relation `name`, allocated on its first use. The relation `EQUALITY` is interpreted as
+    /// equality.
+    pub fn rel(&mut self, name: &str) -> Rel {
+        self.translate_rel(name.to_string())
+    }
+
+    /// The functions used so far, with their names.
+    pub fn funs(&self) -> impl Iterator<Item = (&String, Fun)> {
+        self.funs.iter().map(|(name, fun)| (name, *fun))
+    }
+
+    /// The relations used so far, with their names.
+    pub fn rels(&self) -> impl Iterator<Item = (&String, Rel)> {
+        self.rels.iter().map(|(name, rel)| (name, *rel))
+    }
+
+    pub fn find_fun(&self, name: &str) -> Option<Fun> {
+        self.funs.get(name).copied()
+    }
+
+    pub fn find_rel(&self, name: &str) -> Option<Rel> {
+        self.rels.get(name).copied()
+    }
+
+    /// The relation interpreted as equality, if it is used.
+    pub fn equality(&self) -> Option<Rel> {
+        self.find_rel(EQUALITY)
+    }
+
+    /// The allocator of the functions, from which Skolem functions are allocated.
+    pub fn fun_alloc(&mut self) -> &mut NameAllocator<Fun> {
+        &mut self.fun_alloc
+    }
+
+    /// The allocators of the functions and the relations, from which Skolem functions and the
+    /// relations defining parts of the clausal form are allocated.
+    pub(crate) fn fresh_symbols(&mut self) -> (&mut NameAllocator<Fun>, &mut NameAllocator<Rel>) {
+        (&mut self.fun_alloc, &mut self.rel_alloc)
+    }
+
+    fn translate_var(&mut self, var: String) -> Var {
+        let sort = match self.declared.vars.get(&var) {
+            Some(sort) => self.sort(&sort.clone()),
+            None => Sort::default(),
+        };
+        let alloc = &mut self.var_alloc;
+        *self
+            .vars
+            .entry(var)
+            .or_insert_with(|| alloc.alloc().with_sort(sort))
+    }
+
+    fn translate_fun(&mut self, fun: String) -> Fun {
+        let sort = match self.declared.funs.get(&fun) {
+            Some((_, sort)) => self.sort(&sort.clone()),
+            None => Sort::default(),
+        };
+        let alloc = &mut self.fun_alloc;
+        *self
+            .funs
+            .entry(fun)
+            .or_insert_with(|| alloc.alloc().with_sort(sort))
+    }
+
+    fn translate_rel(&mut self, rel: String) -> Rel {
+        let alloc = &mut self.rel_alloc;
+        *self.rels.entry(rel).or_insert_with(|| alloc.alloc())
+    }
+
+    fn translate_term(&mut self, term: Term) -> fol::Term {
+        match term {
+            Term::Var(name) => fol::Term::Var(self.translate_var(name)),
+            Term::Fun(name, terms) => fol::Term::Fun(
+                self.translate_fun(name),
+                terms.into_iter().map(|x| self.translate_term(x)).collect(),
+            ),
+        }
+    }
+
+    /// Translates a formula of the parser. Bound variables with the same name are distinct
+    /// variables, and so are they from the free variables.
+    pub fn translate(&mut self, formula: Formula) -> fol::Formula {
+        match formula {
+            Formula::True => fol::Formula::True,
+            Formula::False => fol::Formula::False,
+            Formula::Rel(name, terms) => fol::Formula::Rel(
+                self.translate_rel(name),
+                terms.into_iter().map(|x| self.translate_term(x)).collect(),
+            ),
+            Formula::Not(phi) => fol::Formula::Not(Box::new(self.translate(*phi))),
+            Formula::Or(phi, psi) => fol::Formula::Or(
+                Box::new(self.translate(*phi)),
+                Box::new(self.translate(*psi)),
+            ),
+            Formula::And(phi, psi) => fol::Formula::And(
+                Box::new(self.translate(*phi)),
+                Box::new(self.translate(*psi)),
+            ),
+            Formula::Implies(phi, psi) => fol::Formula::Implies(
+                Box::new(self.translate(*phi)),
+                Box::new(self.translate(*psi)),
+            ),
+            Formula::Iff(phi, psi) => fol::Formula::Iff(
+                Box::new(self.translate(*phi)),
+                Box::new(self.translate(*psi)),
+            ),
+            Formula::Exists(var, phi) => {
+                let old_mapping = self.vars.remove_entry(&var);
+                let res = fol::Formula::Exists(
+                    self.translate_var(var.clone()),
+                    Box::new(self.translate(*phi)),
+                );
+                match old_mapping {
+                    Some((k, v)) => {
+                        self.vars.insert(k, v);
+                    }
+                    None => {
+                        self.vars.remove(&var);
+                    }
+                };
+                res
+            }
+            Formula::Forall(var, phi) => {
+                let old_mapping = self.vars.remove_entry(&var);
+                let res = fol::Formula::Forall(
+                    self.translate_var(var.clone()),
+                    Box::new(self.translate(*phi)),
+                );
+                match old_mapping {
+                    Some((k, v)) => {
+                        self.vars.insert(k, v);
+                    }
+                    None => {
+                        self.vars.remove(&var);
+                    }
+                };
+                res
+            }
+        }
+    }
+}
+
+/// Names the symbols of `fol::Formula`s back as in the input of a `Translator`. Other functions
+/// are Skolem functions `sk<n>`, other relations are definitions `def<n>` and variables are
+/// `v<n>`, primed until they differ from the names in the input.
+pub struct Namer<'a> {
+    funs: HashMap<Fun, &'a String>,
+    rels: HashMap<Rel, &'a String>,
+    taken: HashSet<&'a String>,
+}
+
+impl<'a> Namer<'a> {
+    pub fn new(translator: &'a Translator) -> Self {
+        Self {
+            funs: translator
+                .funs
+                .iter()
+                .map(|(name, fun)| (*fun, name))
+                .collect(),
+            rels: translator
+                .rels
+                .iter()
+                .map(|(name, rel)| (*rel, name))
+                .collect(),
+            taken: translator
+                .funs
+                .keys()
+                .chain(translator.rels.keys())
+                .collect(),
+        }
+    }
+
+    fn fresh(&self, mut name: String) -> String {
+        while self.taken.contains(&name) {
+            name.push('\'');
+        }
+        name
+    }
+
+    fn var(&self, var: Var) -> String {
+        self.fresh(format!("v{}", var.index()))
+    }
+
+    pub fn term(&self, term: &fol::Term) -> Term {
+        match term {
+            fol::Term::Var(var) => Term::Var(self.var(*var)),
+            fol::Term::Fun(fun, args) => Term::Fun(
+                match self.funs.get(fun) {
+                    Some(name) => name.to_string(),
+                    None => self.fresh(format!("sk{}", fun.index())),
+                },
+                args.iter().map(|arg| self.term(arg)).collect(),
+            ),
+        }
+    }
+
+    pub fn formula(&self, formula: &fol::Formula) -> Formula {
+        let binary = |a: &fol::Formula, b: &fol::Formula| {
+            (Box::new(self.formula(a)), Box::new(self.formula(b)))
+        };
+        match formula {
+            fol::Formula::True => Formula::True,
+            fol::Formula::False => Formula::False,
+            fol::Formula::Rel(rel, args) => Formula::Rel(
+                match self.rels.get(rel) {
+                    Some(name) => name.to_string(),
+                    None => self.fresh(format!("def{}", rel.index())),
+                },
+                args.iter().map(|arg| self.term(arg)).collect(),
+            ),
+            fol::Formula::Not(phi) => Formula::Not(Box::new(self.formula(phi))),
+            fol::Formula::Or(a, b) => {
+                let (a, b) = binary(a, b);
+                Formula::Or(a, b)
+            }
+            fol::Formula::And(a, b) => {
+                let (a, b) = binary(a, b);
+                Formula::And(a, b)
+            }
+            fol::Formula::Implies(a, b) => {
+                let (a, b) = binary(a, b);
+                Formula::Implies(a, b)
+            }
+            fol::Formula::Iff(a, b) => {
+                let (a, b) = binary(a, b);
+                Formula::Iff(a, b)
+            }
+            fol::Formula::Exists(var, phi) => {
+                Formula::Exists(self.var(*var), Box::new(self.formula(phi)))
+            }
+            fol::Formula::Forall(var, phi) => {
+                Formula::Forall(self.var(*var), Box::new(self.formula(phi)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use parser::signature::Signature;
+    use parser::tptp::parse_tptp_with;
+
+    use super::Translator;
+    use crate::fol::{self, Sort};
+
+    #[test]
+    fn checks_and_annotates_sorts() {
+        let translate = |input: &str| {
+            let mut signature = Signature::new();
+            let problem = parse_tptp_with(input, Path::new("."), &mut signature).unwrap();
+            let mut translator = Translator::new();
+            translator.declare(signature.sorts());
+            let formula = problem.into_formula();
+            translator.check_sorts(&formula)?;
+            Ok::<_, String>((translator.translate(*formula), translator))
+        };
+        let declarations = "tff(s_type, type, s: $tType).\n\
+                            tff(a_type, type, a: s).\n\
+                            tff(f_type, type, f: s > $i).\n\
+                            tff(p_type, type, p: (s * $i) > $o).\n";
+        let (formula, mut translator) = translate(&format!(
+            "{}tff(ax, axiom, ![X: s]: p(X, f(X))).",
+            declarations
+        ))
+        .unwrap();
+        let s = translator.sort("s");
+        assert_ne!(s, Sort::default());
+        assert_eq!(translator.fun("a").sort(), s);
+        assert_eq!(translator.find_fun("f").unwrap().sort(), Sort::default());
+        match formula {
+            fol::Formula::Implies(axiom, _) => match *axiom {
+                fol::Formula::Forall(x, _) => assert_eq!(x.sort(), s),
+                formula => panic!("unexpected {:?}", formula),
+            },
+            formula => panic!("unexpected {:?}", formula),
+        }
+        // The variables of the clause and the free variables are not those bound with the sort s
+        let (_, mut translator) = translate(&format!(
+            "{}tff(ax, axiom, ![X: s]: p(X, f(X))).\ncnf(c, axiom, p(a, X) | q(X)).",
+            declarations
+        ))
+        .unwrap();
+        assert_eq!(translator.var("X").sort(), Sort::default());
+        for (axiom, message) in [
+            (
+                "p(f(a), a)",
+                "argument 1 of `p` has the sort `$i` instead of `s`",
+            ),
+            (
+                "a = f(a)",
+                "the sides of an equality have the sorts `s` and `$i`",
+            ),
+            ("q(a)", "argument 1 of `q` has the sort `s` instead of `$i`"),
+        ] {
+            let input = format!("{}tff(ax, axiom, {}).", declarations, axiom);
+            assert_eq!(translate(&input).err().unwrap(), message);
+        }
+    }
+}
@@ -154,7 +154,7 @@ mod tests {
                 // Correct elements
                 let mut prev_sum = 0;
                 for tuple in v.iter() {
-                    let sum = tuple.iter().fold(0, |acc, x| acc + x);
+                    let sum = tuple.iter().sum::<usize>();
                     assert!(prev_sum <= sum);
                     prev_sum = sum;
                     let sum = sum as i64;